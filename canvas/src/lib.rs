@@ -18,13 +18,18 @@ use pathfinder_geometry::basic::point::Point2DF32;
 use pathfinder_geometry::basic::rect::RectF32;
 use pathfinder_geometry::basic::transform2d::Transform2DF32;
 use pathfinder_geometry::color::ColorU;
+use pathfinder_geometry::fill::FillRule;
 use pathfinder_geometry::outline::{Contour, Outline};
+use pathfinder_geometry::path_data::PathDataError;
 use pathfinder_geometry::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
-use pathfinder_renderer::paint::Paint;
-use pathfinder_renderer::scene::{PathObject, Scene};
+use pathfinder_geometry::util;
+use pathfinder_renderer::blend::BlendMode;
+use pathfinder_renderer::paint::{Gradient, Image, Paint, Pattern, RepeatMode};
+use pathfinder_renderer::scene::{ClipPath, ClipPathId, PathObject, Scene};
 use pathfinder_text::{SceneExt, TextRenderMode};
 use skribo::{FontCollection, FontFamily, TextStyle};
 use std::default::Default;
+use std::f32::consts::PI;
 use std::mem;
 use std::sync::Arc;
 
@@ -82,7 +87,7 @@ impl CanvasRenderingContext2D {
     pub fn fill_rect(&mut self, rect: RectF32) {
         let mut path = Path2D::new();
         path.rect(rect);
-        self.fill_path(path);
+        self.fill_path(path, FillRule::Winding);
     }
 
     #[inline]
@@ -92,18 +97,38 @@ impl CanvasRenderingContext2D {
         self.stroke_path(path);
     }
 
+    /// Draws the image stretched to fill `dest_rect`.
+    pub fn draw_image(&mut self, image: &Image, dest_rect: RectF32) {
+        let image_size = image.size().to_f32();
+        let scale = Point2DF32::new(dest_rect.size().x() / image_size.x(),
+                                    dest_rect.size().y() / image_size.y());
+        let mut pattern = Pattern::new(image.clone(), RepeatMode::NoRepeat);
+        pattern.set_transform(Transform2DF32::from_scale(scale).post_mul(
+            &Transform2DF32::from_translation(dest_rect.origin())));
+
+        let mut path = Path2D::new();
+        path.rect(dest_rect);
+
+        let paint = self.current_state.resolve_paint(Paint::Pattern(pattern));
+        let paint_id = self.scene.push_paint(&paint);
+        self.push_path(PathObject::new(path.into_outline(), paint_id, String::new()))
+    }
+
     pub fn fill_text(&mut self, string: &str, position: Point2DF32) {
         // TODO(pcwalton): Report errors.
         let paint_id = self.scene.push_paint(&self.current_state.fill_paint);
         let transform = Transform2DF32::from_translation(position).post_mul(&self.current_state
                                                                                  .transform);
+        self.push_compositing_group(false);
         drop(self.scene.push_text(string,
                                   &TextStyle { size: self.current_state.font_size },
                                   &self.current_state.font_collection,
                                   &transform,
                                   TextRenderMode::Fill,
                                   HintingOptions::None,
-                                  paint_id));
+                                  paint_id,
+                                  self.current_state.clip_path));
+        self.pop_compositing_group(false);
     }
 
     pub fn stroke_text(&mut self, string: &str, position: Point2DF32) {
@@ -111,13 +136,16 @@ impl CanvasRenderingContext2D {
         let paint_id = self.scene.push_paint(&self.current_state.stroke_paint);
         let transform = Transform2DF32::from_translation(position).post_mul(&self.current_state
                                                                                  .transform);
+        self.push_compositing_group(false);
         drop(self.scene.push_text(string,
                                   &TextStyle { size: self.current_state.font_size },
                                   &self.current_state.font_collection,
                                   &transform,
-                                  TextRenderMode::Stroke(self.current_state.stroke_style),
+                                  TextRenderMode::Stroke(self.current_state.stroke_style.clone()),
                                   HintingOptions::None,
-                                  paint_id));
+                                  paint_id,
+                                  self.current_state.clip_path));
+        self.pop_compositing_group(false);
     }
 
     // Line styles
@@ -137,6 +165,24 @@ impl CanvasRenderingContext2D {
         self.current_state.stroke_style.line_join = new_line_join
     }
 
+    #[inline]
+    pub fn set_miter_limit(&mut self, new_miter_limit: f32) {
+        self.current_state.stroke_style.miter_limit = new_miter_limit
+    }
+
+    /// Sets the dash pattern, as alternating dash and gap lengths. As in HTML canvas, a pattern
+    /// with negative or non-finite lengths is ignored.
+    pub fn set_line_dash(&mut self, new_line_dash: Vec<f32>) {
+        if new_line_dash.iter().all(|length| length.is_finite() && *length >= 0.0) {
+            self.current_state.stroke_style.dash_array = new_line_dash.into()
+        }
+    }
+
+    #[inline]
+    pub fn set_line_dash_offset(&mut self, new_line_dash_offset: f32) {
+        self.current_state.stroke_style.dash_offset = new_line_dash_offset
+    }
+
     #[inline]
     pub fn set_fill_style(&mut self, new_fill_style: FillStyle) {
         self.current_state.fill_paint = new_fill_style.to_paint();
@@ -147,6 +193,11 @@ impl CanvasRenderingContext2D {
         self.current_state.stroke_paint = new_stroke_style.to_paint();
     }
 
+    #[inline]
+    pub fn create_pattern(&self, image: &Image, repeat_mode: RepeatMode) -> Pattern {
+        Pattern::new(image.clone(), repeat_mode)
+    }
+
     // Text styles
 
     #[inline]
@@ -157,28 +208,84 @@ impl CanvasRenderingContext2D {
     // Drawing paths
 
     #[inline]
-    pub fn fill_path(&mut self, path: Path2D) {
-        let mut outline = path.into_outline();
-        outline.transform(&self.current_state.transform);
-
-        let paint = self.current_state.resolve_paint(self.current_state.fill_paint);
+    pub fn fill_path(&mut self, path: Path2D, fill_rule: FillRule) {
+        let paint = self.current_state.resolve_paint(self.current_state.fill_paint.clone());
         let paint_id = self.scene.push_paint(&paint);
 
-        self.scene.push_path(PathObject::new(outline, paint_id, String::new()))
+        let mut path = PathObject::new(path.into_outline(), paint_id, String::new());
+        path.set_fill_rule(fill_rule);
+        self.push_path(path)
     }
 
     #[inline]
     pub fn stroke_path(&mut self, path: Path2D) {
-        let paint = self.current_state.resolve_paint(self.current_state.stroke_paint);
+        let paint = self.current_state.resolve_paint(self.current_state.stroke_paint.clone());
         let paint_id = self.scene.push_paint(&paint);
 
-        let mut stroke_style = self.current_state.stroke_style;
+        let mut stroke_style = self.current_state.stroke_style.clone();
         stroke_style.line_width = f32::max(stroke_style.line_width, HAIRLINE_STROKE_WIDTH);
 
         let mut stroke_to_fill = OutlineStrokeToFill::new(path.into_outline(), stroke_style);
         stroke_to_fill.offset();
-        stroke_to_fill.outline.transform(&self.current_state.transform);
-        self.scene.push_path(PathObject::new(stroke_to_fill.outline, paint_id, String::new()))
+        self.push_path(PathObject::new(stroke_to_fill.outline, paint_id, String::new()))
+    }
+
+    /// Returns true if `point`, in canvas coordinates, is inside the path as it would be filled
+    /// with the current transform.
+    pub fn is_point_in_path(&self, path: &Path2D, point: Point2DF32, fill_rule: FillRule)
+                            -> bool {
+        let point = self.current_state.transform.inverse().transform_point(point);
+        path.clone().into_outline().contains_point(point, fill_rule)
+    }
+
+    /// Returns true if `point`, in canvas coordinates, is inside the path as it would be stroked
+    /// with the current stroke style and transform.
+    pub fn is_point_in_stroke(&self, path: &Path2D, point: Point2DF32) -> bool {
+        let mut stroke_style = self.current_state.stroke_style.clone();
+        stroke_style.line_width = f32::max(stroke_style.line_width, HAIRLINE_STROKE_WIDTH);
+
+        let point = self.current_state.transform.inverse().transform_point(point);
+        path.clone().into_outline().stroke_contains_point(point, &stroke_style)
+    }
+
+    /// Clips everything drawn afterward, until the state is restored, to the intersection of the
+    /// path and the current clip.
+    pub fn clip_path(&mut self, path: Path2D, fill_rule: FillRule) {
+        let mut outline = path.into_outline();
+        outline.transform(&self.current_state.transform);
+        let mut clip_path = ClipPath::new(outline, fill_rule);
+        if let Some(current_clip_path) = self.current_state.clip_path {
+            let current_clip_path = &self.scene.clip_paths()[current_clip_path.0 as usize];
+            clip_path = current_clip_path.intersection(&clip_path);
+        }
+        self.current_state.clip_path = Some(self.scene.push_clip_path(clip_path));
+    }
+
+    // Places the path with the current transform, which the renderer applies, so that the outline
+    // itself stays in user space.
+    fn push_path(&mut self, mut path: PathObject) {
+        path.set_transform(self.current_state.transform);
+        path.set_clip_path(self.current_state.clip_path);
+        self.push_compositing_group(true);
+        self.scene.push_path(path);
+        self.pop_compositing_group(true);
+    }
+
+    // The global alpha and composite operation apply to everything a drawing operation draws
+    // as a whole. A single path drawn with source-over has the global alpha folded into its paint
+    // by `resolve_paint`; anything else is composited as a group of its own.
+    fn push_compositing_group(&mut self, single_path: bool) {
+        if self.current_state.needs_compositing_group(single_path) {
+            let group = self.scene.push_group(self.current_state.global_alpha);
+            let blend_mode = self.current_state.global_composite_operation.to_blend_mode();
+            self.scene.group_mut(group).set_blend_mode(blend_mode);
+        }
+    }
+
+    fn pop_compositing_group(&mut self, single_path: bool) {
+        if self.current_state.needs_compositing_group(single_path) {
+            self.scene.pop_group();
+        }
     }
 
     // Transformations
@@ -210,6 +317,17 @@ impl CanvasRenderingContext2D {
         self.current_state.global_alpha = new_global_alpha;
     }
 
+    #[inline]
+    pub fn global_composite_operation(&self) -> CompositeOperation {
+        self.current_state.global_composite_operation
+    }
+
+    #[inline]
+    pub fn set_global_composite_operation(&mut self,
+                                          new_composite_operation: CompositeOperation) {
+        self.current_state.global_composite_operation = new_composite_operation;
+    }
+
     // The canvas state
 
     #[inline]
@@ -234,6 +352,8 @@ pub struct State {
     stroke_paint: Paint,
     stroke_style: StrokeStyle,
     global_alpha: f32,
+    global_composite_operation: CompositeOperation,
+    clip_path: Option<ClipPathId>,
 }
 
 impl State {
@@ -242,15 +362,26 @@ impl State {
             transform: Transform2DF32::default(),
             font_collection: default_font_collection,
             font_size: DEFAULT_FONT_SIZE,
-            fill_paint: Paint { color: ColorU::black() },
-            stroke_paint: Paint { color: ColorU::black() },
+            fill_paint: Paint::Color(ColorU::black()),
+            stroke_paint: Paint::Color(ColorU::black()),
             stroke_style: StrokeStyle::default(),
             global_alpha: 1.0,
+            global_composite_operation: CompositeOperation::SourceOver,
+            clip_path: None,
         }
     }
 
+    #[inline]
+    fn needs_compositing_group(&self, single_path: bool) -> bool {
+        self.global_composite_operation != CompositeOperation::SourceOver ||
+            (!single_path && self.global_alpha < 1.0)
+    }
+
     fn resolve_paint(&self, mut paint: Paint) -> Paint {
-        paint.color.a = (paint.color.a as f32 * self.global_alpha).round() as u8;
+        paint.apply_transform(&self.transform);
+        if self.global_composite_operation == CompositeOperation::SourceOver {
+            paint.apply_opacity(self.global_alpha);
+        }
         paint
     }
 }
@@ -261,13 +392,19 @@ pub struct Path2D {
     current_contour: Contour,
 }
 
-// TODO(pcwalton): `ellipse`
 impl Path2D {
     #[inline]
     pub fn new() -> Path2D {
         Path2D { outline: Outline::new(), current_contour: Contour::new() }
     }
 
+    /// Creates a path from SVG path data, like the `Path2D` constructor that takes a string.
+    #[inline]
+    pub fn from_svg_path_data(data: &str) -> Result<Path2D, PathDataError> {
+        let outline = Outline::from_svg_path_data(data)?;
+        Ok(Path2D { outline, current_contour: Contour::new() })
+    }
+
     #[inline]
     pub fn close_path(&mut self) {
         self.current_contour.close();
@@ -300,6 +437,59 @@ impl Path2D {
         self.current_contour.push_arc(center, radius, start_angle, end_angle);
     }
 
+    /// Adds a circular arc tangent to the line from the last point to `ctrl` and the line from
+    /// `ctrl` to `to`, joined to the last point by a straight line.
+    pub fn arc_to(&mut self, ctrl: Point2DF32, to: Point2DF32, radius: f32) {
+        let from = match self.current_contour.last_position() {
+            None => return self.current_contour.push_endpoint(ctrl),
+            Some(from) => from,
+        };
+
+        let (vector0, vector1) = (from - ctrl, to - ctrl);
+        let det = vector0.det(vector1);
+        if radius <= 0.0 || vector0.is_zero() || vector1.is_zero() || det == 0.0 {
+            return self.current_contour.push_endpoint(ctrl);
+        }
+
+        // The center lies on the bisector of the corner, at the distance where it's `radius` away
+        // from both lines.
+        let (direction0, direction1) = (vector0.normalize(), vector1.normalize());
+        let half_angle = 0.5 * f32::acos(util::clamp(direction0.dot(direction1), -1.0, 1.0));
+        let bisector = (direction0 + direction1).normalize();
+        let center = ctrl + bisector.scale(radius / half_angle.sin());
+        let start = ctrl + direction0.scale(radius / half_angle.tan());
+        let start_vector = start - center;
+
+        let mut sweep_angle = PI - 2.0 * half_angle;
+        if det > 0.0 {
+            sweep_angle = -sweep_angle;
+        }
+        self.current_contour.push_ellipse_arc(center,
+                                              Point2DF32::splat(radius),
+                                              0.0,
+                                              f32::atan2(start_vector.y(), start_vector.x()),
+                                              sweep_angle);
+    }
+
+    /// Adds an arc of an ellipse with the given radii, rotated by `rotation`, joined to the last
+    /// point by a straight line. Angles are measured before rotation, and the arc is a full
+    /// ellipse if they're at least a full turn apart in the given direction.
+    pub fn ellipse(&mut self,
+                   center: Point2DF32,
+                   axes: Point2DF32,
+                   rotation: f32,
+                   start_angle: f32,
+                   end_angle: f32,
+                   direction: ArcDirection) {
+        let sweep_angle = match direction {
+            ArcDirection::CW if end_angle - start_angle >= 2.0 * PI => 2.0 * PI,
+            ArcDirection::CCW if start_angle - end_angle >= 2.0 * PI => -2.0 * PI,
+            ArcDirection::CW => (end_angle - start_angle).rem_euclid(2.0 * PI),
+            ArcDirection::CCW => -(start_angle - end_angle).rem_euclid(2.0 * PI),
+        };
+        self.current_contour.push_ellipse_arc(center, axes, rotation, start_angle, sweep_angle);
+    }
+
     pub fn rect(&mut self, rect: RectF32) {
         self.flush_current_contour();
         self.current_contour.push_endpoint(rect.origin());
@@ -321,15 +511,95 @@ impl Path2D {
     }
 }
 
-// TODO(pcwalton): Gradients.
-#[derive(Clone, Copy)]
+/// The direction in which an arc goes, in a coordinate system where positive y is down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArcDirection {
+    /// Clockwise, from the positive x axis toward the positive y axis.
+    CW,
+    /// Counterclockwise, from the positive x axis toward the negative y axis.
+    CCW,
+}
+
+#[derive(Clone)]
 pub enum FillStyle {
     Color(ColorU),
+    /// A gradient, in the coordinate space of the transform that is current when it is drawn.
+    Gradient(Gradient),
+    /// A pattern, in the coordinate space of the transform that is current when it is drawn.
+    Pattern(Pattern),
 }
 
 impl FillStyle {
     #[inline]
     fn to_paint(&self) -> Paint {
-        match *self { FillStyle::Color(color) => Paint { color } }
+        match *self {
+            FillStyle::Color(color) => Paint::Color(color),
+            FillStyle::Gradient(ref gradient) => Paint::Gradient(gradient.clone()),
+            FillStyle::Pattern(ref pattern) => Paint::Pattern(pattern.clone()),
+        }
+    }
+}
+
+/// The values of `globalCompositeOperation`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompositeOperation {
+    SourceOver,
+    SourceIn,
+    SourceOut,
+    SourceAtop,
+    DestinationOver,
+    DestinationIn,
+    DestinationOut,
+    DestinationAtop,
+    Lighter,
+    Copy,
+    Xor,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl CompositeOperation {
+    fn to_blend_mode(self) -> BlendMode {
+        match self {
+            CompositeOperation::SourceOver => BlendMode::SrcOver,
+            CompositeOperation::SourceIn => BlendMode::SrcIn,
+            CompositeOperation::SourceOut => BlendMode::SrcOut,
+            CompositeOperation::SourceAtop => BlendMode::SrcAtop,
+            CompositeOperation::DestinationOver => BlendMode::DestOver,
+            CompositeOperation::DestinationIn => BlendMode::DestIn,
+            CompositeOperation::DestinationOut => BlendMode::DestOut,
+            CompositeOperation::DestinationAtop => BlendMode::DestAtop,
+            CompositeOperation::Lighter => BlendMode::Lighter,
+            CompositeOperation::Copy => BlendMode::Copy,
+            CompositeOperation::Xor => BlendMode::Xor,
+            CompositeOperation::Multiply => BlendMode::Multiply,
+            CompositeOperation::Screen => BlendMode::Screen,
+            CompositeOperation::Overlay => BlendMode::Overlay,
+            CompositeOperation::Darken => BlendMode::Darken,
+            CompositeOperation::Lighten => BlendMode::Lighten,
+            CompositeOperation::ColorDodge => BlendMode::ColorDodge,
+            CompositeOperation::ColorBurn => BlendMode::ColorBurn,
+            CompositeOperation::HardLight => BlendMode::HardLight,
+            CompositeOperation::SoftLight => BlendMode::SoftLight,
+            CompositeOperation::Difference => BlendMode::Difference,
+            CompositeOperation::Exclusion => BlendMode::Exclusion,
+            CompositeOperation::Hue => BlendMode::Hue,
+            CompositeOperation::Saturation => BlendMode::Saturation,
+            CompositeOperation::Color => BlendMode::Color,
+            CompositeOperation::Luminosity => BlendMode::Luminosity,
+        }
     }
 }
@@ -248,6 +248,7 @@ impl<W> DemoApp<W> where W: Window {
                 Point2DF32::default()
             },
             subpixel_aa_enabled: self.ui_model.subpixel_aa_effect_enabled,
+            ..RenderOptions::default()
         };
 
         self.render_command_stream = Some(self.scene_proxy.build_with_stream(render_options));
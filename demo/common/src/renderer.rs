@@ -298,7 +298,9 @@ impl<W> DemoApp<W> where W: Window {
             .unwrap()
             .scene_stats
             .push(self.renderer.stats);
-        self.renderer.end_scene();
+        if let Err(error) = self.renderer.end_scene() {
+            error!("failed to render the scene: {}", error);
+        }
     }
 
     pub fn take_raster_screenshot(&mut self, path: PathBuf) {
@@ -139,7 +139,7 @@ impl MoireRenderer {
 
         // Build and render scene.
         self.scene.replace_scene(canvas.into_scene());
-        self.scene.build_and_render(&mut self.renderer, RenderOptions::default());
+        self.scene.build_and_render(&mut self.renderer, RenderOptions::default()).unwrap();
 
         self.frame += 1;
     }
@@ -62,7 +62,7 @@ fn main() {
 
     // Render the canvas to screen.
     let scene = SceneProxy::from_scene(canvas.into_scene(), RayonExecutor);
-    scene.build_and_render(&mut renderer, RenderOptions::default());
+    scene.build_and_render(&mut renderer, RenderOptions::default()).unwrap();
     window.gl_swap_window();
 
     // Wait for a keypress.
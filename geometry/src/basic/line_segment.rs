@@ -14,11 +14,26 @@ use crate::basic::point::Point2DF32;
 use crate::basic::transform2d::Matrix2x2F32;
 use crate::util;
 use pathfinder_simd::default::F32x4;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::ops::{Add, Sub};
 
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub struct LineSegmentF32(pub F32x4);
 
+impl Serialize for LineSegmentF32 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        (self.from(), self.to()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for LineSegmentF32 {
+    fn deserialize<D>(deserializer: D) -> Result<LineSegmentF32, D::Error>
+                      where D: Deserializer<'de> {
+        let (from, to) = Deserialize::deserialize(deserializer)?;
+        Ok(LineSegmentF32::new(from, to))
+    }
+}
+
 impl LineSegmentF32 {
     #[inline]
     pub fn new(from: Point2DF32, to: Point2DF32) -> LineSegmentF32 {
@@ -279,10 +294,10 @@ impl Sub<Point2DF32> for LineSegmentF32 {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
 #[repr(transparent)]
 pub struct LineSegmentU4(pub u16);
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
 #[repr(transparent)]
 pub struct LineSegmentU8(pub u32);
@@ -11,12 +11,26 @@
 //! A SIMD-optimized point type.
 
 use pathfinder_simd::default::{F32x4, I32x4};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::ops::{Add, AddAssign, Mul, Neg, Sub};
 
 /// 2D points with 32-bit floating point coordinates.
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Point2DF32(pub F32x4);
 
+impl Serialize for Point2DF32 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        (self.x(), self.y()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Point2DF32 {
+    fn deserialize<D>(deserializer: D) -> Result<Point2DF32, D::Error> where D: Deserializer<'de> {
+        let (x, y) = Deserialize::deserialize(deserializer)?;
+        Ok(Point2DF32::new(x, y))
+    }
+}
+
 impl Point2DF32 {
     #[inline]
     pub fn new(x: f32, y: f32) -> Point2DF32 {
@@ -184,6 +198,19 @@ impl Neg for Point2DF32 {
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Point2DI32(pub I32x4);
 
+impl Serialize for Point2DI32 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        (self.x(), self.y()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Point2DI32 {
+    fn deserialize<D>(deserializer: D) -> Result<Point2DI32, D::Error> where D: Deserializer<'de> {
+        let (x, y) = Deserialize::deserialize(deserializer)?;
+        Ok(Point2DI32::new(x, y))
+    }
+}
+
 impl Point2DI32 {
     #[inline]
     pub fn new(x: i32, y: i32) -> Point2DI32 {
@@ -266,6 +293,19 @@ impl PartialEq for Point2DI32 {
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Point3DF32(pub F32x4);
 
+impl Serialize for Point3DF32 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        (self.x(), self.y(), self.z(), self.w()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Point3DF32 {
+    fn deserialize<D>(deserializer: D) -> Result<Point3DF32, D::Error> where D: Deserializer<'de> {
+        let (x, y, z, w) = Deserialize::deserialize(deserializer)?;
+        Ok(Point3DF32::new(x, y, z, w))
+    }
+}
+
 impl Point3DF32 {
     #[inline]
     pub fn new(x: f32, y: f32, z: f32, w: f32) -> Point3DF32 {
@@ -12,10 +12,24 @@
 
 use crate::basic::point::{Point2DF32, Point2DI32};
 use pathfinder_simd::default::{F32x4, I32x4};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub struct RectF32(pub F32x4);
 
+impl Serialize for RectF32 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        (self.origin(), self.lower_right()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RectF32 {
+    fn deserialize<D>(deserializer: D) -> Result<RectF32, D::Error> where D: Deserializer<'de> {
+        let (origin, lower_right) = Deserialize::deserialize(deserializer)?;
+        Ok(RectF32::from_points(origin, lower_right))
+    }
+}
+
 impl RectF32 {
     #[inline]
     pub fn new(origin: Point2DF32, size: Point2DF32) -> RectF32 {
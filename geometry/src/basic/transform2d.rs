@@ -16,6 +16,7 @@ use crate::basic::rect::RectF32;
 use crate::basic::transform3d::Transform3DF32;
 use crate::segment::Segment;
 use pathfinder_simd::default::F32x4;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::ops::Sub;
 
 /// A 2x2 matrix, optimized with SIMD, in column-major order.
@@ -116,6 +117,21 @@ pub struct Transform2DF32 {
     vector: Point2DF32,
 }
 
+impl Serialize for Transform2DF32 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        (self.m11(), self.m12(), self.m21(), self.m22(), self.vector).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Transform2DF32 {
+    fn deserialize<D>(deserializer: D) -> Result<Transform2DF32, D::Error>
+                      where D: Deserializer<'de> {
+        let (m11, m12, m21, m22, vector): (f32, f32, f32, f32, Point2DF32) =
+            Deserialize::deserialize(deserializer)?;
+        Ok(Transform2DF32::row_major(m11, m12, m21, m22, vector.x(), vector.y()))
+    }
+}
+
 impl Default for Transform2DF32 {
     #[inline]
     fn default() -> Transform2DF32 {
@@ -186,9 +202,10 @@ impl Transform2DF32 {
         RectF32::from_points(min_point, max_point)
     }
 
+    /// Returns the transform that applies this transform and then `other`.
     #[inline]
     pub fn post_mul(&self, other: &Transform2DF32) -> Transform2DF32 {
-        let matrix = self.matrix.post_mul(&other.matrix);
+        let matrix = other.matrix.post_mul(&self.matrix);
         let vector = other.transform_point(self.vector);
         Transform2DF32 { matrix, vector }
     }
@@ -226,6 +243,13 @@ impl Transform2DF32 {
         *self == Transform2DF32::default()
     }
 
+    #[inline]
+    pub fn inverse(&self) -> Transform2DF32 {
+        let matrix = self.matrix.inverse();
+        let vector = -matrix.transform_point(self.vector);
+        Transform2DF32 { matrix, vector }
+    }
+
     #[inline]
     pub fn m11(&self) -> f32 {
         self.matrix.m11()
@@ -336,3 +360,29 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::basic::point::Point2DF32;
+    use crate::basic::transform2d::Transform2DF32;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_post_mul() {
+        // Scaling and then rotating doesn't commute, so this catches multiplying the matrices in
+        // the wrong order.
+        let scale = Transform2DF32::from_scale(Point2DF32::new(2.0, 1.0));
+        let rotation = Transform2DF32::from_rotation(FRAC_PI_2);
+        let translation = Transform2DF32::from_translation(Point2DF32::new(5.0, 7.0));
+        let transform = scale.post_mul(&rotation).post_mul(&translation);
+        for &point in &[Point2DF32::new(1.0, 0.0), Point2DF32::new(3.0, -4.0)] {
+            let expected = translation.transform_point(rotation.transform_point(
+                scale.transform_point(point)));
+            let actual = transform.transform_point(point);
+            assert!((actual - expected).length() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+        let point = scale.post_mul(&rotation).transform_point(Point2DF32::new(1.0, 0.0));
+        assert!((point - Point2DF32::new(0.0, 2.0)).length() < 1e-5);
+        assert_eq!(rotation.pre_mul(&scale), scale.post_mul(&rotation));
+    }
+}
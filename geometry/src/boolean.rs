@@ -0,0 +1,545 @@
+// pathfinder/geometry/src/boolean.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Boolean operations on outlines: union, intersection, difference and exclusive or.
+//!
+//! Every segment of both operands is split wherever it crosses another segment. Each resulting
+//! piece is kept if the result of the operation differs on its two sides, and it is oriented so
+//! that the result lies on its left. The pieces are then linked back up into contours.
+//!
+//! Lines that coincide along a stretch are handled, but curves that do may leave slivers.
+
+use crate::basic::line_segment::LineSegmentF32;
+use crate::basic::point::Point2DF32;
+use crate::basic::rect::RectF32;
+use crate::fill::FillRule;
+use crate::outline::{Contour, Outline};
+use crate::segment::{Segment, SegmentFlags};
+use crate::util;
+
+// Tolerances, as fractions of the size of the operands.
+const INTERSECTION_TOLERANCE: f32 = 1.0e-5;
+const SNAP_TOLERANCE: f32 = 1.0e-4;
+const SIDE_OFFSET: f32 = 1.0e-4;
+
+const MAX_SUBDIVISIONS: u32 = 24;
+
+/// An operation combining two outlines into one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BooleanOp {
+    /// The area inside either outline.
+    Union,
+    /// The area inside both outlines.
+    Intersection,
+    /// The area inside the first outline but not the second.
+    Difference,
+    /// The area inside exactly one of the outlines.
+    Xor,
+}
+
+impl BooleanOp {
+    #[inline]
+    fn apply(self, inside_a: bool, inside_b: bool) -> bool {
+        match self {
+            BooleanOp::Union => inside_a || inside_b,
+            BooleanOp::Intersection => inside_a && inside_b,
+            BooleanOp::Difference => inside_a && !inside_b,
+            BooleanOp::Xor => inside_a != inside_b,
+        }
+    }
+}
+
+impl Outline {
+    #[inline]
+    pub fn union(&self, other: &Outline, fill_rule: FillRule) -> Outline {
+        self.boolean_op(other, BooleanOp::Union, fill_rule)
+    }
+
+    #[inline]
+    pub fn intersection(&self, other: &Outline, fill_rule: FillRule) -> Outline {
+        self.boolean_op(other, BooleanOp::Intersection, fill_rule)
+    }
+
+    #[inline]
+    pub fn difference(&self, other: &Outline, fill_rule: FillRule) -> Outline {
+        self.boolean_op(other, BooleanOp::Difference, fill_rule)
+    }
+
+    #[inline]
+    pub fn xor(&self, other: &Outline, fill_rule: FillRule) -> Outline {
+        self.boolean_op(other, BooleanOp::Xor, fill_rule)
+    }
+
+    /// Combines this outline with `other`, both of which are interpreted according to
+    /// `fill_rule`.
+    ///
+    /// The contours of the result don't cross one another and all wind the same way around the
+    /// area they enclose, so the result fills identically under either fill rule.
+    pub fn boolean_op(&self, other: &Outline, op: BooleanOp, fill_rule: FillRule) -> Outline {
+        BooleanOperation::new(self, other, op, fill_rule).run()
+    }
+}
+
+struct BooleanOperation {
+    operands: [Vec<Segment>; 2],
+    op: BooleanOp,
+    fill_rule: FillRule,
+    intersection_tolerance: f32,
+    snap_tolerance: f32,
+    side_offset: f32,
+}
+
+struct Edge {
+    segment: Segment,
+    bounds: RectF32,
+    // The parameters and positions of the points where this edge is to be split.
+    splits: Vec<(f32, Point2DF32)>,
+}
+
+impl BooleanOperation {
+    fn new(a: &Outline, b: &Outline, op: BooleanOp, fill_rule: FillRule) -> BooleanOperation {
+        let size = a.bounds().union_rect(b.bounds()).size();
+        let size = f32::max(size.x(), size.y());
+        BooleanOperation {
+            operands: [collect_segments(a), collect_segments(b)],
+            op,
+            fill_rule,
+            intersection_tolerance: size * INTERSECTION_TOLERANCE,
+            snap_tolerance: size * SNAP_TOLERANCE,
+            side_offset: size * SIDE_OFFSET,
+        }
+    }
+
+    fn run(self) -> Outline {
+        let mut edges: Vec<Edge> = self.operands
+                                       .iter()
+                                       .flat_map(|segments| segments.iter())
+                                       .map(|segment| Edge::new(*segment))
+                                       .collect();
+        self.find_intersections(&mut edges);
+
+        let mut fragments = vec![];
+        for edge in &edges {
+            self.split_edge(edge, &mut fragments);
+        }
+
+        let mut kept_fragments: Vec<Segment> = vec![];
+        for fragment in fragments {
+            if let Some(fragment) = self.classify(&fragment) {
+                if !kept_fragments.iter().any(|kept| self.fragments_coincide(kept, &fragment)) {
+                    kept_fragments.push(fragment);
+                }
+            }
+        }
+
+        self.link(kept_fragments)
+    }
+
+    fn find_intersections(&self, edges: &mut [Edge]) {
+        let mut intersections = vec![];
+        for index_a in 0..edges.len() {
+            for index_b in (index_a + 1)..edges.len() {
+                let (edge_a, edge_b) = (&edges[index_a], &edges[index_b]);
+                let tolerance = Point2DF32::splat(self.intersection_tolerance);
+                if !edge_a.bounds.dilate(tolerance).intersects(edge_b.bounds.dilate(tolerance)) {
+                    continue;
+                }
+
+                intersections.clear();
+                intersect_segments(&edge_a.segment,
+                                   &edge_b.segment,
+                                   self.intersection_tolerance,
+                                   &mut intersections);
+
+                for &(t_a, t_b) in &intersections {
+                    let point = self.intersection_point(&edges[index_a].segment,
+                                                        t_a,
+                                                        &edges[index_b].segment,
+                                                        t_b);
+                    edges[index_a].splits.push((t_a, point));
+                    edges[index_b].splits.push((t_b, point));
+                }
+            }
+        }
+    }
+
+    // Picks a single position for an intersection, so that the pieces of both edges meet
+    // exactly. Existing endpoints take precedence.
+    fn intersection_point(&self, a: &Segment, t_a: f32, b: &Segment, t_b: f32) -> Point2DF32 {
+        let point = a.sample(t_a);
+        for &endpoint in &[a.baseline.from(), a.baseline.to(), b.baseline.from(), b.baseline.to()] {
+            if (endpoint - point).length() <= self.snap_tolerance {
+                return endpoint;
+            }
+        }
+        point.lerp(b.sample(t_b), 0.5)
+    }
+
+    fn split_edge(&self, edge: &Edge, fragments: &mut Vec<Segment>) {
+        let mut splits = edge.splits.clone();
+        splits.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        let (mut rest, mut rest_start_t) = (edge.segment, 0.0);
+        for &(t, point) in &splits {
+            if (point - rest.baseline.from()).length() <= self.snap_tolerance ||
+                    (point - rest.baseline.to()).length() <= self.snap_tolerance ||
+                    t >= 1.0 {
+                continue;
+            }
+
+            let (mut before, mut after) = rest.split((t - rest_start_t) / (1.0 - rest_start_t));
+            before.baseline.set_to(&point);
+            after.baseline.set_from(&point);
+            fragments.push(before);
+            rest = after;
+            rest_start_t = t;
+        }
+        fragments.push(rest);
+    }
+
+    // Returns the fragment, oriented so that the result is on its left, if it lies on the
+    // boundary of the result.
+    fn classify(&self, fragment: &Segment) -> Option<Segment> {
+        let (before, after) = fragment.split(0.5);
+        let midpoint = before.baseline.to();
+        let mut tangent = if fragment.is_line() {
+            fragment.baseline.vector()
+        } else {
+            midpoint - before.ctrl.to()
+        };
+        if tangent.is_zero() {
+            tangent = after.ctrl.from() - midpoint;
+        }
+        if tangent.is_zero() {
+            return None;
+        }
+
+        let normal = Point2DF32::new(-tangent.y(), tangent.x()).normalize();
+        let offset = normal.scale(self.side_offset);
+        let inside_left = self.result_contains(midpoint + offset);
+        let inside_right = self.result_contains(midpoint - offset);
+        match (inside_left, inside_right) {
+            (true, false) => Some(*fragment),
+            (false, true) => Some(fragment.reversed()),
+            _ => None,
+        }
+    }
+
+    fn result_contains(&self, point: Point2DF32) -> bool {
+        let inside_a = self.fill_rule.winding_is_inside(winding_number(&self.operands[0], point));
+        let inside_b = self.fill_rule.winding_is_inside(winding_number(&self.operands[1], point));
+        self.op.apply(inside_a, inside_b)
+    }
+
+    // Catches edges shared by both operands, which would otherwise appear twice.
+    fn fragments_coincide(&self, a: &Segment, b: &Segment) -> bool {
+        (a.baseline.from() - b.baseline.from()).length() <= self.snap_tolerance &&
+            (a.baseline.to() - b.baseline.to()).length() <= self.snap_tolerance &&
+            (a.sample(0.5) - b.sample(0.5)).length() <= self.snap_tolerance
+    }
+
+    // Joins fragments end to start to form closed contours. Where the boundary touches itself,
+    // the sharpest left turn is taken, which keeps regions that meet at a point separate.
+    fn link(&self, fragments: Vec<Segment>) -> Outline {
+        let mut outline = Outline::new();
+        let mut used = vec![false; fragments.len()];
+
+        for first_index in 0..fragments.len() {
+            if used[first_index] {
+                continue;
+            }
+            used[first_index] = true;
+
+            let first = fragments[first_index];
+            let start = first.baseline.from();
+            let mut contour = Contour::new();
+            contour.push_full_segment(&first, true);
+
+            let mut prev = first;
+            while (prev.baseline.to() - start).length() > self.snap_tolerance {
+                let (end, end_direction) = (prev.baseline.to(), end_direction(&prev));
+                let mut next_index = None;
+                let mut best_turn = f32::NEG_INFINITY;
+                for (index, fragment) in fragments.iter().enumerate() {
+                    if used[index] ||
+                            (fragment.baseline.from() - end).length() > self.snap_tolerance {
+                        continue;
+                    }
+                    let start_direction = start_direction(fragment);
+                    let turn = f32::atan2(end_direction.det(start_direction),
+                                          end_direction.dot(start_direction));
+                    if turn > best_turn {
+                        next_index = Some(index);
+                        best_turn = turn;
+                    }
+                }
+
+                let next_index = match next_index {
+                    None => break,
+                    Some(next_index) => next_index,
+                };
+                used[next_index] = true;
+
+                let mut next = fragments[next_index];
+                next.baseline.set_from(&end);
+                if (next.baseline.to() - start).length() <= self.snap_tolerance {
+                    // Leave the final line out; closing the contour draws it.
+                    if next.is_line() {
+                        break;
+                    }
+                    next.baseline.set_to(&start);
+                }
+                contour.push_segment(next, true);
+                prev = next;
+            }
+
+            contour.close();
+            outline.push_contour(contour);
+        }
+
+        outline
+    }
+}
+
+impl Edge {
+    fn new(segment: Segment) -> Edge {
+        Edge { segment, bounds: segment.bounds(), splits: vec![] }
+    }
+}
+
+// Returns the segments of all contours, treating open contours as closed since that's how they
+// are filled.
+fn collect_segments(outline: &Outline) -> Vec<Segment> {
+    let mut segments = vec![];
+    for contour in outline.contours() {
+        for mut segment in contour.iter() {
+            if segment.is_none() || (segment.is_line() && segment.baseline.is_zero_length()) {
+                continue;
+            }
+            segment.flags = SegmentFlags::empty();
+            segments.push(segment);
+        }
+
+        if !contour.is_closed() && contour.len() > 1 {
+            let (first, last) = (contour.position_of(0), contour.position_of(contour.len() - 1));
+            if first != last {
+                segments.push(Segment::line(&LineSegmentF32::new(last, first)));
+            }
+        }
+    }
+    segments
+}
+
+fn start_direction(segment: &Segment) -> Point2DF32 {
+    let from = segment.baseline.from();
+    let mut points = vec![];
+    if !segment.is_line() {
+        points.push(segment.ctrl.from());
+        if segment.is_cubic() {
+            points.push(segment.ctrl.to());
+        }
+    }
+    points.push(segment.baseline.to());
+    points.into_iter().map(|point| point - from).find(|direction| !direction.is_zero())
+                      .unwrap_or_default()
+}
+
+fn end_direction(segment: &Segment) -> Point2DF32 {
+    start_direction(&segment.reversed()).scale(-1.0)
+}
+
+// Finds the parameters at which two segments cross, by subdividing curves until they're flat.
+// Only lines are checked for overlapping along a stretch; curves that do so aren't split there.
+fn intersect_segments(a: &Segment,
+                      b: &Segment,
+                      tolerance: f32,
+                      intersections: &mut Vec<(f32, f32)>) {
+    if a.is_line() && b.is_line() {
+        intersect_lines(&a.baseline, &b.baseline, tolerance, intersections);
+        return;
+    }
+
+    let a = if a.is_line() { *a } else { a.to_cubic() };
+    let b = if b.is_line() { *b } else { b.to_cubic() };
+    intersect_curves(&a, (0.0, 1.0), &b, (0.0, 1.0), tolerance, 0, intersections);
+}
+
+fn intersect_curves(a: &Segment,
+                    a_range: (f32, f32),
+                    b: &Segment,
+                    b_range: (f32, f32),
+                    tolerance: f32,
+                    depth: u32,
+                    intersections: &mut Vec<(f32, f32)>) {
+    let (a_bounds, b_bounds) = (a.bounds(), b.bounds());
+    let dilation = Point2DF32::splat(tolerance);
+    if !a_bounds.dilate(dilation).intersects(b_bounds.dilate(dilation)) {
+        return;
+    }
+
+    let a_is_flat = a.is_line() || a.as_cubic_segment().is_flat(tolerance);
+    let b_is_flat = b.is_line() || b.as_cubic_segment().is_flat(tolerance);
+    if (a_is_flat && b_is_flat) || depth == MAX_SUBDIVISIONS {
+        let mut line_intersections = vec![];
+        intersect_lines(&a.baseline, &b.baseline, tolerance, &mut line_intersections);
+        for (t_a, t_b) in line_intersections {
+            intersections.push((lerp_range(a_range, t_a), lerp_range(b_range, t_b)));
+        }
+        return;
+    }
+
+    let a_size = a_bounds.size().x() + a_bounds.size().y();
+    let b_size = b_bounds.size().x() + b_bounds.size().y();
+    if !a_is_flat && (b_is_flat || a_size >= b_size) {
+        let (before, after) = a.split(0.5);
+        let middle = lerp_range(a_range, 0.5);
+        intersect_curves(&before, (a_range.0, middle), b, b_range, tolerance, depth + 1,
+                         intersections);
+        intersect_curves(&after, (middle, a_range.1), b, b_range, tolerance, depth + 1,
+                         intersections);
+    } else {
+        let (before, after) = b.split(0.5);
+        let middle = lerp_range(b_range, 0.5);
+        intersect_curves(a, a_range, &before, (b_range.0, middle), tolerance, depth + 1,
+                         intersections);
+        intersect_curves(a, a_range, &after, (middle, b_range.1), tolerance, depth + 1,
+                         intersections);
+    }
+}
+
+fn intersect_lines(a: &LineSegmentF32,
+                   b: &LineSegmentF32,
+                   tolerance: f32,
+                   intersections: &mut Vec<(f32, f32)>) {
+    let (a_vector, b_vector) = (a.vector(), b.vector());
+    let (a_length, b_length) = (a_vector.length(), b_vector.length());
+    if a_length == 0.0 || b_length == 0.0 {
+        return;
+    }
+
+    let delta = b.from() - a.from();
+    let denom = a_vector.det(b_vector);
+    if f32::abs(denom) > 1.0e-6 * a_length * b_length {
+        let (t_a, t_b) = (delta.det(b_vector) / denom, delta.det(a_vector) / denom);
+        let (slop_a, slop_b) = (tolerance / a_length, tolerance / b_length);
+        if t_a >= -slop_a && t_a <= 1.0 + slop_a && t_b >= -slop_b && t_b <= 1.0 + slop_b {
+            intersections.push((util::clamp(t_a, 0.0, 1.0), util::clamp(t_b, 0.0, 1.0)));
+        }
+        return;
+    }
+
+    // The lines are parallel. If they're also collinear, split each at the other's endpoints
+    // so that the overlapping parts match up.
+    if f32::abs(delta.det(a_vector)) / a_length > tolerance {
+        return;
+    }
+    for &(t_b, point) in &[(0.0, b.from()), (1.0, b.to())] {
+        let t_a = (point - a.from()).dot(a_vector) / (a_length * a_length);
+        if t_a > 0.0 && t_a < 1.0 {
+            intersections.push((t_a, t_b));
+        }
+    }
+    for &(t_a, point) in &[(0.0, a.from()), (1.0, a.to())] {
+        let t_b = (point - b.from()).dot(b_vector) / (b_length * b_length);
+        if t_b > 0.0 && t_b < 1.0 {
+            intersections.push((t_a, t_b));
+        }
+    }
+}
+
+#[inline]
+fn lerp_range(range: (f32, f32), t: f32) -> f32 {
+    range.0 + (range.1 - range.0) * t
+}
+
+fn winding_number(segments: &[Segment], point: Point2DF32) -> i32 {
+    segments.iter().map(|segment| segment.winding_number(point)).sum()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::basic::point::Point2DF32;
+    use crate::basic::rect::RectF32;
+    use crate::fill::FillRule;
+    use crate::outline::{Contour, Outline};
+    use crate::test_util::{assert_bounds_eq, rect_outline};
+
+    fn circle(center: (f32, f32), radius: f32) -> Outline {
+        const K: f32 = 0.552_284_8;
+        let center = Point2DF32::new(center.0, center.1);
+        let point = |x: f32, y: f32| center + Point2DF32::new(x, y).scale(radius);
+        let mut contour = Contour::new();
+        contour.push_endpoint(point(1.0, 0.0));
+        contour.push_cubic(point(1.0, K), point(K, 1.0), point(0.0, 1.0));
+        contour.push_cubic(point(-K, 1.0), point(-1.0, K), point(-1.0, 0.0));
+        contour.push_cubic(point(-1.0, -K), point(-K, -1.0), point(0.0, -1.0));
+        contour.push_cubic(point(K, -1.0), point(1.0, -K), point(1.0, 0.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        outline
+    }
+
+    #[test]
+    fn test_overlapping_rects() {
+        let a = rect_outline(RectF32::new(Point2DF32::splat(0.0), Point2DF32::splat(10.0)));
+        let b = rect_outline(RectF32::new(Point2DF32::splat(5.0), Point2DF32::splat(10.0)));
+
+        let union = a.union(&b, FillRule::Winding);
+        assert_eq!(union.contours().len(), 1);
+        assert_eq!(union.contours()[0].len(), 8);
+        assert_bounds_eq(union.bounds(), RectF32::new(Point2DF32::splat(0.0),
+                                                      Point2DF32::splat(15.0)));
+
+        let intersection = a.intersection(&b, FillRule::Winding);
+        assert_eq!(intersection.contours().len(), 1);
+        assert_bounds_eq(intersection.bounds(), RectF32::new(Point2DF32::splat(5.0),
+                                                             Point2DF32::splat(5.0)));
+
+        let difference = a.difference(&b, FillRule::Winding);
+        assert_eq!(difference.contours().len(), 1);
+        assert_bounds_eq(difference.bounds(), RectF32::new(Point2DF32::splat(0.0),
+                                                           Point2DF32::splat(10.0)));
+
+        let xor = a.xor(&b, FillRule::Winding);
+        assert_eq!(xor.contours().len(), 2);
+        assert_bounds_eq(xor.bounds(), union.bounds());
+    }
+
+    #[test]
+    fn test_disjoint_and_nested() {
+        let outer = rect_outline(RectF32::new(Point2DF32::splat(0.0), Point2DF32::splat(10.0)));
+        let inner = rect_outline(RectF32::new(Point2DF32::splat(2.0), Point2DF32::splat(2.0)));
+        let far = rect_outline(RectF32::new(Point2DF32::splat(20.0), Point2DF32::splat(1.0)));
+
+        assert_eq!(outer.union(&far, FillRule::Winding).contours().len(), 2);
+        assert!(outer.intersection(&far, FillRule::Winding).contours().is_empty());
+        assert_eq!(outer.difference(&inner, FillRule::Winding).contours().len(), 2);
+        assert_eq!(inner.difference(&outer, FillRule::EvenOdd).contours().len(), 0);
+    }
+
+    #[test]
+    fn test_curves_are_kept() {
+        let a = circle((0.0, 0.0), 10.0);
+        let b = circle((10.0, 0.0), 10.0);
+        let union = a.union(&b, FillRule::Winding);
+        assert_eq!(union.contours().len(), 1);
+        assert!(union.contours()[0].iter().all(|segment| {
+            segment.is_cubic() || segment.baseline.is_zero_length()
+        }));
+        assert_bounds_eq(union.bounds(), RectF32::new(Point2DF32::new(-10.0, -10.0),
+                                                      Point2DF32::new(30.0, 20.0)));
+
+        let intersection = a.intersection(&b, FillRule::Winding);
+        assert_eq!(intersection.contours().len(), 1);
+        let bounds = intersection.bounds();
+        assert!(bounds.min_x() > -0.01 && bounds.max_x() < 10.01);
+    }
+}
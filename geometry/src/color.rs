@@ -12,7 +12,7 @@ use pathfinder_simd::default::F32x4;
 use std::fmt::{self, Debug, Formatter};
 
 // TODO(pcwalton): Maybe this should be a u32?
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
 pub struct ColorU {
     pub r: u8,
     pub g: u8,
@@ -41,6 +41,11 @@ impl ColorU {
         }
     }
 
+    #[inline]
+    pub fn transparent_black() -> ColorU {
+        ColorU::default()
+    }
+
     #[inline]
     pub fn to_f32(&self) -> ColorF {
         let color = F32x4::new(self.r as f32, self.g as f32, self.b as f32, self.a as f32);
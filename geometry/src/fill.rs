@@ -0,0 +1,47 @@
+// pathfinder/geometry/src/fill.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Fill rules, which determine which points are inside an outline.
+
+/// How a winding number is turned into insideness.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FillRule {
+    /// A point is inside if its winding number is nonzero.
+    Winding,
+    /// A point is inside if its winding number is odd.
+    EvenOdd,
+}
+
+impl Default for FillRule {
+    #[inline]
+    fn default() -> FillRule {
+        FillRule::Winding
+    }
+}
+
+impl FillRule {
+    /// Returns true if a point with the given winding number is inside the outline.
+    #[inline]
+    pub fn winding_is_inside(self, winding: i32) -> bool {
+        match self {
+            FillRule::Winding => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+
+    /// Converts an accumulated, possibly fractional, winding number to coverage in [0, 1].
+    #[inline]
+    pub fn coverage(self, winding: f32) -> f32 {
+        match self {
+            FillRule::Winding => f32::min(f32::abs(winding), 1.0),
+            FillRule::EvenOdd => 1.0 - f32::abs(1.0 - f32::abs(winding) % 2.0),
+        }
+    }
+}
@@ -16,14 +16,22 @@
 extern crate bitflags;
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde_derive;
 
 pub mod basic;
+pub mod boolean;
 pub mod clip;
 pub mod color;
+pub mod fill;
+pub mod measure;
 pub mod orientation;
 pub mod outline;
+pub mod path_data;
 pub mod segment;
 pub mod stroke;
 pub mod util;
 
 mod dilation;
+#[cfg(test)]
+mod test_util;
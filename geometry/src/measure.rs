@@ -0,0 +1,257 @@
+// pathfinder/geometry/src/measure.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Arc length queries along outlines: lengths, positions and tangents at a distance, and the
+//! pieces of contours between two distances.
+
+use crate::basic::point::Point2DF32;
+use crate::outline::{Contour, Outline};
+use crate::segment::Segment;
+
+/// Measures every contour of an outline.
+pub struct OutlineMeasure {
+    contours: Vec<ContourMeasure>,
+}
+
+impl OutlineMeasure {
+    /// Measures `outline`, with lengths of curves accurate to roughly `tolerance`.
+    pub fn new(outline: &Outline, tolerance: f32) -> OutlineMeasure {
+        OutlineMeasure {
+            contours: outline.contours()
+                             .iter()
+                             .map(|contour| ContourMeasure::new(contour, tolerance))
+                             .collect(),
+        }
+    }
+
+    #[inline]
+    pub fn contours(&self) -> &[ContourMeasure] {
+        &self.contours
+    }
+
+    /// The sum of the lengths of all contours.
+    #[inline]
+    pub fn length(&self) -> f32 {
+        self.contours.iter().map(|contour| contour.length()).sum()
+    }
+}
+
+/// Measures one contour. For closed contours, the closing segment is included.
+pub struct ContourMeasure {
+    segments: Vec<MeasuredSegment>,
+    length: f32,
+    closed: bool,
+    tolerance: f32,
+}
+
+#[derive(Clone, Copy)]
+struct MeasuredSegment {
+    segment: Segment,
+    // The distance along the contour at which this segment starts.
+    start: f32,
+    length: f32,
+}
+
+impl ContourMeasure {
+    /// Measures `contour`, with lengths of curves accurate to roughly `tolerance`.
+    pub fn new(contour: &Contour, tolerance: f32) -> ContourMeasure {
+        let mut segments = vec![];
+        let mut length = 0.0;
+        for segment in contour.iter() {
+            let segment_length = segment.arc_length(tolerance);
+            segments.push(MeasuredSegment { segment, start: length, length: segment_length });
+            length += segment_length;
+        }
+
+        ContourMeasure { segments, length, closed: contour.is_closed(), tolerance }
+    }
+
+    #[inline]
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Returns the position at `distance` along the contour, clamped to its ends, and the unit
+    /// tangent there. Returns `None` if the contour has no segments.
+    pub fn sample(&self, distance: f32) -> Option<(Point2DF32, Point2DF32)> {
+        let (segment, t) = self.segment_and_time_at(distance)?;
+        Some((segment.sample(t), tangent(&segment, t)))
+    }
+
+    #[inline]
+    pub fn position_at(&self, distance: f32) -> Option<Point2DF32> {
+        self.sample(distance).map(|(position, _)| position)
+    }
+
+    #[inline]
+    pub fn tangent_at(&self, distance: f32) -> Option<Point2DF32> {
+        self.sample(distance).map(|(_, tangent)| tangent)
+    }
+
+    /// Returns the open contour running from `start` to `end` along this one. The distances are
+    /// clamped to the ends of the contour; if `start` isn't before `end`, the result is empty.
+    #[inline]
+    pub fn sub_contour(&self, start: f32, end: f32) -> Contour {
+        self.sub_contour_from_segment(start, end, &mut 0)
+    }
+
+    // Like `sub_contour`, but starts looking for the segment containing `start` at index
+    // `*cursor`, and leaves the cursor at the segment containing `end`. Successive pieces of a
+    // contour can then be extracted in one pass over its segments.
+    pub(crate) fn sub_contour_from_segment(&self, start: f32, end: f32, cursor: &mut usize)
+                                           -> Contour {
+        let mut contour = Contour::new();
+        let (start, end) = (f32::max(start, 0.0), f32::min(end, self.length));
+        if start >= end {
+            return contour;
+        }
+
+        while *cursor < self.segments.len() {
+            let measured = &self.segments[*cursor];
+            if measured.start + measured.length > start {
+                break;
+            }
+            *cursor += 1;
+        }
+
+        for (index, measured) in self.segments.iter().enumerate().skip(*cursor) {
+            if measured.start >= end {
+                break;
+            }
+            *cursor = index;
+
+            let segment = &measured.segment;
+            let t0 = segment.time_for_distance(start - measured.start, self.tolerance);
+            let t1 = segment.time_for_distance(end - measured.start, self.tolerance);
+            let piece = segment_between(segment, t0, t1);
+            if contour.is_empty() {
+                contour.push_full_segment(&piece, true);
+            } else {
+                contour.push_segment(piece, true);
+            }
+        }
+
+        contour
+    }
+
+    fn segment_and_time_at(&self, distance: f32) -> Option<(Segment, f32)> {
+        let distance = f32::max(0.0, f32::min(distance, self.length));
+        let index = match self.segments.binary_search_by(|measured| {
+            measured.start.partial_cmp(&distance).unwrap()
+        }) {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+
+        // Skip over zero-length segments that start at the same distance.
+        let index = (index..self.segments.len()).find(|&index| {
+            self.segments[index].length > 0.0 || index + 1 == self.segments.len()
+        }).unwrap_or(index);
+
+        let measured = &self.segments[index];
+        let t = measured.segment.time_for_distance(distance - measured.start, self.tolerance);
+        Some((measured.segment, t))
+    }
+}
+
+// Returns the part of `segment` between the parameters `t0` and `t1`.
+fn segment_between(segment: &Segment, t0: f32, t1: f32) -> Segment {
+    let mut segment = *segment;
+    if t1 < 1.0 {
+        segment = segment.split(t1).0;
+    }
+    if t0 > 0.0 && t1 > 0.0 {
+        segment = segment.split(t0 / t1).1;
+    }
+    segment
+}
+
+fn tangent(segment: &Segment, t: f32) -> Point2DF32 {
+    if segment.is_line() {
+        return segment.baseline.vector().normalize();
+    }
+
+    let segment = segment.to_cubic();
+    let (p0, p3) = (segment.baseline.from(), segment.baseline.to());
+    let (p1, p2) = (segment.ctrl.from(), segment.ctrl.to());
+    let u = 1.0 - t;
+    let derivative = (p1 - p0).scale(u * u) + (p2 - p1).scale(2.0 * u * t) +
+        (p3 - p2).scale(t * t);
+    if !derivative.is_zero() {
+        return derivative.normalize();
+    }
+
+    // The derivative vanishes where a control point coincides with an endpoint. Fall back to
+    // the direction toward the other control point.
+    let fallback = if t < 0.5 { p2 - p0 } else { p3 - p1 };
+    if fallback.is_zero() {
+        (p3 - p0).normalize()
+    } else {
+        fallback.normalize()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::basic::point::Point2DF32;
+    use crate::basic::rect::RectF32;
+    use crate::outline::Contour;
+    use crate::test_util::{assert_close, rect_contour};
+    use super::ContourMeasure;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_lines() {
+        let square = rect_contour(RectF32::new(Point2DF32::splat(0.0), Point2DF32::splat(10.0)));
+        let measure = ContourMeasure::new(&square, 0.01);
+        assert_eq!(measure.length(), 40.0);
+
+        let (position, tangent) = measure.sample(15.0).unwrap();
+        assert_close(position, Point2DF32::new(10.0, 5.0));
+        assert_close(tangent, Point2DF32::new(0.0, 1.0));
+        assert_close(measure.position_at(35.0).unwrap(), Point2DF32::new(0.0, 5.0));
+        assert_close(measure.position_at(100.0).unwrap(), Point2DF32::new(0.0, 0.0));
+
+        let sub_contour = measure.sub_contour(5.0, 15.0);
+        assert_eq!(sub_contour.len(), 3);
+        assert_close(sub_contour.position_of(0), Point2DF32::new(5.0, 0.0));
+        assert_close(sub_contour.position_of(1), Point2DF32::new(10.0, 0.0));
+        assert_close(sub_contour.position_of(2), Point2DF32::new(10.0, 5.0));
+        assert!(measure.sub_contour(15.0, 5.0).is_empty());
+    }
+
+    #[test]
+    fn test_curves() {
+        // A quarter circle of radius 10.
+        const K: f32 = 5.522_848;
+        let mut contour = Contour::new();
+        contour.push_endpoint(Point2DF32::new(10.0, 0.0));
+        contour.push_cubic(Point2DF32::new(10.0, K),
+                           Point2DF32::new(K, 10.0),
+                           Point2DF32::new(0.0, 10.0));
+
+        let measure = ContourMeasure::new(&contour, 0.001);
+        assert!((measure.length() - 5.0 * PI).abs() < 0.01);
+
+        let (position, tangent) = measure.sample(measure.length() * 0.5).unwrap();
+        let diagonal = f32::sqrt(0.5);
+        assert_close(position, Point2DF32::new(10.0 * diagonal, 10.0 * diagonal));
+        assert_close(tangent, Point2DF32::new(-diagonal, diagonal));
+
+        let sub_contour = measure.sub_contour(0.0, measure.length() * 0.5);
+        assert_close(sub_contour.position_of(sub_contour.len() - 1), position);
+    }
+}
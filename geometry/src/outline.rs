@@ -17,19 +17,26 @@ use crate::basic::transform2d::Transform2DF32;
 use crate::basic::transform3d::Perspective;
 use crate::clip::{self, ContourPolygonClipper, ContourRectClipper};
 use crate::dilation::ContourDilator;
+use crate::fill::FillRule;
 use crate::orientation::Orientation;
 use crate::segment::{Segment, SegmentFlags, SegmentKind};
-use std::f32::consts::FRAC_PI_2;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::f32::consts::{FRAC_PI_2, PI};
 use std::fmt::{self, Debug, Formatter};
 use std::mem;
 
-#[derive(Clone)]
+/// The maximum distance between an arc and the cubic Bézier curves that approximate it.
+pub const ARC_TOLERANCE: f32 = 0.01;
+
+const MAX_ARC_PIECES: u32 = 256;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Outline {
     pub(crate) contours: Vec<Contour>,
     pub(crate) bounds: RectF32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Contour {
     pub(crate) points: Vec<Point2DF32>,
     pub(crate) flags: Vec<PointFlags>,
@@ -44,6 +51,18 @@ bitflags! {
     }
 }
 
+impl Serialize for PointFlags {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        self.bits().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PointFlags {
+    fn deserialize<D>(deserializer: D) -> Result<PointFlags, D::Error> where D: Deserializer<'de> {
+        Ok(PointFlags::from_bits_truncate(Deserialize::deserialize(deserializer)?))
+    }
+}
+
 impl Outline {
     #[inline]
     pub fn new() -> Outline {
@@ -126,6 +145,24 @@ impl Outline {
         self.contours.push(contour);
     }
 
+    /// Checks that every contour is well formed and recomputes the bounds from the points.
+    ///
+    /// Use this on outlines from untrusted sources, such as deserialized ones, before handing them
+    /// to anything that iterates over their segments. Returns false if a contour is malformed.
+    pub fn validate(&mut self) -> bool {
+        let mut new_bounds = None;
+        for contour in &mut self.contours {
+            if !contour.validate() {
+                return false;
+            }
+            if !contour.is_empty() {
+                contour.update_bounds(&mut new_bounds);
+            }
+        }
+        self.bounds = new_bounds.unwrap_or_else(RectF32::default);
+        true
+    }
+
     pub fn transform(&mut self, transform: &Transform2DF32) {
         if transform.is_identity() {
             return;
@@ -166,6 +203,20 @@ impl Outline {
             .unwrap_or_else(|| RectF32::default());
     }
 
+    /// Returns the winding number of this outline around `point`. Open contours are treated as
+    /// though they were closed, as they are when filled.
+    pub fn winding_number(&self, point: Point2DF32) -> i32 {
+        self.contours.iter().map(|contour| contour.winding_number(point)).sum()
+    }
+
+    /// Returns true if `point` is inside this outline when filled with `fill_rule`.
+    pub fn contains_point(&self, point: Point2DF32, fill_rule: FillRule) -> bool {
+        if !self.bounds.contains_point(point) {
+            return false;
+        }
+        fill_rule.winding_is_inside(self.winding_number(point))
+    }
+
     pub fn is_outside_polygon(&self, clip_polygon: &[Point2DF32]) -> bool {
         clip::rect_is_outside_polygon(self.bounds, clip_polygon)
     }
@@ -262,6 +313,18 @@ impl Contour {
         self.closed
     }
 
+    /// Returns the winding number of this contour around `point`, treating it as closed.
+    pub fn winding_number(&self, point: Point2DF32) -> i32 {
+        let mut winding = self.iter().map(|segment| segment.winding_number(point)).sum();
+        if !self.closed && self.len() > 1 {
+            let (first, last) = (self.position_of(0), self.position_of_last(1));
+            if first != last {
+                winding += Segment::line(&LineSegmentF32::new(last, first)).winding_number(point);
+            }
+        }
+        winding
+    }
+
     #[inline]
     pub fn position_of(&self, index: u32) -> Point2DF32 {
         self.points[index as usize]
@@ -273,7 +336,7 @@ impl Contour {
     }
 
     #[inline]
-    pub(crate) fn last_position(&self) -> Option<Point2DF32> {
+    pub fn last_position(&self) -> Option<Point2DF32> {
         self.points.last().cloned()
     }
 
@@ -367,32 +430,129 @@ impl Contour {
         self.push_point(segment.baseline.to(), PointFlags::empty(), update_bounds);
     }
 
+    /// Appends a circular arc from `start_angle` to `end_angle`, which must be greater. See
+    /// `push_ellipse_arc()`.
     pub fn push_arc(&mut self, center: Point2DF32, radius: f32, start_angle: f32, end_angle: f32) {
-        let scale = Transform2DF32::from_scale(Point2DF32::splat(radius));
-        let translation = Transform2DF32::from_translation(center);
-
-        let (mut angle, mut first_segment) = (start_angle, true);
-        while angle < end_angle {
-            let sweep_angle = f32::min(FRAC_PI_2, end_angle - angle);
-            let mut segment = Segment::arc(sweep_angle);
-            let rotation = Transform2DF32::from_rotation(angle);
-            segment = segment.transform(&scale.post_mul(&rotation).post_mul(&translation));
-
-            debug!("angle={} start_angle={} end_angle={} sweep_angle={} segment={:?}",
-                   angle,
-                   start_angle,
-                   end_angle,
-                   sweep_angle,
-                   segment);
-
-            if first_segment {
-                self.push_full_segment(&segment, true);
-                first_segment = false;
-            } else {
-                self.push_segment(segment, true);
-            }
+        if end_angle > start_angle {
+            self.push_ellipse_arc(center,
+                                  Point2DF32::splat(radius),
+                                  0.0,
+                                  start_angle,
+                                  end_angle - start_angle);
+        }
+    }
+
+    /// Appends an arc of the ellipse with the given center and radii, rotated by
+    /// `x_axis_rotation`, that starts at `start_angle` and sweeps through `sweep_angle` radians.
+    /// Positive angles go from the positive x axis toward the positive y axis.
+    ///
+    /// If the contour isn't empty, a line joins its last point to the start of the arc. The arc
+    /// is approximated with cubic Bézier curves that stray from it by at most `ARC_TOLERANCE`.
+    pub fn push_ellipse_arc(&mut self,
+                            center: Point2DF32,
+                            radii: Point2DF32,
+                            x_axis_rotation: f32,
+                            start_angle: f32,
+                            sweep_angle: f32) {
+        let transform = ellipse_transform(center, radii, x_axis_rotation);
+        let end_angle = start_angle + sweep_angle;
+        let start = transform.transform_point(Point2DF32::new(start_angle.cos(),
+                                                              start_angle.sin()));
+        let end = transform.transform_point(Point2DF32::new(end_angle.cos(), end_angle.sin()));
+        self.push_arc_pieces(&transform, start_angle, sweep_angle, start, end);
+    }
 
-            angle += sweep_angle;
+    /// Appends an elliptical arc from the last point to `to`, parameterized as SVG arcs are.
+    ///
+    /// Of the four arcs of an ellipse with the given radii and rotation that join the points,
+    /// `large_arc` picks one sweeping more than π radians, and `sweep` one that goes in the
+    /// direction of positive angles. Radii too small to join the points are scaled up, and an arc
+    /// with a zero radius is a line.
+    pub fn push_svg_arc(&mut self,
+                        radii: Point2DF32,
+                        x_axis_rotation: f32,
+                        large_arc: bool,
+                        sweep: bool,
+                        to: Point2DF32) {
+        // See the SVG 1.1 specification, appendix F.6, "Elliptical arc implementation notes".
+        let from = match self.last_position() {
+            Some(from) if from != to => from,
+            _ => return self.push_endpoint(to),
+        };
+        let mut radii = Point2DF32::new(radii.x().abs(), radii.y().abs());
+        if radii.x() == 0.0 || radii.y() == 0.0 {
+            return self.push_endpoint(to);
+        }
+
+        // Find the midpoint of the chord in the ellipse's own coordinate space.
+        let rotation = Transform2DF32::from_rotation(x_axis_rotation);
+        let half_chord = Transform2DF32::from_rotation(-x_axis_rotation)
+            .transform_point((from - to).scale(0.5));
+
+        // Scale up radii that are too small for the ellipse to reach both endpoints.
+        let lambda = (half_chord.x() / radii.x()).powi(2) + (half_chord.y() / radii.y()).powi(2);
+        if lambda > 1.0 {
+            radii = radii.scale(lambda.sqrt());
+        }
+
+        let (rx2, ry2) = (radii.x() * radii.x(), radii.y() * radii.y());
+        let (x2, y2) = (half_chord.x() * half_chord.x(), half_chord.y() * half_chord.y());
+        let numerator = f32::max(rx2 * ry2 - rx2 * y2 - ry2 * x2, 0.0);
+        let mut coefficient = f32::sqrt(numerator / (rx2 * y2 + ry2 * x2));
+        if large_arc == sweep {
+            coefficient = -coefficient;
+        }
+        let center = Point2DF32::new(radii.x() * half_chord.y() / radii.y(),
+                                     -radii.y() * half_chord.x() / radii.x()).scale(coefficient);
+
+        // Find the angles on the unit circle that the endpoints map to.
+        let start_vector = Point2DF32::new((half_chord.x() - center.x()) / radii.x(),
+                                           (half_chord.y() - center.y()) / radii.y());
+        let end_vector = Point2DF32::new((-half_chord.x() - center.x()) / radii.x(),
+                                         (-half_chord.y() - center.y()) / radii.y());
+        let start_angle = f32::atan2(start_vector.y(), start_vector.x());
+        let mut sweep_angle = f32::atan2(end_vector.y(), end_vector.x()) - start_angle;
+        if sweep && sweep_angle < 0.0 {
+            sweep_angle += 2.0 * PI;
+        } else if !sweep && sweep_angle > 0.0 {
+            sweep_angle -= 2.0 * PI;
+        }
+
+        let center = rotation.transform_point(center) + (from + to).scale(0.5);
+        let transform = ellipse_transform(center, radii, x_axis_rotation);
+        self.push_arc_pieces(&transform, start_angle, sweep_angle, from, to);
+    }
+
+    // Appends the arc of the unit circle from `start_angle` through `sweep_angle`, mapped through
+    // `transform`. The endpoints are given separately so that rounding error can't leave gaps.
+    fn push_arc_pieces(&mut self,
+                       transform: &Transform2DF32,
+                       start_angle: f32,
+                       sweep_angle: f32,
+                       start: Point2DF32,
+                       end: Point2DF32) {
+        if self.last_position() != Some(start) {
+            self.push_endpoint(start);
+        }
+        if sweep_angle == 0.0 {
+            return;
+        }
+
+        // The approximation error of each piece scales with the radius. Take the larger one, since
+        // the transform can't magnify the error by more than that.
+        let radius = f32::max(transform.m11().hypot(transform.m21()),
+                              transform.m12().hypot(transform.m22()));
+        let piece_count = arc_piece_count(radius, sweep_angle);
+        let piece_sweep_angle = sweep_angle / piece_count as f32;
+        for piece_index in 0..piece_count {
+            let middle_angle = start_angle + piece_sweep_angle * (piece_index as f32 + 0.5);
+            let mut segment = Segment::arc(piece_sweep_angle)
+                .transform(&Transform2DF32::from_rotation(middle_angle))
+                .transform(transform);
+            if piece_index + 1 == piece_count {
+                segment.baseline.set_to(&end);
+            }
+            self.push_segment(segment, true);
         }
     }
 
@@ -661,6 +821,37 @@ impl Contour {
         true
     }
 
+    // Checks that the points and flags match up, that the coordinates are finite, and that every
+    // run of at most two control points sits between endpoints. Recomputes the bounds on success.
+    fn validate(&mut self) -> bool {
+        if self.points.len() != self.flags.len() {
+            return false;
+        }
+
+        let mut control_point_count = 0;
+        for (point_index, (point, flags)) in self.points.iter().zip(self.flags.iter()).enumerate() {
+            if !point.x().is_finite() || !point.y().is_finite() {
+                return false;
+            }
+            if flags.intersects(PointFlags::CONTROL_POINT_0 | PointFlags::CONTROL_POINT_1) {
+                control_point_count += 1;
+                if point_index == 0 || control_point_count > 2 {
+                    return false;
+                }
+            } else {
+                control_point_count = 0;
+            }
+        }
+        if control_point_count > 0 {
+            return false;
+        }
+
+        for (point_index, point) in self.points.iter().enumerate() {
+            union_rect(&mut self.bounds, *point, point_index == 0);
+        }
+        true
+    }
+
     // Use this function to keep bounds up to date when mutating paths. See `Outline::transform()`
     // for an example of use.
     pub(crate) fn update_bounds(&self, bounds: &mut Option<RectF32>) {
@@ -797,6 +988,35 @@ impl<'a> Iterator for ContourIter<'a> {
     }
 }
 
+// Maps the unit circle onto the ellipse with the given center, radii and rotation.
+fn ellipse_transform(center: Point2DF32, radii: Point2DF32, rotation: f32) -> Transform2DF32 {
+    let (sin, cos) = rotation.sin_cos();
+    Transform2DF32::row_major(radii.x() * cos,
+                              -radii.y() * sin,
+                              radii.x() * sin,
+                              radii.y() * cos,
+                              center.x(),
+                              center.y())
+}
+
+// Returns the number of cubic Bézier curves needed to approximate an arc of the given radius and
+// angle to within `ARC_TOLERANCE`. No curve spans more than a quarter turn.
+fn arc_piece_count(radius: f32, sweep_angle: f32) -> u32 {
+    let sweep_angle = sweep_angle.abs();
+    let mut piece_count = f32::max(1.0, f32::ceil(sweep_angle / FRAC_PI_2 - 0.001)) as u32;
+    while piece_count < MAX_ARC_PIECES {
+        // The maximum radial error of the approximation of a unit arc through angle θ is
+        // 2/27 · sin⁶(θ/4) / cos²(θ/4).
+        let quarter_angle = 0.25 * sweep_angle / piece_count as f32;
+        let error = 2.0 / 27.0 * quarter_angle.sin().powi(6) / quarter_angle.cos().powi(2);
+        if radius * error <= ARC_TOLERANCE {
+            break;
+        }
+        piece_count += 1;
+    }
+    piece_count
+}
+
 #[inline]
 pub(crate) fn union_rect(bounds: &mut RectF32, new_point: Point2DF32, first: bool) {
     if first {
@@ -805,3 +1025,111 @@ pub(crate) fn union_rect(bounds: &mut RectF32, new_point: Point2DF32, first: boo
         *bounds = bounds.union_point(new_point)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::basic::point::Point2DF32;
+    use crate::basic::rect::RectF32;
+    use crate::fill::FillRule;
+    use crate::test_util::rect_contour;
+    use std::f32::consts::{FRAC_PI_2, PI};
+    use super::{ARC_TOLERANCE, Contour, Outline, PointFlags};
+
+    #[test]
+    fn test_contains_point() {
+        // Two nested squares wound the same way.
+        let mut outline = Outline::new();
+        let outer = RectF32::new(Point2DF32::splat(0.0), Point2DF32::splat(10.0));
+        let inner = RectF32::new(Point2DF32::splat(2.0), Point2DF32::splat(6.0));
+        outline.push_contour(rect_contour(outer));
+        outline.push_contour(rect_contour(inner));
+        let center = Point2DF32::new(5.0, 5.0);
+        assert!(outline.contains_point(center, FillRule::Winding));
+        assert!(!outline.contains_point(center, FillRule::EvenOdd));
+        assert!(outline.contains_point(Point2DF32::new(1.0, 5.0), FillRule::EvenOdd));
+        assert!(!outline.contains_point(Point2DF32::new(11.0, 5.0), FillRule::Winding));
+
+        // A bulge to the right of the line from (0, 0) to (0, 10), left open.
+        let mut contour = Contour::new();
+        contour.push_endpoint(Point2DF32::new(0.0, 0.0));
+        contour.push_cubic(Point2DF32::new(10.0, 0.0),
+                           Point2DF32::new(10.0, 10.0),
+                           Point2DF32::new(0.0, 10.0));
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        assert!(outline.contains_point(Point2DF32::new(7.0, 5.0), FillRule::Winding));
+        assert!(!outline.contains_point(Point2DF32::new(7.0, 1.0), FillRule::Winding));
+        assert!(!outline.contains_point(Point2DF32::new(-1.0, 5.0), FillRule::Winding));
+    }
+
+    #[test]
+    fn test_arcs() {
+        // A circle large enough to need more than four curves.
+        let mut contour = Contour::new();
+        contour.push_ellipse_arc(Point2DF32::new(10.0, 20.0),
+                                 Point2DF32::splat(1000.0),
+                                 0.0,
+                                 0.0,
+                                 -2.0 * PI);
+        assert!(contour.len() > 13);
+        assert!((contour.position_of(0) - contour.position_of_last(1)).length() < 0.001);
+        for segment in contour.iter() {
+            for step in 0..=8 {
+                let point = segment.sample(step as f32 / 8.0);
+                let radius = (point - Point2DF32::new(10.0, 20.0)).length();
+                assert!((radius - 1000.0).abs() <= ARC_TOLERANCE, "{}", radius);
+            }
+        }
+
+        // A quarter of an ellipse rotated by a quarter turn, joined to the previous point by a
+        // line.
+        let mut contour = Contour::new();
+        contour.push_endpoint(Point2DF32::new(0.0, 0.0));
+        contour.push_ellipse_arc(Point2DF32::new(0.0, 0.0),
+                                 Point2DF32::new(2.0, 1.0),
+                                 FRAC_PI_2,
+                                 0.0,
+                                 FRAC_PI_2);
+        assert_eq!(contour.len(), 5);
+        assert!((contour.position_of(1) - Point2DF32::new(0.0, 2.0)).length() < 0.001);
+        assert!((contour.position_of(4) - Point2DF32::new(-1.0, 0.0)).length() < 0.001);
+
+        // `push_arc()` goes from the start angle to the end angle.
+        let mut contour = Contour::new();
+        contour.push_arc(Point2DF32::default(), 1.0, FRAC_PI_2, PI);
+        assert!((contour.position_of(0) - Point2DF32::new(0.0, 1.0)).length() < 0.001);
+        assert!((contour.position_of(3) - Point2DF32::new(-1.0, 0.0)).length() < 0.001);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut outline = Outline::new();
+        let square = RectF32::new(Point2DF32::default(), Point2DF32::splat(10.0));
+        outline.push_contour(rect_contour(square));
+        outline.bounds = RectF32::default();
+        assert!(outline.validate());
+        assert_eq!(outline.bounds(), square);
+
+        let mut corrupt = outline.clone();
+        corrupt.contours[0].flags.pop();
+        assert!(!corrupt.validate());
+
+        let mut corrupt = outline.clone();
+        corrupt.contours[0].points[1] = Point2DF32::new(f32::NAN, 0.0);
+        assert!(!corrupt.validate());
+
+        // Contours must start and end on an endpoint and have at most two control points in a
+        // row.
+        for &index in &[0, 3] {
+            let mut corrupt = outline.clone();
+            corrupt.contours[0].flags[index] = PointFlags::CONTROL_POINT_0;
+            assert!(!corrupt.validate());
+        }
+        let mut corrupt = outline.clone();
+        corrupt.contours[0].flags[1] = PointFlags::CONTROL_POINT_0;
+        corrupt.contours[0].flags[2] = PointFlags::CONTROL_POINT_1;
+        assert!(corrupt.validate());
+        corrupt.contours[0].flags[3] = PointFlags::CONTROL_POINT_1;
+        assert!(!corrupt.validate());
+    }
+}
@@ -0,0 +1,428 @@
+// pathfinder/geometry/src/path_data.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Reading and writing outlines as SVG path data, the language of the `d` attribute.
+
+use crate::basic::point::Point2DF32;
+use crate::outline::{Contour, Outline, PointFlags};
+use std::fmt::{self, Display, Formatter, Write};
+use std::mem;
+
+impl Outline {
+    /// Parses SVG path data, such as the contents of a `d` attribute.
+    ///
+    /// Elliptical arcs are approximated with cubic Bézier curves.
+    pub fn from_svg_path_data(data: &str) -> Result<Outline, PathDataError> {
+        PathDataParser::new(data).parse()
+    }
+
+    /// Writes this outline as SVG path data, using only absolute `M`, `L`, `Q`, `C` and `Z`
+    /// commands. The result parses back to an identical outline.
+    pub fn to_svg_path_data(&self) -> String {
+        let mut data = String::new();
+        for contour in &self.contours {
+            write_contour(&mut data, contour);
+        }
+        data
+    }
+}
+
+fn write_contour(data: &mut String, contour: &Contour) {
+    let mut index = 0;
+    while index < contour.points.len() {
+        let command = if index == 0 {
+            'M'
+        } else if contour.flags[index].contains(PointFlags::CONTROL_POINT_0) {
+            if index + 1 < contour.flags.len() &&
+                    contour.flags[index + 1].contains(PointFlags::CONTROL_POINT_1) {
+                'C'
+            } else {
+                'Q'
+            }
+        } else {
+            'L'
+        };
+        let point_count = match command {
+            'C' => 3,
+            'Q' => 2,
+            _ => 1,
+        };
+
+        if !data.is_empty() {
+            data.push(' ');
+        }
+        data.push(command);
+        for point in &contour.points[index..(index + point_count)] {
+            write!(data, " {} {}", point.x(), point.y()).unwrap();
+        }
+        index += point_count;
+    }
+
+    if contour.closed && !contour.points.is_empty() {
+        data.push_str(" Z");
+    }
+}
+
+/// An error encountered while parsing SVG path data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PathDataError {
+    /// The byte offset in the path data at which the error was found.
+    pub position: usize,
+    pub kind: PathDataErrorKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathDataErrorKind {
+    /// A character that doesn't begin a command, number or separator.
+    UnexpectedCharacter(char),
+    /// A command was missing some of its arguments.
+    ExpectedNumber,
+    /// An arc's large-arc or sweep flag was something other than `0` or `1`.
+    ExpectedFlag,
+    /// The path data didn't begin with a move-to command.
+    ExpectedMoveTo,
+}
+
+impl Display for PathDataError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self.kind {
+            PathDataErrorKind::UnexpectedCharacter(ch) => {
+                write!(formatter, "unexpected character {:?}", ch)?
+            }
+            PathDataErrorKind::ExpectedNumber => write!(formatter, "expected a number")?,
+            PathDataErrorKind::ExpectedFlag => write!(formatter, "expected a flag")?,
+            PathDataErrorKind::ExpectedMoveTo => write!(formatter, "expected a move-to command")?,
+        }
+        write!(formatter, " at offset {}", self.position)
+    }
+}
+
+struct PathDataParser<'a> {
+    data: &'a [u8],
+    position: usize,
+
+    outline: Outline,
+    contour: Contour,
+    current_point: Point2DF32,
+    subpath_start: Point2DF32,
+    // The control point to reflect for a following `S` or `T`, if the last command was a cubic or
+    // quadratic curve respectively.
+    last_cubic_ctrl: Option<Point2DF32>,
+    last_quadratic_ctrl: Option<Point2DF32>,
+}
+
+impl<'a> PathDataParser<'a> {
+    fn new(data: &'a str) -> PathDataParser<'a> {
+        PathDataParser {
+            data: data.as_bytes(),
+            position: 0,
+            outline: Outline::new(),
+            contour: Contour::new(),
+            current_point: Point2DF32::default(),
+            subpath_start: Point2DF32::default(),
+            last_cubic_ctrl: None,
+            last_quadratic_ctrl: None,
+        }
+    }
+
+    fn parse(mut self) -> Result<Outline, PathDataError> {
+        self.skip_whitespace();
+        let mut command = None;
+        while self.position < self.data.len() {
+            // A command letter can be omitted if it would repeat the previous one, except that
+            // coordinates following a move-to are line-tos.
+            let ch = self.data[self.position];
+            if ch.is_ascii_alphabetic() {
+                self.position += 1;
+                command = Some(ch);
+            } else {
+                command = match command {
+                    Some(b'M') => Some(b'L'),
+                    Some(b'm') => Some(b'l'),
+                    Some(b'Z') | Some(b'z') | None => {
+                        return Err(self.error(PathDataErrorKind::UnexpectedCharacter(ch as char)))
+                    }
+                    command => command,
+                };
+            }
+
+            let command = command.unwrap();
+            if self.outline.contours.is_empty() && self.contour.is_empty() &&
+                    command != b'M' && command != b'm' {
+                return Err(self.error(PathDataErrorKind::ExpectedMoveTo));
+            }
+            self.parse_command(command)?;
+            self.skip_whitespace();
+        }
+
+        self.flush_contour();
+        Ok(self.outline)
+    }
+
+    fn parse_command(&mut self, command: u8) -> Result<(), PathDataError> {
+        let absolute_command = command.to_ascii_uppercase();
+        let relative = command != absolute_command;
+        let (mut last_cubic_ctrl, mut last_quadratic_ctrl) = (None, None);
+        match absolute_command {
+            b'M' => {
+                let to = self.parse_point(relative)?;
+                self.flush_contour();
+                self.contour.push_endpoint(to);
+                self.current_point = to;
+                self.subpath_start = to;
+            }
+            b'L' => {
+                let to = self.parse_point(relative)?;
+                self.line_to(to);
+            }
+            b'H' => {
+                let mut x = self.parse_number()?;
+                if relative {
+                    x += self.current_point.x();
+                }
+                self.line_to(Point2DF32::new(x, self.current_point.y()));
+            }
+            b'V' => {
+                let mut y = self.parse_number()?;
+                if relative {
+                    y += self.current_point.y();
+                }
+                self.line_to(Point2DF32::new(self.current_point.x(), y));
+            }
+            b'C' | b'S' => {
+                let ctrl0 = if absolute_command == b'C' {
+                    self.parse_point(relative)?
+                } else {
+                    self.reflect(self.last_cubic_ctrl)
+                };
+                let ctrl1 = self.parse_point(relative)?;
+                let to = self.parse_point(relative)?;
+                self.start_segment();
+                self.contour.push_cubic(ctrl0, ctrl1, to);
+                self.current_point = to;
+                last_cubic_ctrl = Some(ctrl1);
+            }
+            b'Q' | b'T' => {
+                let ctrl = if absolute_command == b'Q' {
+                    self.parse_point(relative)?
+                } else {
+                    self.reflect(self.last_quadratic_ctrl)
+                };
+                let to = self.parse_point(relative)?;
+                self.start_segment();
+                self.contour.push_quadratic(ctrl, to);
+                self.current_point = to;
+                last_quadratic_ctrl = Some(ctrl);
+            }
+            b'A' => {
+                let radii = Point2DF32::new(self.parse_number()?, self.parse_number()?);
+                let x_axis_rotation = self.parse_number()?.to_radians();
+                let large_arc = self.parse_flag()?;
+                let sweep = self.parse_flag()?;
+                let to = self.parse_point(relative)?;
+                self.start_segment();
+                self.contour.push_svg_arc(radii, x_axis_rotation, large_arc, sweep, to);
+                self.current_point = to;
+            }
+            b'Z' => {
+                self.contour.close();
+                self.flush_contour();
+                self.current_point = self.subpath_start;
+            }
+            _ => {
+                self.position -= 1;
+                return Err(self.error(PathDataErrorKind::UnexpectedCharacter(command as char)));
+            }
+        }
+
+        self.last_cubic_ctrl = last_cubic_ctrl;
+        self.last_quadratic_ctrl = last_quadratic_ctrl;
+        Ok(())
+    }
+
+    fn line_to(&mut self, to: Point2DF32) {
+        self.start_segment();
+        self.contour.push_endpoint(to);
+        self.current_point = to;
+    }
+
+    // Drawing after a close-path without a move-to starts a new contour at the same place as the
+    // one that was closed.
+    fn start_segment(&mut self) {
+        if self.contour.is_empty() {
+            self.contour.push_endpoint(self.current_point);
+        }
+    }
+
+    fn flush_contour(&mut self) {
+        self.outline.push_contour(mem::replace(&mut self.contour, Contour::new()));
+    }
+
+    fn reflect(&self, ctrl: Option<Point2DF32>) -> Point2DF32 {
+        match ctrl {
+            Some(ctrl) => self.current_point.scale(2.0) - ctrl,
+            None => self.current_point,
+        }
+    }
+
+    fn parse_point(&mut self, relative: bool) -> Result<Point2DF32, PathDataError> {
+        let point = Point2DF32::new(self.parse_number()?, self.parse_number()?);
+        if relative {
+            Ok(point + self.current_point)
+        } else {
+            Ok(point)
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<f32, PathDataError> {
+        self.skip_separator();
+        let start = self.position;
+        self.skip_sign();
+        let integer_digits = self.skip_digits();
+        let fraction_digits = if self.eat(b'.') { self.skip_digits() } else { 0 };
+        if integer_digits == 0 && fraction_digits == 0 {
+            self.position = start;
+            return Err(self.error(PathDataErrorKind::ExpectedNumber));
+        }
+
+        // Only treat an `e` as an exponent if digits follow, so that it can't swallow a command.
+        let mantissa_end = self.position;
+        if self.eat(b'e') || self.eat(b'E') {
+            self.skip_sign();
+            if self.skip_digits() == 0 {
+                self.position = mantissa_end;
+            }
+        }
+
+        let string = String::from_utf8_lossy(&self.data[start..self.position]);
+        match string.parse() {
+            Ok(number) => Ok(number),
+            Err(_) => {
+                self.position = start;
+                Err(self.error(PathDataErrorKind::ExpectedNumber))
+            }
+        }
+    }
+
+    // Flags are single characters, so they needn't be separated from what follows.
+    fn parse_flag(&mut self) -> Result<bool, PathDataError> {
+        self.skip_separator();
+        if self.eat(b'0') {
+            Ok(false)
+        } else if self.eat(b'1') {
+            Ok(true)
+        } else {
+            Err(self.error(PathDataErrorKind::ExpectedFlag))
+        }
+    }
+
+    fn skip_sign(&mut self) {
+        if !self.eat(b'+') {
+            self.eat(b'-');
+        }
+    }
+
+    fn skip_digits(&mut self) -> usize {
+        let start = self.position;
+        while self.position < self.data.len() && self.data[self.position].is_ascii_digit() {
+            self.position += 1;
+        }
+        self.position - start
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.position < self.data.len() && is_whitespace(self.data[self.position]) {
+            self.position += 1;
+        }
+    }
+
+    // Skips whitespace with at most one comma in it.
+    fn skip_separator(&mut self) {
+        self.skip_whitespace();
+        if self.eat(b',') {
+            self.skip_whitespace();
+        }
+    }
+
+    fn eat(&mut self, ch: u8) -> bool {
+        if self.position < self.data.len() && self.data[self.position] == ch {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error(&self, kind: PathDataErrorKind) -> PathDataError {
+        PathDataError { position: self.position, kind }
+    }
+}
+
+fn is_whitespace(ch: u8) -> bool {
+    ch == b' ' || ch == b'\t' || ch == b'\n' || ch == b'\r' || ch == b'\x0c'
+}
+
+#[cfg(test)]
+mod test {
+    use crate::basic::point::Point2DF32;
+    use crate::outline::Outline;
+    use crate::test_util::assert_close;
+    use super::PathDataErrorKind;
+
+    #[test]
+    fn test_relative_and_implicit_commands() {
+        let outline = Outline::from_svg_path_data("m10,10 10-0 0 10h-10zM-1.5e1.5 1 2").unwrap();
+        assert_eq!(outline.to_svg_path_data(), "M 10 10 L 20 10 L 20 20 L 10 20 Z M -15 0.5 L 1 2");
+
+        // Drawing after a close-path continues from the start of the closed contour.
+        let outline = Outline::from_svg_path_data("M 1 1 L 2 1 z l 0 5").unwrap();
+        assert_eq!(outline.to_svg_path_data(), "M 1 1 L 2 1 Z M 1 1 L 1 6");
+    }
+
+    #[test]
+    fn test_smooth_curves() {
+        let outline = Outline::from_svg_path_data("M0 0 C 0 1 1 2 2 2 s 2 -1 2 -2 \
+                                                   M0 0 Q 1 1 2 0 t 2 0 T 6 0").unwrap();
+        assert_eq!(outline.to_svg_path_data(),
+                   "M 0 0 C 0 1 1 2 2 2 C 3 2 4 1 4 0 M 0 0 Q 1 1 2 0 Q 3 -1 4 0 Q 5 1 6 0");
+    }
+
+    #[test]
+    fn test_arcs() {
+        // A half circle of radius 5 around (5, 0), which passes through (5, 5), and then an arc
+        // whose radii are too small and are scaled up.
+        let outline = Outline::from_svg_path_data("M0 0 A5 5 0 0 0 10 0 a1 1 0 00-10 0").unwrap();
+        let contour = &outline.contours()[0];
+        assert_eq!(contour.len(), 13);
+        assert_close(contour.position_of(3), Point2DF32::new(5.0, 5.0));
+        assert_close(contour.position_of(6), Point2DF32::new(10.0, 0.0));
+        assert_close(contour.position_of(9), Point2DF32::new(5.0, -5.0));
+        assert_close(contour.position_of(12), Point2DF32::new(0.0, 0.0));
+
+        // Half of an ellipse that is twice as wide as it is tall.
+        let outline = Outline::from_svg_path_data("M0 0 A10 5 0 0 1 20 0").unwrap();
+        assert_close(outline.contours()[0].position_of(3), Point2DF32::new(10.0, -5.0));
+    }
+
+    #[test]
+    fn test_round_trip_and_errors() {
+        let data = "M 0.5 -1 Q 2 3 4 5 C 6 7 8 9 10 11 L 0.125 0 Z M 3 3";
+        assert_eq!(Outline::from_svg_path_data(data).unwrap().to_svg_path_data(), data);
+
+        let error = Outline::from_svg_path_data("L 1 1").unwrap_err();
+        assert_eq!(error.kind, PathDataErrorKind::ExpectedMoveTo);
+        let error = Outline::from_svg_path_data("M 1 1 L 2").unwrap_err();
+        assert_eq!((error.position, error.kind), (9, PathDataErrorKind::ExpectedNumber));
+        let error = Outline::from_svg_path_data("M 0 0 A 1 1 0 2 0 1 1").unwrap_err();
+        assert_eq!(error.kind, PathDataErrorKind::ExpectedFlag);
+        let error = Outline::from_svg_path_data("M 0 0 X").unwrap_err();
+        assert_eq!(error.kind, PathDataErrorKind::UnexpectedCharacter('X'));
+    }
+}
@@ -12,12 +12,14 @@
 
 use crate::basic::line_segment::LineSegmentF32;
 use crate::basic::point::Point2DF32;
+use crate::basic::rect::RectF32;
 use crate::basic::transform2d::Transform2DF32;
 use crate::util::{self, EPSILON};
 use pathfinder_simd::default::F32x4;
-use std::f32::consts::SQRT_2;
 
 const MAX_NEWTON_ITERATIONS: u32 = 32;
+const MAX_ARC_LENGTH_SUBDIVISIONS: u32 = 16;
+const WINDING_BISECTION_ITERATIONS: u32 = 24;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Segment {
@@ -68,9 +70,10 @@ impl Segment {
         }
     }
 
-    /// Approximates an unit-length arc with a cubic Bézier curve.
-    /// 
-    /// The maximum supported `sweep_angle` is π/2 (i.e. 90°).
+    /// Approximates an unit-length arc with a cubic Bézier curve. The arc is centered on the
+    /// positive x axis.
+    ///
+    /// The error grows quickly with the angle, so `sweep_angle` should be at most π/2 (i.e. 90°).
     pub fn arc(sweep_angle: f32) -> Segment {
         // Aleksas Riškus, "Approximation of a Cubic Bézier Curve by Circular Arcs and Vice Versa"
         // 2006.
@@ -79,11 +82,10 @@ impl Segment {
         let phi = 0.5 * sweep_angle;
         let p0 = Point2DF32::new(f32::cos(phi), f32::sin(phi));
         let p3 = p0.scale_xy(Point2DF32::new(1.0, -1.0));
-        let p1 = p0 - p3.yx().scale(K);
-        let p2 = p3 + p0.yx().scale(K);
-        return Segment::cubic(&LineSegmentF32::new(p3, p0), &LineSegmentF32::new(p2, p1));
-
-        const K: f32 = 4.0 / 3.0 * (SQRT_2 - 1.0);
+        let k = 4.0 / 3.0 * f32::tan(0.5 * phi);
+        let p1 = p0 - p3.yx().scale(k);
+        let p2 = p3 + p0.yx().scale(k);
+        Segment::cubic(&LineSegmentF32::new(p3, p0), &LineSegmentF32::new(p2, p1))
     }
 
     #[inline]
@@ -197,6 +199,92 @@ impl Segment {
         }
     }
 
+    /// Returns the length of this segment, accurate to roughly `tolerance`.
+    pub fn arc_length(&self, tolerance: f32) -> f32 {
+        if self.is_none() {
+            0.0
+        } else if self.is_line() {
+            self.baseline.vector().length()
+        } else {
+            self.to_cubic().as_cubic_segment().arc_length(tolerance)
+        }
+    }
+
+    /// Returns the `t` value at which the length of the segment up to that point is `distance`,
+    /// accurate to roughly `tolerance`.
+    pub fn time_for_distance(&self, distance: f32, tolerance: f32) -> f32 {
+        if self.is_none() {
+            0.0
+        } else if self.is_line() {
+            let length = self.baseline.vector().length();
+            if length == 0.0 {
+                0.0
+            } else {
+                util::clamp(distance / length, 0.0, 1.0)
+            }
+        } else {
+            self.to_cubic().as_cubic_segment().time_for_distance(distance, tolerance)
+        }
+    }
+
+    /// The bounding box of the endpoints and control points.
+    pub(crate) fn bounds(&self) -> RectF32 {
+        let (mut min, mut max) = (self.baseline.from(), self.baseline.from());
+        let mut points = vec![self.baseline.to()];
+        if !self.is_line() {
+            points.push(self.ctrl.from());
+            if self.is_cubic() {
+                points.push(self.ctrl.to());
+            }
+        }
+        for point in points {
+            min = min.min(point);
+            max = max.max(point);
+        }
+        RectF32::from_points(min, max)
+    }
+
+    /// Returns this segment's contribution to the winding number around `point`: the signed
+    /// number of times it crosses the ray cast from `point` toward positive x.
+    pub(crate) fn winding_number(&self, point: Point2DF32) -> i32 {
+        if self.is_none() {
+            return 0;
+        }
+        if self.is_line() {
+            return monotonic_winding_number(&self.baseline, point, |t| self.baseline.sample(t));
+        }
+
+        let bounds = self.bounds();
+        if point.y() < bounds.min_y() || point.y() >= bounds.max_y() || point.x() > bounds.max_x() {
+            return 0;
+        }
+
+        // Split the curve into pieces that are monotonic in y.
+        let cubic = self.to_cubic();
+        let mut pieces = vec![];
+        match cubic.as_cubic_segment().y_extrema() {
+            (None, _) => pieces.push(cubic),
+            (Some(t0), None) => {
+                let (before, after) = cubic.split(t0);
+                pieces.push(before);
+                pieces.push(after);
+            }
+            (Some(t0), Some(t1)) => {
+                let (before, rest) = cubic.split(t0);
+                let (middle, after) = rest.split((t1 - t0) / (1.0 - t0));
+                pieces.push(before);
+                pieces.push(middle);
+                pieces.push(after);
+            }
+        }
+
+        pieces.iter().map(|piece| {
+            monotonic_winding_number(&piece.baseline, point, |t| {
+                piece.as_cubic_segment().sample_polynomial(t)
+            })
+        }).sum()
+    }
+
     #[inline]
     pub fn transform(self, transform: &Transform2DF32) -> Segment {
         Segment {
@@ -311,6 +399,68 @@ impl<'s> CubicSegment<'s> {
         self.split(t).0.baseline.to()
     }
 
+    pub fn arc_length(self, tolerance: f32) -> f32 {
+        return arc_length(self, tolerance, 0);
+
+        fn arc_length(segment: CubicSegment, tolerance: f32, depth: u32) -> f32 {
+            // The true length lies between the chord and the length of the control polygon.
+            let (chord, polygon) = segment.chord_and_polygon_lengths();
+            if polygon - chord <= tolerance || depth == MAX_ARC_LENGTH_SUBDIVISIONS {
+                return (chord + polygon) * 0.5;
+            }
+
+            let (before, after) = segment.split(0.5);
+            arc_length(before.as_cubic_segment(), tolerance, depth + 1) +
+                arc_length(after.as_cubic_segment(), tolerance, depth + 1)
+        }
+    }
+
+    pub fn time_for_distance(self, distance: f32, tolerance: f32) -> f32 {
+        return time_for_distance(self, distance, tolerance, 0);
+
+        fn time_for_distance(segment: CubicSegment, distance: f32, tolerance: f32, depth: u32)
+                             -> f32 {
+            if distance <= 0.0 {
+                return 0.0;
+            }
+
+            let (chord, polygon) = segment.chord_and_polygon_lengths();
+            if polygon - chord <= tolerance || depth == MAX_ARC_LENGTH_SUBDIVISIONS {
+                let length = (chord + polygon) * 0.5;
+                return if distance >= length { 1.0 } else { distance / length };
+            }
+
+            let (before, after) = segment.split(0.5);
+            let (before, after) = (before.as_cubic_segment(), after.as_cubic_segment());
+            let before_length = before.arc_length(tolerance);
+            if distance < before_length {
+                0.5 * time_for_distance(before, distance, tolerance, depth + 1)
+            } else {
+                0.5 + 0.5 * time_for_distance(after,
+                                              distance - before_length,
+                                              tolerance,
+                                              depth + 1)
+            }
+        }
+    }
+
+    fn chord_and_polygon_lengths(self) -> (f32, f32) {
+        let (p0, p3) = (self.0.baseline.from(), self.0.baseline.to());
+        let (p1, p2) = (self.0.ctrl.from(), self.0.ctrl.to());
+        let chord = (p3 - p0).length();
+        let polygon = (p1 - p0).length() + (p2 - p1).length() + (p3 - p2).length();
+        (chord, polygon)
+    }
+
+    // Like `sample()`, but evaluates the polynomial directly instead of splitting.
+    fn sample_polynomial(self, t: f32) -> Point2DF32 {
+        let (p0, p3) = (self.0.baseline.from(), self.0.baseline.to());
+        let (p1, p2) = (self.0.ctrl.from(), self.0.ctrl.to());
+        let u = 1.0 - t;
+        p0.scale(u * u * u) + p1.scale(3.0 * u * u * t) + p2.scale(3.0 * u * t * t) +
+            p3.scale(t * t * t)
+    }
+
     #[inline]
     pub fn is_monotonic(self) -> bool {
         // TODO(pcwalton): Optimize this.
@@ -394,3 +544,31 @@ impl<'s> CubicSegment<'s> {
         f32::max(self.0.baseline.max_y(), self.0.ctrl.max_y())
     }
 }
+
+// Returns the contribution to the winding number around `point` of a curve that is monotonic in
+// y, given its endpoints and a function to evaluate it. The ray crosses the curve where it
+// reaches the height of the point, which is found by bisection.
+fn monotonic_winding_number<F>(endpoints: &LineSegmentF32, point: Point2DF32, sample: F) -> i32
+                               where F: Fn(f32) -> Point2DF32 {
+    let (from_y, to_y) = (endpoints.from_y(), endpoints.to_y());
+    if point.y() < f32::min(from_y, to_y) || point.y() >= f32::max(from_y, to_y) {
+        return 0;
+    }
+
+    let direction = if from_y < to_y { 1 } else { -1 };
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..WINDING_BISECTION_ITERATIONS {
+        let middle = 0.5 * (low + high);
+        if (sample(middle).y() < point.y()) == (direction > 0) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    if sample(0.5 * (low + high)).x() > point.x() {
+        direction
+    } else {
+        0
+    }
+}
@@ -13,9 +13,13 @@
 use crate::basic::line_segment::LineSegmentF32;
 use crate::basic::point::Point2DF32;
 use crate::basic::rect::RectF32;
+use crate::fill::FillRule;
+use crate::measure::ContourMeasure;
 use crate::outline::{Contour, Outline};
 use crate::segment::Segment;
+use std::f32::consts::{FRAC_PI_2, PI, SQRT_2};
 use std::mem;
+use std::sync::Arc;
 
 const TOLERANCE: f32 = 0.01;
 
@@ -24,23 +28,37 @@ pub struct OutlineStrokeToFill {
     pub style: StrokeStyle,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// How a path is stroked.
+///
+/// This isn't `Copy`, because of the dash array. The array is shared, though, so cloning is cheap.
+#[derive(Clone, Debug, PartialEq)]
 pub struct StrokeStyle {
     pub line_width: f32,
     pub line_cap: LineCap,
     pub line_join: LineJoin,
+    /// The limit on the ratio of the miter length to half the line width, past which miter joins
+    /// are drawn as bevels.
+    pub miter_limit: f32,
+    /// Alternating lengths of dashes and gaps, starting with a dash. An odd number of lengths is
+    /// repeated to make it even. If this is empty, invalid or too short to draw, the stroke is
+    /// solid. Zero-length dashes are drawn as dots with round or square caps.
+    pub dash_array: Arc<[f32]>,
+    /// How far into the dash pattern each contour starts.
+    pub dash_offset: f32,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum LineCap {
     Butt,
     Square,
+    Round,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum LineJoin {
     Miter,
     Bevel,
+    Round,
 }
 
 impl OutlineStrokeToFill {
@@ -50,33 +68,36 @@ impl OutlineStrokeToFill {
     }
 
     pub fn offset(&mut self) {
+        let dots = self.dash();
+
         let mut new_contours = vec![];
         for input in mem::replace(&mut self.outline.contours, vec![]) {
             let closed = input.closed;
-            let mut stroker = ContourStrokeToFill::new(input,
-                                                       Contour::new(),
-                                                       self.style.line_width * 0.5,
-                                                       self.style.line_join);
+            let mut stroker = ContourStrokeToFill::new(input, Contour::new(), &self.style);
 
             stroker.offset_forward();
             if closed {
-                self.push_stroked_contour(&mut new_contours, stroker.output, true);
-                stroker = ContourStrokeToFill::new(stroker.input,
-                                                   Contour::new(),
-                                                   self.style.line_width * 0.5,
-                                                   self.style.line_join);
+                stroker.add_closing_join();
+                self.push_stroked_contour(&mut new_contours, stroker.output);
+                stroker = ContourStrokeToFill::new(stroker.input, Contour::new(), &self.style);
             } else {
                 self.add_cap(&mut stroker.output);
             }
 
             stroker.offset_backward();
             if closed {
-                // TODO(pcwalton): Line join.
+                stroker.add_closing_join();
             } else {
                 self.add_cap(&mut stroker.output);
             }
 
-            self.push_stroked_contour(&mut new_contours, stroker.output, closed);
+            self.push_stroked_contour(&mut new_contours, stroker.output);
+        }
+
+        for (position, tangent) in dots {
+            if let Some(dot) = self.dot(position, tangent) {
+                new_contours.push(dot);
+            }
         }
 
         let mut new_bounds = None;
@@ -86,16 +107,57 @@ impl OutlineStrokeToFill {
         self.outline.bounds = new_bounds.unwrap_or_else(|| RectF32::default());
     }
 
-    fn push_stroked_contour(&mut self,
-                            new_contours: &mut Vec<Contour>,
-                            mut contour: Contour,
-                            closed: bool) {
-        // Add join if necessary.
-        if closed && contour.needs_join(self.style.line_join) {
-            let (p1, p0) = (contour.position_of(1), contour.position_of(0));
-            contour.add_join(self.style.line_join, &LineSegmentF32::new(p0, p1));
+    // Splits contours into one open contour per dash. Each dash is then stroked on its own, so it
+    // gets caps at both ends. Returns the position and direction of each zero-length dash.
+    fn dash(&mut self) -> Vec<(Point2DF32, Point2DF32)> {
+        let mut dots = vec![];
+        let mut dash_array = self.style.dash_array.to_vec();
+        if dash_array.iter().any(|&length| length < 0.0 || !length.is_finite()) {
+            return dots;
+        }
+        // A pattern this short would produce an unbounded number of invisible dashes.
+        if dash_array.iter().sum::<f32>() < TOLERANCE {
+            return dots;
+        }
+        if dash_array.len() % 2 == 1 {
+            dash_array.extend_from_slice(&self.style.dash_array);
         }
 
+        let mut new_contours = vec![];
+        for input in mem::replace(&mut self.outline.contours, vec![]) {
+            let mut dasher = ContourDash::new(&input, &dash_array, self.style.dash_offset);
+            dasher.dash(&mut new_contours, &mut dots);
+        }
+        self.outline.contours = new_contours;
+        dots
+    }
+
+    // Returns the shape of a zero-length dash, which is just its two caps.
+    fn dot(&self, position: Point2DF32, tangent: Point2DF32) -> Option<Contour> {
+        let width = self.style.line_width;
+        let along = tangent.scale(width * 0.5);
+        let across = tangent.yx().scale_xy(Point2DF32::new(width, -width));
+
+        let mut contour = Contour::new();
+        match self.style.line_cap {
+            LineCap::Butt => return None,
+            LineCap::Round => {
+                contour.push_endpoint(position - across.scale(0.5));
+                contour.push_arc_from_last(position, -2.0 * PI);
+            }
+            LineCap::Square => {
+                let start = position - across.scale(0.5);
+                contour.push_endpoint(start - along);
+                contour.push_endpoint(start + along);
+                contour.push_endpoint(start + along + across);
+                contour.push_endpoint(start - along + across);
+            }
+        }
+        contour.close();
+        Some(contour)
+    }
+
+    fn push_stroked_contour(&mut self, new_contours: &mut Vec<Contour>, mut contour: Contour) {
         contour.closed = true;
         new_contours.push(contour);
     }
@@ -108,10 +170,17 @@ impl OutlineStrokeToFill {
         let width = self.style.line_width;
         let (p0, p1) = (contour.position_of_last(2), contour.position_of_last(1));
         let gradient = (p1 - p0).normalize();
-        let offset = gradient.scale(width * 0.5);
+        let across = gradient.yx().scale_xy(Point2DF32::new(width, -width));
+
+        if self.style.line_cap == LineCap::Round {
+            // A half circle centered on the end of the centerline.
+            contour.push_arc_from_last(p1 + across.scale(0.5), -PI);
+            return;
+        }
 
+        let offset = gradient.scale(width * 0.5);
         let p2 = p1 + offset;
-        let p3 = p2 + gradient.yx().scale_xy(Point2DF32::new(width, -width));
+        let p3 = p2 + across;
         let p4 = p3 - offset;
 
         contour.push_endpoint(p2);
@@ -120,23 +189,112 @@ impl OutlineStrokeToFill {
     }
 }
 
+struct ContourDash<'a> {
+    input: &'a Contour,
+    measure: ContourMeasure,
+    dash_array: &'a [f32],
+    dash_index: usize,
+    // How much of the dash or gap the contour starts in is left.
+    remaining: f32,
+    // The segment of `measure` that the current dash or gap starts in.
+    segment_cursor: usize,
+}
+
+impl<'a> ContourDash<'a> {
+    fn new(input: &'a Contour, dash_array: &'a [f32], dash_offset: f32) -> ContourDash<'a> {
+        let pattern_length: f32 = dash_array.iter().sum();
+        let mut offset = dash_offset % pattern_length;
+        if offset < 0.0 {
+            offset += pattern_length;
+        }
+
+        // Zero-length dashes right at the start are kept.
+        let mut dash_index = 0;
+        while offset > 0.0 && offset >= dash_array[dash_index] {
+            offset -= dash_array[dash_index];
+            dash_index = (dash_index + 1) % dash_array.len();
+        }
+
+        ContourDash {
+            input,
+            measure: ContourMeasure::new(input, TOLERANCE),
+            dash_array,
+            dash_index,
+            remaining: dash_array[dash_index] - offset,
+            segment_cursor: 0,
+        }
+    }
+
+    fn dash(&mut self, output: &mut Vec<Contour>, dots: &mut Vec<(Point2DF32, Point2DF32)>) {
+        let first_output_index = output.len();
+        let starts_on = self.dash_index % 2 == 0;
+        let length = self.measure.length();
+
+        let (mut distance, mut ends_on) = (0.0, false);
+        while distance < length {
+            let end = f32::min(distance + self.remaining, length);
+            let on = self.dash_index % 2 == 0;
+            ends_on = on && end > distance;
+            if ends_on {
+                let dash =
+                    self.measure.sub_contour_from_segment(distance, end, &mut self.segment_cursor);
+                if !dash.is_empty() {
+                    output.push(dash);
+                }
+            } else if on {
+                if let Some(dot) = self.measure.sample(distance) {
+                    dots.push(dot);
+                }
+            }
+
+            distance = end;
+            self.dash_index = (self.dash_index + 1) % self.dash_array.len();
+            self.remaining = self.dash_array[self.dash_index];
+        }
+
+        if !self.input.closed || !starts_on || !ends_on || output.len() == first_output_index {
+            return;
+        }
+
+        if output.len() == first_output_index + 1 {
+            // The whole contour lies within one dash.
+            output[first_output_index] = self.input.clone();
+        } else {
+            // The dash that runs through the closing point continues into the first one.
+            let mut last = output.pop().unwrap();
+            for segment in output[first_output_index].iter() {
+                last.push_segment(segment, true);
+            }
+            output[first_output_index] = last;
+        }
+    }
+}
+
 struct ContourStrokeToFill {
     input: Contour,
     output: Contour,
     radius: f32,
     join: LineJoin,
+    miter_limit: f32,
 }
 
 impl ContourStrokeToFill {
     #[inline]
-    fn new(input: Contour, output: Contour, radius: f32, join: LineJoin) -> ContourStrokeToFill {
-        ContourStrokeToFill { input, output, radius, join }
+    fn new(input: Contour, output: Contour, style: &StrokeStyle) -> ContourStrokeToFill {
+        ContourStrokeToFill {
+            input,
+            output,
+            radius: style.line_width * 0.5,
+            join: style.line_join,
+            miter_limit: style.miter_limit,
+        }
     }
 
     fn offset_forward(&mut self) {
-        for (segment_index, segment) in self.input.iter().enumerate() {
+        let segments: Vec<_> = self.input.iter().collect();
+        for (segment_index, segment) in segments.iter().enumerate() {
             let join = if segment_index == 0 { LineJoin::Bevel } else { self.join };
-            segment.offset(self.radius, join, &mut self.output);
+            self.offset_segment(segment, join);
         }
     }
 
@@ -149,28 +307,54 @@ impl ContourStrokeToFill {
         segments.reverse();
         for (segment_index, segment) in segments.iter().enumerate() {
             let join = if segment_index == 0 { LineJoin::Bevel } else { self.join };
-            segment.offset(self.radius, join, &mut self.output);
+            self.offset_segment(segment, join);
+        }
+    }
+
+    fn offset_segment(&mut self, segment: &Segment, join: LineJoin) {
+        // Zero-length lines (e.g. an explicit line back to the start right before a close) have
+        // no direction to offset along. Skip them; the neighboring join covers the vertex.
+        if segment.is_line() && segment.baseline.square_length() < TOLERANCE * TOLERANCE {
+            return;
         }
+
+        let pivot = segment.baseline.from();
+        let next_tangent = segment.start_tangent().offset(self.radius);
+        self.output.add_join(join, pivot, &next_tangent, self.radius, self.miter_limit);
+        segment.offset(self.radius, &mut self.output);
+    }
+
+    // Joins the end of the output back to its start, around the first point of the input.
+    fn add_closing_join(&mut self) {
+        if self.output.len() < 2 {
+            return;
+        }
+
+        let pivot = self.input.position_of(0);
+        let (p0, p1) = (self.output.position_of(0), self.output.position_of(1));
+        let next_tangent = LineSegmentF32::new(p0, p1);
+        self.output.add_join(self.join, pivot, &next_tangent, self.radius, self.miter_limit);
     }
 }
 
 trait Offset {
-    fn offset(&self, distance: f32, join: LineJoin, contour: &mut Contour);
-    fn add_to_contour(&self, join: LineJoin, contour: &mut Contour);
+    fn offset(&self, distance: f32, contour: &mut Contour);
+    fn add_to_contour(&self, contour: &mut Contour);
     fn offset_once(&self, distance: f32) -> Self;
     fn error_is_within_tolerance(&self, other: &Segment, distance: f32) -> bool;
+    fn start_tangent(&self) -> LineSegmentF32;
 }
 
 impl Offset for Segment {
-    fn offset(&self, distance: f32, join: LineJoin, contour: &mut Contour) {
+    fn offset(&self, distance: f32, contour: &mut Contour) {
         if self.baseline.square_length() < TOLERANCE * TOLERANCE {
-            self.add_to_contour(join, contour);
+            self.add_to_contour(contour);
             return;
         }
 
         let candidate = self.offset_once(distance);
         if self.error_is_within_tolerance(&candidate, distance) {
-            candidate.add_to_contour(join, contour);
+            candidate.add_to_contour(contour);
             return;
         }
 
@@ -178,29 +362,30 @@ impl Offset for Segment {
         debug!("... PRE-SPLIT: {:?}", self);
         let (before, after) = self.split(0.5);
         debug!("... AFTER-SPLIT: {:?} {:?}", before, after);
-        before.offset(distance, join, contour);
-        after.offset(distance, join, contour);
+        before.offset(distance, contour);
+        after.offset(distance, contour);
     }
 
-    fn add_to_contour(&self, join: LineJoin, contour: &mut Contour) {
-        // Add join if necessary.
-        if contour.needs_join(join) {
-            let p3 = self.baseline.from();
-            let p4 = if self.is_line() {
-                self.baseline.to()
-            } else {
-                // NB: If you change the representation of quadratic curves, you will need to
-                // change this.
-                self.ctrl.from()
-            };
-
-            contour.add_join(join, &LineSegmentF32::new(p4, p3));
-        }
-
-        // Push segment.
+    fn add_to_contour(&self, contour: &mut Contour) {
         contour.push_full_segment(self, true);
     }
 
+    // The line from the start of the segment along its initial direction. This matches the first
+    // control polygon edge that `offset_once()` offsets.
+    fn start_tangent(&self) -> LineSegmentF32 {
+        let p0 = self.baseline.from();
+        let p1 = if self.is_line() {
+            self.baseline.to()
+        } else if self.is_cubic() && self.ctrl.from() == p0 {
+            self.ctrl.to()
+        } else {
+            // NB: If you change the representation of quadratic curves, you will need to
+            // change this.
+            self.ctrl.from()
+        };
+        LineSegmentF32::new(p0, p1)
+    }
+
     fn offset_once(&self, distance: f32) -> Segment {
         if self.is_line() {
             return Segment::line(&self.baseline.offset(distance));
@@ -299,18 +484,88 @@ impl Offset for Segment {
     }
 }
 
-impl Contour {
-    fn needs_join(&self, join: LineJoin) -> bool {
-        // TODO(pcwalton): Miter limit.
-        join == LineJoin::Miter && self.len() >= 2
+impl Outline {
+    /// Returns true if `point` is covered by the stroke of this outline drawn with `style`.
+    pub fn stroke_contains_point(&self, point: Point2DF32, style: &StrokeStyle) -> bool {
+        // Miters can reach at most `miter_limit` half-widths from the path, and square caps
+        // reach √2 half-widths from the endpoint.
+        let reach = 0.5 * style.line_width * f32::max(style.miter_limit, SQRT_2);
+        if !self.bounds.dilate(Point2DF32::splat(reach)).contains_point(point) {
+            return false;
+        }
+
+        let mut stroke_to_fill = OutlineStrokeToFill::new(self.clone(), style.clone());
+        stroke_to_fill.offset();
+        stroke_to_fill.outline.contains_point(point, FillRule::Winding)
     }
+}
+
+impl Contour {
+    // Connects the last point to the start of `next_tangent`, where both are offsets of `pivot`.
+    // Nothing is pushed for bevels; the next segment's start point closes the gap with a line.
+    fn add_join(&mut self,
+                join: LineJoin,
+                pivot: Point2DF32,
+                next_tangent: &LineSegmentF32,
+                radius: f32,
+                miter_limit: f32) {
+        if join == LineJoin::Bevel || self.len() < 2 {
+            return;
+        }
 
-    fn add_join(&mut self, _: LineJoin, next_tangent: &LineSegmentF32) {
-        // TODO(pcwalton): Round joins.
         let (p0, p1) = (self.position_of_last(2), self.position_of_last(1));
         let prev_tangent = LineSegmentF32::new(p0, p1);
-        if let Some(prev_tangent_t) = prev_tangent.intersection_t(&next_tangent) {
-            self.push_endpoint(prev_tangent.sample(prev_tangent_t));
+        let miter = prev_tangent.intersection_t(next_tangent).map(|t| prev_tangent.sample(t));
+
+        // Offsets are to the left of the direction of travel, so a left turn puts us on the
+        // inside of the corner. There the offset segments overlap, and going through their
+        // intersection keeps the outline free of spurious loops. A full reversal counts as
+        // outside, since the offsets end up on opposite sides of the pivot.
+        let (prev_vector, next_vector) = (prev_tangent.vector(), next_tangent.vector());
+        let turn = prev_vector.det(next_vector);
+        let outside = turn < 0.0 || (turn == 0.0 && prev_vector.dot(next_vector) < 0.0);
+        if !outside {
+            if let Some(miter) = miter {
+                self.push_endpoint(miter);
+            }
+            return;
+        }
+
+        match join {
+            LineJoin::Miter => {
+                if let Some(miter) = miter {
+                    if (miter - pivot).square_length() <= (miter_limit * radius).powi(2) {
+                        self.push_endpoint(miter);
+                    }
+                }
+            }
+            LineJoin::Round => {
+                // Right turns sweep clockwise, by less than a half turn.
+                let (from, to) = (p1 - pivot, next_tangent.from() - pivot);
+                let sweep_angle = -f32::atan2(f32::abs(from.det(to)), from.dot(to));
+                self.push_arc_from_last(pivot, sweep_angle);
+            }
+            LineJoin::Bevel => unreachable!(),
+        }
+    }
+
+    // Appends a circular arc around `center` starting at the last point and sweeping
+    // `sweep_angle` radians, using one cubic Bézier curve per quarter turn or less.
+    fn push_arc_from_last(&mut self, center: Point2DF32, sweep_angle: f32) {
+        let piece_count = f32::max(1.0, f32::ceil(f32::abs(sweep_angle) / FRAC_PI_2 - TOLERANCE));
+        let piece_angle = sweep_angle / piece_count;
+        let (sin, cos) = piece_angle.sin_cos();
+        let k = 4.0 / 3.0 * f32::tan(piece_angle * 0.25);
+
+        let mut vector = self.position_of_last(1) - center;
+        for _ in 0..(piece_count as u32) {
+            let next_vector = Point2DF32::new(vector.x() * cos - vector.y() * sin,
+                                              vector.x() * sin + vector.y() * cos);
+            let ctrl0 = center + vector + Point2DF32::new(-vector.y(), vector.x()).scale(k);
+            let ctrl1 = center + next_vector -
+                Point2DF32::new(-next_vector.y(), next_vector.x()).scale(k);
+            self.push_cubic(ctrl0, ctrl1, center + next_vector);
+            vector = next_vector;
         }
     }
 }
@@ -322,6 +577,9 @@ impl Default for StrokeStyle {
             line_width: 1.0,
             line_cap: LineCap::default(),
             line_join: LineJoin::default(),
+            miter_limit: 10.0,
+            dash_array: Arc::new([]),
+            dash_offset: 0.0,
         }
     }
 }
@@ -335,3 +593,146 @@ impl Default for LineJoin {
     #[inline]
     fn default() -> LineJoin { LineJoin::Miter }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::basic::point::Point2DF32;
+    use crate::basic::rect::RectF32;
+    use crate::fill::FillRule;
+    use crate::outline::{Contour, Outline};
+    use crate::test_util::assert_bounds_eq;
+    use super::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
+
+    fn stroke(points: &[(f32, f32)], closed: bool, style: StrokeStyle) -> Outline {
+        let mut contour = Contour::new();
+        for &(x, y) in points {
+            contour.push_endpoint(Point2DF32::new(x, y));
+        }
+        if closed {
+            contour.close();
+        }
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        let mut stroke_to_fill = OutlineStrokeToFill::new(outline, style);
+        stroke_to_fill.offset();
+        stroke_to_fill.outline
+    }
+
+    #[test]
+    fn test_round_cap() {
+        let style = StrokeStyle {
+            line_width: 2.0,
+            line_cap: LineCap::Round,
+            ..StrokeStyle::default()
+        };
+        let outline = stroke(&[(0.0, 0.0), (10.0, 0.0)], false, style);
+        assert_bounds_eq(outline.bounds(),
+                         RectF32::new(Point2DF32::new(-1.0, -1.0), Point2DF32::new(12.0, 2.0)));
+    }
+
+    #[test]
+    fn test_closed_contour_joins() {
+        let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let expected = RectF32::new(Point2DF32::new(-1.0, -1.0), Point2DF32::new(12.0, 12.0));
+        for &line_join in &[LineJoin::Miter, LineJoin::Round] {
+            let style = StrokeStyle { line_width: 2.0, line_join, ..StrokeStyle::default() };
+            let outline = stroke(&square, true, style);
+            assert_eq!(outline.contours().len(), 2);
+            assert_bounds_eq(outline.bounds(), expected);
+        }
+    }
+
+    #[test]
+    fn test_miter_limit() {
+        // A 45° corner has a miter ratio of 1 / sin(22.5°) ≈ 2.61.
+        let corner = [(0.0, 0.0), (10.0, 0.0), (0.0, -10.0)];
+        let mut style = StrokeStyle { line_width: 2.0, ..StrokeStyle::default() };
+        assert!(stroke(&corner, false, style.clone()).bounds().max_x() > 12.0);
+
+        style.miter_limit = 2.5;
+        assert!(stroke(&corner, false, style).bounds().max_x() < 11.0);
+    }
+
+    #[test]
+    fn test_dashes() {
+        let style = StrokeStyle {
+            line_width: 2.0,
+            dash_array: vec![2.0, 3.0].into(),
+            ..StrokeStyle::default()
+        };
+        let outline = stroke(&[(0.0, 0.0), (10.0, 0.0)], false, style);
+        assert_eq!(outline.contours().len(), 2);
+        assert_bounds_eq(outline.bounds(),
+                         RectF32::new(Point2DF32::new(0.0, -1.0), Point2DF32::new(7.0, 2.0)));
+    }
+
+    #[test]
+    fn test_dashes_across_closing_point() {
+        // The dash that straddles the start of the square is stroked as one piece, so there are
+        // four dashes rather than five.
+        let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let style = StrokeStyle {
+            line_width: 2.0,
+            dash_array: vec![5.0].into(),
+            dash_offset: 2.5,
+            ..StrokeStyle::default()
+        };
+        let outline = stroke(&square, true, style);
+        assert_eq!(outline.contours().len(), 4);
+    }
+
+    #[test]
+    fn test_zero_length_dashes() {
+        let line = [(0.0, 0.0), (10.0, 0.0)];
+        let expected = RectF32::new(Point2DF32::new(-1.0, -1.0), Point2DF32::new(7.0, 2.0));
+        for &line_cap in &[LineCap::Round, LineCap::Square] {
+            let style = StrokeStyle {
+                line_width: 2.0,
+                line_cap,
+                dash_array: vec![0.0, 5.0].into(),
+                ..StrokeStyle::default()
+            };
+            let outline = stroke(&line, false, style);
+            assert_eq!(outline.contours().len(), 2);
+            assert_bounds_eq(outline.bounds(), expected);
+            assert!(outline.contains_point(Point2DF32::new(5.0, 0.5), FillRule::Winding));
+        }
+
+        let style = StrokeStyle {
+            line_width: 2.0,
+            dash_array: vec![0.0, 5.0].into(),
+            ..StrokeStyle::default()
+        };
+        assert!(stroke(&line, false, style).contours().is_empty());
+    }
+
+    #[test]
+    fn test_degenerate_dash_pattern() {
+        let style = StrokeStyle {
+            line_width: 2.0,
+            dash_array: vec![1e-6, 1e-6].into(),
+            ..StrokeStyle::default()
+        };
+        let outline = stroke(&[(0.0, 0.0), (10.0, 0.0)], false, style);
+        assert_eq!(outline.contours().len(), 1);
+        assert_bounds_eq(outline.bounds(),
+                         RectF32::new(Point2DF32::new(0.0, -1.0), Point2DF32::new(10.0, 2.0)));
+    }
+
+    #[test]
+    fn test_stroke_contains_point() {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Point2DF32::new(0.0, 0.0));
+        contour.push_endpoint(Point2DF32::new(10.0, 0.0));
+        contour.push_endpoint(Point2DF32::new(10.0, 10.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        let style = StrokeStyle { line_width: 2.0, ..StrokeStyle::default() };
+        assert!(outline.stroke_contains_point(Point2DF32::new(5.0, 0.5), &style));
+        assert!(outline.stroke_contains_point(Point2DF32::new(5.0, -0.5), &style));
+        assert!(!outline.stroke_contains_point(Point2DF32::new(5.0, -1.5), &style));
+        assert!(!outline.stroke_contains_point(Point2DF32::new(7.0, 3.0), &style));
+    }
+}
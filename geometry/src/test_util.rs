@@ -0,0 +1,42 @@
+// pathfinder/geometry/src/test_util.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Helpers shared by the unit tests.
+
+use crate::basic::point::Point2DF32;
+use crate::basic::rect::RectF32;
+use crate::outline::{Contour, Outline};
+
+pub(crate) fn assert_close(actual: Point2DF32, expected: Point2DF32) {
+    assert!((actual - expected).length() < 0.001, "{:?} != {:?}", actual, expected);
+}
+
+pub(crate) fn assert_bounds_eq(actual: RectF32, expected: RectF32) {
+    let (actual_min, expected_min) = (actual.origin(), expected.origin());
+    let (actual_max, expected_max) = (actual.lower_right(), expected.lower_right());
+    assert!((actual_min - expected_min).length() < 0.01, "{:?} != {:?}", actual, expected);
+    assert!((actual_max - expected_max).length() < 0.01, "{:?} != {:?}", actual, expected);
+}
+
+pub(crate) fn rect_contour(rect: RectF32) -> Contour {
+    let mut contour = Contour::new();
+    contour.push_endpoint(rect.origin());
+    contour.push_endpoint(rect.upper_right());
+    contour.push_endpoint(rect.lower_right());
+    contour.push_endpoint(rect.lower_left());
+    contour.close();
+    contour
+}
+
+pub(crate) fn rect_outline(rect: RectF32) -> Outline {
+    let mut outline = Outline::new();
+    outline.push_contour(rect_contour(rect));
+    outline
+}
@@ -85,6 +85,19 @@ impl GLDevice {
                                           gl::ONE); ck();
                     gl::Enable(gl::BLEND); ck();
                 }
+                BlendState::SrcOverPremultiplied => {
+                    gl::BlendEquation(gl::FUNC_ADD); ck();
+                    gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA); ck();
+                    gl::Enable(gl::BLEND); ck();
+                }
+                BlendState::SrcOverStraight => {
+                    gl::BlendEquation(gl::FUNC_ADD); ck();
+                    gl::BlendFuncSeparate(gl::SRC_ALPHA,
+                                          gl::ONE_MINUS_SRC_ALPHA,
+                                          gl::ONE,
+                                          gl::ONE_MINUS_SRC_ALPHA); ck();
+                    gl::Enable(gl::BLEND); ck();
+                }
             }
 
             // Set depth.
@@ -131,7 +144,9 @@ impl GLDevice {
                 BlendState::Off => {}
                 BlendState::RGBOneAlphaOneMinusSrcAlpha |
                 BlendState::RGBOneAlphaOne |
-                BlendState::RGBSrcAlphaAlphaOneMinusSrcAlpha => {
+                BlendState::RGBSrcAlphaAlphaOneMinusSrcAlpha |
+                BlendState::SrcOverPremultiplied |
+                BlendState::SrcOverStraight => {
                     gl::Disable(gl::BLEND); ck();
                 }
             }
@@ -362,6 +377,9 @@ impl Device for GLDevice {
     fn set_uniform(&self, uniform: &Self::Uniform, data: UniformData) {
         unsafe {
             match data {
+                UniformData::Float(value) => {
+                    gl::Uniform1f(uniform.location, value); ck();
+                }
                 UniformData::Int(value) => {
                     gl::Uniform1i(uniform.location, value); ck();
                 }
@@ -450,6 +468,14 @@ impl Device for GLDevice {
         texture.size
     }
 
+    fn max_texture_size(&self) -> i32 {
+        let mut max_texture_size = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max_texture_size); ck();
+        }
+        max_texture_size
+    }
+
     fn upload_to_texture(&self, texture: &Self::Texture, size: Point2DI32, data: &[u8]) {
         assert!(data.len() >= size.x() as usize * size.y() as usize * 4);
         unsafe {
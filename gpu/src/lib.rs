@@ -65,6 +65,8 @@ pub trait Device {
     );
     fn framebuffer_texture<'f>(&self, framebuffer: &'f Self::Framebuffer) -> &'f Self::Texture;
     fn texture_size(&self, texture: &Self::Texture) -> Point2DI32;
+    /// The largest width or height a texture may have on this device.
+    fn max_texture_size(&self) -> i32;
     fn upload_to_texture(&self, texture: &Self::Texture, size: Point2DI32, data: &[u8]);
     fn read_pixels_from_default_framebuffer(&self, size: Point2DI32) -> Vec<u8>;
     fn clear(&self, params: &ClearParams);
@@ -115,6 +117,7 @@ pub trait Device {
 
         let mut load_include_tile_alpha_vertex =
             |_| load_shader_include(resources, "tile_alpha_vertex");
+        let mut load_include_tile_paint = |_| load_shader_include(resources, "tile_paint");
         let mut load_include_tile_monochrome =
             |_| load_shader_include(resources, "tile_monochrome");
         let mut load_include_tile_multicolor =
@@ -129,6 +132,7 @@ pub trait Device {
                 "include_tile_alpha_vertex",
                 &mut load_include_tile_alpha_vertex,
             )
+            .insert_lambda("include_tile_paint", &mut load_include_tile_paint)
             .insert_lambda("include_tile_monochrome", &mut load_include_tile_monochrome)
             .insert_lambda("include_tile_multicolor", &mut load_include_tile_multicolor)
             .insert_lambda(
@@ -204,6 +208,7 @@ pub enum ShaderKind {
 
 #[derive(Clone, Copy)]
 pub enum UniformData {
+    Float(f32),
     Int(i32),
     Mat2(F32x4),
     Mat4([F32x4; 4]),
@@ -241,6 +246,11 @@ pub enum BlendState {
     RGBOneAlphaOne,
     RGBOneAlphaOneMinusSrcAlpha,
     RGBSrcAlphaAlphaOneMinusSrcAlpha,
+    /// Porter-Duff source-over on premultiplied color, in every channel including alpha.
+    SrcOverPremultiplied,
+    /// Source-over of straight color into a premultiplied target, in every channel including
+    /// alpha.
+    SrcOverStraight,
 }
 
 #[derive(Clone, Copy, Default, Debug)]
@@ -0,0 +1,238 @@
+// pathfinder/renderer/src/blend.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Porter-Duff operators and the blend modes of the W3C Compositing and Blending spec.
+
+use pathfinder_geometry::color::ColorF;
+use pathfinder_simd::default::F32x4;
+
+/// How a path or group is combined with what's beneath it.
+///
+/// The discriminants must match the `BLEND_MODE_*` constants in `composite.fs.glsl`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BlendMode {
+    // Porter-Duff operators
+    SrcOver = 0,
+    Clear = 1,
+    Copy = 2,
+    SrcIn = 3,
+    SrcOut = 4,
+    SrcAtop = 5,
+    DestOver = 6,
+    DestIn = 7,
+    DestOut = 8,
+    DestAtop = 9,
+    Xor = 10,
+    Lighter = 11,
+
+    // Separable blend modes
+    Multiply = 12,
+    Screen = 13,
+    Overlay = 14,
+    Darken = 15,
+    Lighten = 16,
+    ColorDodge = 17,
+    ColorBurn = 18,
+    HardLight = 19,
+    SoftLight = 20,
+    Difference = 21,
+    Exclusion = 22,
+
+    // Non-separable blend modes
+    Hue = 23,
+    Saturation = 24,
+    Color = 25,
+    Luminosity = 26,
+}
+
+impl Default for BlendMode {
+    #[inline]
+    fn default() -> BlendMode {
+        BlendMode::SrcOver
+    }
+}
+
+impl BlendMode {
+    /// Whether this mode needs to read the contents of the target. Everything but source-over
+    /// does, which means the renderer has to draw it into a group and composite that.
+    #[inline]
+    pub fn needs_backdrop(self) -> bool {
+        self != BlendMode::SrcOver
+    }
+
+    /// Whether this mode leaves none of the source behind, only removing parts of the backdrop.
+    #[inline]
+    pub fn erases(self) -> bool {
+        self == BlendMode::Clear || self == BlendMode::DestIn || self == BlendMode::DestOut
+    }
+
+    #[inline]
+    fn is_porter_duff(self) -> bool {
+        (self as u8) < BlendMode::Multiply as u8
+    }
+
+    // The Porter-Duff factors of the source and the backdrop, as functions of their alphas.
+    fn porter_duff_factors(self, src_alpha: f32, dest_alpha: f32) -> (f32, f32) {
+        match self {
+            BlendMode::Clear => (0.0, 0.0),
+            BlendMode::Copy => (1.0, 0.0),
+            BlendMode::SrcIn => (dest_alpha, 0.0),
+            BlendMode::SrcOut => (1.0 - dest_alpha, 0.0),
+            BlendMode::SrcAtop => (dest_alpha, 1.0 - src_alpha),
+            BlendMode::DestOver => (1.0 - dest_alpha, 1.0),
+            BlendMode::DestIn => (0.0, src_alpha),
+            BlendMode::DestOut => (0.0, 1.0 - src_alpha),
+            BlendMode::DestAtop => (1.0 - dest_alpha, src_alpha),
+            BlendMode::Xor => (1.0 - dest_alpha, 1.0 - src_alpha),
+            BlendMode::Lighter => (1.0, 1.0),
+            _ => (1.0, 1.0 - src_alpha),
+        }
+    }
+}
+
+/// Composites premultiplied `src` onto premultiplied `dest`. This is the reference for
+/// `composite.fs.glsl`.
+pub(crate) fn composite(src: ColorF, dest: ColorF, blend_mode: BlendMode) -> ColorF {
+    let (src_alpha, dest_alpha) = (src.a(), dest.a());
+
+    // Blend modes mix the source color with the backdrop where both are present.
+    let mut src_color = src.0;
+    if !blend_mode.is_porter_duff() && src_alpha > 0.0 && dest_alpha > 0.0 {
+        let src_straight = src.0 * F32x4::splat(1.0 / src_alpha);
+        let dest_straight = dest.0 * F32x4::splat(1.0 / dest_alpha);
+        let blended = blend(src_straight, dest_straight, blend_mode);
+        let mixed = src_straight * F32x4::splat(1.0 - dest_alpha) +
+            blended * F32x4::splat(dest_alpha);
+        src_color = mixed * F32x4::splat(src_alpha);
+    }
+
+    let (src_factor, dest_factor) = blend_mode.porter_duff_factors(src_alpha, dest_alpha);
+    let rgb = src_color * F32x4::splat(src_factor) + dest.0 * F32x4::splat(dest_factor);
+    let alpha = src_alpha * src_factor + dest_alpha * dest_factor;
+    let result = F32x4::new(rgb[0], rgb[1], rgb[2], alpha);
+    ColorF(result.clamp(F32x4::default(), F32x4::splat(1.0)))
+}
+
+// Applies a blend mode to unpremultiplied colors.
+fn blend(src: F32x4, dest: F32x4, blend_mode: BlendMode) -> F32x4 {
+    let (src, dest) = ([src[0], src[1], src[2]], [dest[0], dest[1], dest[2]]);
+    let result = match blend_mode {
+        BlendMode::Hue => set_lum(set_sat(src, sat(dest)), lum(dest)),
+        BlendMode::Saturation => set_lum(set_sat(dest, sat(src)), lum(dest)),
+        BlendMode::Color => set_lum(src, lum(dest)),
+        BlendMode::Luminosity => set_lum(dest, lum(src)),
+        _ => {
+            let mut result = [0.0; 3];
+            for (result, (&src, &dest)) in result.iter_mut().zip(src.iter().zip(dest.iter())) {
+                *result = blend_channel(src, dest, blend_mode);
+            }
+            result
+        }
+    };
+    F32x4::new(result[0], result[1], result[2], 0.0)
+}
+
+fn blend_channel(src: f32, dest: f32, blend_mode: BlendMode) -> f32 {
+    match blend_mode {
+        BlendMode::Multiply => src * dest,
+        BlendMode::Screen => screen(src, dest),
+        BlendMode::Overlay => hard_light(dest, src),
+        BlendMode::Darken => f32::min(src, dest),
+        BlendMode::Lighten => f32::max(src, dest),
+        BlendMode::ColorDodge => {
+            if dest == 0.0 {
+                0.0
+            } else if src >= 1.0 {
+                1.0
+            } else {
+                f32::min(1.0, dest / (1.0 - src))
+            }
+        }
+        BlendMode::ColorBurn => {
+            if dest >= 1.0 {
+                1.0
+            } else if src == 0.0 {
+                0.0
+            } else {
+                1.0 - f32::min(1.0, (1.0 - dest) / src)
+            }
+        }
+        BlendMode::HardLight => hard_light(src, dest),
+        BlendMode::SoftLight => {
+            if src <= 0.5 {
+                dest - (1.0 - 2.0 * src) * dest * (1.0 - dest)
+            } else {
+                let d = if dest <= 0.25 {
+                    ((16.0 * dest - 12.0) * dest + 4.0) * dest
+                } else {
+                    f32::sqrt(dest)
+                };
+                dest + (2.0 * src - 1.0) * (d - dest)
+            }
+        }
+        BlendMode::Difference => f32::abs(dest - src),
+        BlendMode::Exclusion => dest + src - 2.0 * dest * src,
+        _ => src,
+    }
+}
+
+fn screen(src: f32, dest: f32) -> f32 {
+    dest + src - dest * src
+}
+
+fn hard_light(src: f32, dest: f32) -> f32 {
+    if src <= 0.5 {
+        dest * 2.0 * src
+    } else {
+        screen(2.0 * src - 1.0, dest)
+    }
+}
+
+fn lum(color: [f32; 3]) -> f32 {
+    0.3 * color[0] + 0.59 * color[1] + 0.11 * color[2]
+}
+
+fn sat(color: [f32; 3]) -> f32 {
+    f32::max(color[0], f32::max(color[1], color[2])) -
+        f32::min(color[0], f32::min(color[1], color[2]))
+}
+
+fn set_lum(color: [f32; 3], new_lum: f32) -> [f32; 3] {
+    let delta = new_lum - lum(color);
+    clip_color([color[0] + delta, color[1] + delta, color[2] + delta])
+}
+
+fn clip_color(color: [f32; 3]) -> [f32; 3] {
+    let l = lum(color);
+    let min = f32::min(color[0], f32::min(color[1], color[2]));
+    let max = f32::max(color[0], f32::max(color[1], color[2]));
+    let mut result = color;
+    for channel in &mut result {
+        if min < 0.0 {
+            *channel = l + (*channel - l) * l / (l - min);
+        }
+        if max > 1.0 {
+            *channel = l + (*channel - l) * (1.0 - l) / (max - l);
+        }
+    }
+    result
+}
+
+fn set_sat(color: [f32; 3], new_sat: f32) -> [f32; 3] {
+    let min = f32::min(color[0], f32::min(color[1], color[2]));
+    let max = f32::max(color[0], f32::max(color[1], color[2]));
+    let mut result = [0.0; 3];
+    if max > min {
+        for (result, &channel) in result.iter_mut().zip(color.iter()) {
+            *result = (channel - min) * new_sat / (max - min);
+        }
+    }
+    result
+}
@@ -13,26 +13,59 @@
 use crate::concurrent::executor::Executor;
 use crate::gpu_data::{AlphaTileBatchPrimitive, BuiltObject, FillBatchPrimitive, RenderCommand};
 use crate::options::{PreparedRenderOptions, RenderCommandListener};
-use crate::scene::Scene;
+use crate::blend::BlendMode;
+use crate::scene::{BuildError, PathId, Scene};
+use crate::tile_cache::{CachedTiles, TileCache};
 use crate::tile_map::DenseTileMap;
-use crate::tiles::{self, TILE_HEIGHT, TILE_WIDTH, Tiler};
+use crate::tiles::{self, Tiler};
 use crate::z_buffer::ZBuffer;
-use pathfinder_geometry::basic::line_segment::{LineSegmentF32, LineSegmentU4, LineSegmentU8};
+use pathfinder_geometry::basic::line_segment::{LineSegmentF32, LineSegmentU8};
 use pathfinder_geometry::basic::point::{Point2DF32, Point2DI32};
 use pathfinder_geometry::basic::rect::{RectF32, RectI32};
+use pathfinder_geometry::basic::transform2d::Transform2DF32;
+use pathfinder_geometry::fill::FillRule;
+use pathfinder_geometry::outline::Outline;
 use pathfinder_geometry::util;
 use pathfinder_simd::default::{F32x4, I32x4};
+use std::iter::Peekable;
+use std::mem;
+use std::ops::Range;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use std::u16;
 
+// The mask framebuffer holds 256×256 alpha tiles, but the last index marks solid tiles.
+const MAX_ALPHA_TILES: usize = 0xffff;
+
 pub(crate) struct SceneBuilder<'a> {
     scene: &'a Scene,
     built_options: &'a PreparedRenderOptions,
 
     pub(crate) next_alpha_tile_index: AtomicUsize,
-    pub(crate) z_buffer: ZBuffer,
     pub(crate) listener: Box<dyn RenderCommandListener>,
+    built_clip_paths: Vec<BuiltObject>,
+
+    // Each layer gets its own Z-buffer, since opaque paths in a layer only hide what's beneath
+    // them in that layer.
+    layers: Vec<Layer>,
+    z_buffers: Vec<ZBuffer>,
+    path_layers: Vec<u32>,
+
+    // The tiles of the last build, and those of this one as they're built.
+    tile_cache: Option<&'a TileCache>,
+    new_tile_cache_entries: Mutex<Vec<(PathId, Arc<CachedTiles>)>>,
+    reused_path_count: AtomicUsize,
+}
+
+// The scene itself, a group, or a path that has to be blended on its own. Layers are rendered
+// into intermediate targets, except for the scene.
+struct Layer {
+    path_range: Range<u32>,
+    opacity: f32,
+    blend_mode: BlendMode,
+    parent: Option<usize>,
+    children: Vec<usize>,
 }
 
 impl<'a> SceneBuilder<'a> {
@@ -40,38 +73,150 @@ impl<'a> SceneBuilder<'a> {
         scene: &'a Scene,
         built_options: &'a PreparedRenderOptions,
         listener: Box<dyn RenderCommandListener>,
+        tile_cache: Option<&'a TileCache>,
     ) -> SceneBuilder<'a> {
         let effective_view_box = scene.effective_view_box(built_options);
+
+        let path_count = scene.paths.len() as u32;
+        let mut layers = vec![Layer {
+            path_range: 0..path_count,
+            opacity: 1.0,
+            blend_mode: BlendMode::SrcOver,
+            parent: None,
+            children: vec![],
+        }];
+
+        // Groups come after their parents, so the innermost group of each path wins.
+        let mut path_layers = vec![0; scene.paths.len()];
+        for group in &scene.groups {
+            let layer = layers.len();
+            for path_index in group.path_range() {
+                path_layers[path_index as usize] = layer as u32;
+            }
+            let parent = group.parent().map_or(0, |parent| parent.0 as usize + 1);
+            layers.push(Layer {
+                path_range: group.path_range(),
+                opacity: group.opacity(),
+                blend_mode: group.blend_mode(),
+                parent: Some(parent),
+                children: vec![],
+            });
+        }
+        for (path_index, path_object) in scene.paths.iter().enumerate() {
+            if !path_object.blend_mode().needs_backdrop() {
+                continue;
+            }
+            let path_index = path_index as u32;
+            layers.push(Layer {
+                path_range: path_index..(path_index + 1),
+                opacity: 1.0,
+                blend_mode: path_object.blend_mode(),
+                parent: Some(path_layers[path_index as usize] as usize),
+                children: vec![],
+            });
+            path_layers[path_index as usize] = (layers.len() - 1) as u32;
+        }
+
+        for layer_index in 1..layers.len() {
+            let parent = layers[layer_index].parent.unwrap();
+            layers[parent].children.push(layer_index);
+        }
+        for layer_index in 0..layers.len() {
+            let mut children = mem::replace(&mut layers[layer_index].children, vec![]);
+            children.sort_by_key(|&child| layers[child].path_range.start);
+            layers[layer_index].children = children;
+        }
+
+        let tile_size = built_options.tile_size;
+        let z_buffers = layers.iter()
+                              .map(|_| ZBuffer::new(effective_view_box, tile_size))
+                              .collect();
+
         SceneBuilder {
             scene,
             built_options,
 
             next_alpha_tile_index: AtomicUsize::new(0),
-            z_buffer: ZBuffer::new(effective_view_box),
             listener,
+            built_clip_paths: vec![],
+
+            layers,
+            z_buffers,
+            path_layers,
+
+            tile_cache,
+            new_tile_cache_entries: Mutex::new(vec![]),
+            reused_path_count: AtomicUsize::new(0),
         }
     }
 
-    pub fn build<E>(&mut self, executor: &E) where E: Executor {
+    // Returns the tile cache entries of the paths of this build, and the number of them that
+    // were reused.
+    pub(crate) fn into_tile_cache_entries(self) -> (Vec<(PathId, Arc<CachedTiles>)>, usize) {
+        (self.new_tile_cache_entries.into_inner().unwrap(), self.reused_path_count.into_inner())
+    }
+
+    pub fn build<E>(&mut self, executor: &E) -> Result<(), BuildError> where E: Executor {
         let start_time = Instant::now();
 
+        let paint_data = match self.scene.build_paint_data(&self.built_options.transform_2d()) {
+            Ok(paint_data) => paint_data,
+            Err(error) => {
+                self.finish(start_time);
+                return Err(error);
+            }
+        };
+
         let bounding_quad = self.built_options.bounding_quad();
         let path_count = self.scene.paths.len();
-        self.listener.send(RenderCommand::Start { bounding_quad, path_count });
-
-        self.listener.send(RenderCommand::AddPaintData(self.scene.build_paint_data()));
+        let tile_size = self.built_options.tile_size;
+        self.listener.send(RenderCommand::Start { bounding_quad, path_count, tile_size });
+        self.listener.send(RenderCommand::AddPaintData(paint_data));
 
         let effective_view_box = self.scene.effective_view_box(self.built_options);
+        let clip_path_count = self.scene.clip_paths.len();
+        let built_clip_paths = executor.flatten_into_vector(clip_path_count, |clip_path_index| {
+            vec![self.build_clip_path(clip_path_index, effective_view_box)]
+        });
+        self.built_clip_paths = built_clip_paths;
+
         let alpha_tiles = executor.flatten_into_vector(path_count, |path_index| {
             self.build_path(path_index, effective_view_box, &self.built_options, &self.scene)
         });
 
-        self.finish_building(alpha_tiles);
+        // Paths whose alpha tiles didn't fit sent no fills, so draw none of the tiles.
+        let result = if self.next_alpha_tile_index.load(Ordering::Relaxed) > MAX_ALPHA_TILES {
+            Err(BuildError::TooManyAlphaTiles)
+        } else {
+            self.finish_building(alpha_tiles);
+            Ok(())
+        };
+
+        self.finish(start_time);
+        result
+    }
 
+    fn finish(&self, start_time: Instant) {
         let build_time = Instant::now() - start_time;
         self.listener.send(RenderCommand::Finish { build_time });
     }
 
+    // Tiles a clip path. Its fills are rendered into the mask framebuffer like those of any
+    // other path, but its tiles are only kept for the paths it clips to refer to.
+    fn build_clip_path(&self, clip_path_index: usize, view_box: RectF32) -> BuiltObject {
+        let clip_path = &self.scene.clip_paths[clip_path_index];
+        let mut built_clip_path = self.tile_outline(clip_path.outline(),
+                                                    &Transform2DF32::default(),
+                                                    clip_path.fill_rule(),
+                                                    view_box,
+                                                    None);
+        if self.allocate_alpha_tiles(&mut built_clip_path) {
+            let fills = mem::replace(&mut built_clip_path.fills, vec![]);
+            self.listener.send(RenderCommand::AddFills(fills));
+        }
+        built_clip_path
+    }
+
     fn build_path(
         &self,
         path_index: usize,
@@ -80,30 +225,126 @@ impl<'a> SceneBuilder<'a> {
         scene: &Scene,
     ) -> Vec<AlphaTileBatchPrimitive> {
         let path_object = &scene.paths[path_index];
-        let outline = scene.apply_render_options(path_object.outline(), built_options);
+        let clip_path = path_object.clip_path().map(|clip_path_id| {
+            &self.built_clip_paths[clip_path_id.0 as usize]
+        });
+        // Cut the path off at the tiles of the clip path, not at its exact bounds, so that its
+        // edges aren't antialiased twice.
+        let clip_rect = clip_path.map(|clip_path| {
+            clip_path.tile_rect().to_f32().scale_xy(clip_path.tile_size.to_f32())
+        });
+        let mut built_object = self.tile_path(path_index, view_box, built_options, clip_rect);
+        if !self.allocate_alpha_tiles(&mut built_object) {
+            return vec![];
+        }
+
         let paint_id = path_object.paint();
         let object_is_opaque = scene.paints[paint_id.0 as usize].is_opaque();
+        built_object.pack_and_cull(self,
+                                   path_index as u32,
+                                   paint_id,
+                                   object_is_opaque,
+                                   clip_path);
+
+        self.listener.send(RenderCommand::AddFills(built_object.fills));
+        built_object.alpha_tiles
+    }
+
+    // Returns the fills and tiles of a path, with its alpha tiles numbered from zero, taking them
+    // from the tile cache if possible.
+    fn tile_path(&self,
+                 path_index: usize,
+                 view_box: RectF32,
+                 built_options: &PreparedRenderOptions,
+                 clip_rect: Option<RectF32>)
+                 -> BuiltObject {
+        let path_object = &self.scene.paths[path_index];
+        let (outline, fill_rule) = (path_object.shared_outline(), path_object.fill_rule());
+        let transform = path_object.transform();
+        let tile_cache = match self.tile_cache {
+            None => return self.tile_outline(outline, &transform, fill_rule, view_box, clip_rect),
+            Some(tile_cache) => tile_cache,
+        };
+        let params = match self.scene.tiling_params(outline, &transform, built_options, clip_rect) {
+            None => return self.tile_outline(outline, &transform, fill_rule, view_box, clip_rect),
+            Some(params) => params,
+        };
 
-        let mut tiler = Tiler::new(self,
-                                   &outline,
+        let path_id = self.scene.path_id(path_index);
+        if let Some(cached_tiles) = tile_cache.get(path_id) {
+            if let Some(tile_offset) = cached_tiles.tile_offset(outline, &params) {
+                self.new_tile_cache_entries.lock().unwrap().push((path_id, cached_tiles.clone()));
+                self.reused_path_count.fetch_add(1, Ordering::Relaxed);
+                let mut built_object = cached_tiles.to_built_object(tile_offset);
+                built_object.fill_rule = fill_rule;
+                return built_object;
+            }
+        }
+
+        let built_object = self.tile_outline(outline, &transform, fill_rule, view_box, clip_rect);
+        let cached_tiles = CachedTiles {
+            outline: outline.clone(),
+            params,
+            built_object: built_object.clone(),
+        };
+        self.new_tile_cache_entries.lock().unwrap().push((path_id, Arc::new(cached_tiles)));
+        built_object
+    }
+
+    fn tile_outline(&self,
+                    outline: &Outline,
+                    transform: &Transform2DF32,
+                    fill_rule: FillRule,
+                    view_box: RectF32,
+                    clip_rect: Option<RectF32>)
+                    -> BuiltObject {
+        let outline =
+            self.scene.apply_render_options(outline, transform, self.built_options, clip_rect);
+        let mut tiler = Tiler::new(&outline,
                                    view_box,
-                                   path_index as u16,
-                                   paint_id,
-                                   object_is_opaque);
+                                   fill_rule,
+                                   self.built_options.tile_size,
+                                   self.built_options.flattening_tolerance);
+        tiler.generate_fills();
+        tiler.built_object
+    }
+
+    // Gives the alpha tiles of an object their places in the mask framebuffer. Returns false if
+    // they don't fit, which fails the build.
+    fn allocate_alpha_tiles(&self, built_object: &mut BuiltObject) -> bool {
+        let alpha_tile_count = built_object.alpha_tile_count as usize;
+        let first_alpha_tile_index =
+            self.next_alpha_tile_index.fetch_add(alpha_tile_count, Ordering::Relaxed);
+        if first_alpha_tile_index + alpha_tile_count > MAX_ALPHA_TILES {
+            return false;
+        }
+        built_object.offset_alpha_tile_indices(first_alpha_tile_index as u16);
+        true
+    }
 
-        tiler.generate_tiles();
+    #[inline]
+    pub(crate) fn z_buffer_for_path(&self, path_index: u32) -> &ZBuffer {
+        &self.z_buffers[self.path_layers[path_index as usize] as usize]
+    }
 
-        self.listener.send(RenderCommand::AddFills(tiler.built_object.fills));
-        tiler.built_object.alpha_tiles
+    // Tests the tile against the Z-buffers of the given layer and all the layers enclosing it. An
+    // opaque path that comes later in any of them covers everything in the layer.
+    fn tile_is_visible(&self, mut layer: Option<usize>, coords: Point2DI32, object_index: u32)
+                       -> bool {
+        while let Some(layer_index) = layer {
+            if !self.z_buffers[layer_index].test(coords, object_index) {
+                return false;
+            }
+            layer = self.layers[layer_index].parent;
+        }
+        true
     }
 
     fn cull_alpha_tiles(&self, alpha_tiles: &mut Vec<AlphaTileBatchPrimitive>) {
         for alpha_tile in alpha_tiles {
             let alpha_tile_coords = alpha_tile.tile_coords();
-            if self
-                .z_buffer
-                .test(alpha_tile_coords, alpha_tile.object_index as u32)
-            {
+            let layer = self.path_layers[alpha_tile.object_index as usize] as usize;
+            if self.tile_is_visible(Some(layer), alpha_tile_coords, alpha_tile.object_index) {
                 continue;
             }
 
@@ -114,23 +355,82 @@ impl<'a> SceneBuilder<'a> {
         }
     }
 
-    fn pack_alpha_tiles(&mut self, alpha_tiles: Vec<AlphaTileBatchPrimitive>) {
-        let path_count = self.scene.paths.len() as u32;
-        let solid_tiles = self.z_buffer.build_solid_tiles(&self.scene.paths, 0..path_count);
+    // Sends the tiles of a layer. Its child layers break it up into batches, with each child
+    // rendered on its own in between.
+    fn pack_layer<I>(&self, layer_index: usize, alpha_tiles: &mut Peekable<I>)
+                     where I: Iterator<Item = AlphaTileBatchPrimitive> {
+        let layer = &self.layers[layer_index];
+        let mut batch_start = layer.path_range.start;
+        for &child_index in &layer.children {
+            let child = &self.layers[child_index];
+            if child.path_range.is_empty() {
+                continue;
+            }
+
+            self.pack_batch(layer_index, batch_start..child.path_range.start, alpha_tiles);
+            self.listener.send(RenderCommand::PushGroup);
+            self.pack_layer(child_index, alpha_tiles);
+            self.listener.send(RenderCommand::PopGroup {
+                opacity: child.opacity,
+                blend_mode: child.blend_mode,
+            });
+            batch_start = child.path_range.end;
+        }
+        self.pack_batch(layer_index, batch_start..layer.path_range.end, alpha_tiles);
+    }
+
+    fn pack_batch<I>(&self,
+                     layer: usize,
+                     object_range: Range<u32>,
+                     alpha_tiles: &mut Peekable<I>)
+                     where I: Iterator<Item = AlphaTileBatchPrimitive> {
+        let mut solid_tiles = self.z_buffers[layer].build_solid_tiles(&self.scene.paths,
+                                                                      object_range.clone());
+        let parent_layer = self.layers[layer].parent;
+        solid_tiles.retain(|solid_tile| {
+            let tile_coords = Point2DI32::new(solid_tile.tile_x as i32, solid_tile.tile_y as i32);
+            self.tile_is_visible(parent_layer, tile_coords, solid_tile.object_index)
+        });
+
+        // Alpha tiles are in path order, so this batch's are next.
+        let mut batch_alpha_tiles = vec![];
+        while let Some(alpha_tile) = alpha_tiles.peek() {
+            if alpha_tile.object_index >= object_range.end {
+                break;
+            }
+            batch_alpha_tiles.extend(alpha_tiles.next());
+        }
+
+        if solid_tiles.is_empty() && batch_alpha_tiles.is_empty() {
+            return;
+        }
         if !solid_tiles.is_empty() {
             self.listener.send(RenderCommand::AddSolidTiles(solid_tiles));
         }
-        if !alpha_tiles.is_empty() {
-            self.listener.send(RenderCommand::AddAlphaTiles(alpha_tiles));
+        if !batch_alpha_tiles.is_empty() {
+            self.listener.send(RenderCommand::AddAlphaTiles(batch_alpha_tiles));
         }
+        self.listener.send(RenderCommand::FlushSolidTiles);
+        self.listener.send(RenderCommand::FlushAlphaTiles);
     }
 
     fn finish_building(&mut self, mut alpha_tiles: Vec<AlphaTileBatchPrimitive>) {
         self.listener.send(RenderCommand::FlushFills);
         self.cull_alpha_tiles(&mut alpha_tiles);
-        self.pack_alpha_tiles(alpha_tiles);
-        self.listener.send(RenderCommand::FlushSolidTiles);
-        self.listener.send(RenderCommand::FlushAlphaTiles);
+
+        // Blending reads back what's beneath, which the destination framebuffer may not allow,
+        // so in that case the whole scene goes through a group too.
+        let needs_backdrop = self.layers.iter().any(|layer| layer.blend_mode.needs_backdrop());
+        if needs_backdrop {
+            self.listener.send(RenderCommand::PushGroup);
+        }
+        self.pack_layer(0, &mut alpha_tiles.into_iter().peekable());
+        if needs_backdrop {
+            self.listener.send(RenderCommand::PopGroup {
+                opacity: 1.0,
+                blend_mode: BlendMode::SrcOver,
+            });
+        }
     }
 }
 
@@ -143,14 +443,31 @@ pub struct TileStats {
 // Utilities for built objects
 
 impl BuiltObject {
-    pub(crate) fn new(bounds: RectF32) -> BuiltObject {
-        let tile_rect = tiles::round_rect_out_to_tile_bounds(bounds);
+    pub(crate) fn new(bounds: RectF32, fill_rule: FillRule, tile_size: Point2DI32)
+                      -> BuiltObject {
+        let tile_rect = tiles::round_rect_out_to_tile_bounds(bounds, tile_size);
         let tiles = DenseTileMap::new(tile_rect);
         BuiltObject {
             bounds,
+            fill_rule,
             fills: vec![],
             alpha_tiles: vec![],
             tiles,
+            alpha_tile_count: 0,
+            tile_size,
+        }
+    }
+
+    // Moves the alpha tiles of this object, which are numbered from zero while it's tiled, to the
+    // part of the mask framebuffer starting at `first_alpha_tile_index`.
+    pub(crate) fn offset_alpha_tile_indices(&mut self, first_alpha_tile_index: u16) {
+        for fill in &mut self.fills {
+            fill.alpha_tile_index += first_alpha_tile_index;
+        }
+        for tile in &mut self.tiles.data {
+            if tile.alpha_tile_index != !0 {
+                tile.alpha_tile_index += first_alpha_tile_index;
+            }
         }
     }
 
@@ -159,12 +476,7 @@ impl BuiltObject {
         self.tiles.rect
     }
 
-    fn add_fill(
-        &mut self,
-        builder: &SceneBuilder,
-        segment: &LineSegmentF32,
-        tile_coords: Point2DI32,
-    ) {
+    fn add_fill(&mut self, segment: &LineSegmentF32, tile_coords: Point2DI32) {
         debug!("add_fill({:?} ({:?}))", segment, tile_coords);
 
         // Ensure this fill is in bounds. If not, cull it.
@@ -172,13 +484,12 @@ impl BuiltObject {
             return;
         };
 
-        debug_assert_eq!(TILE_WIDTH, TILE_HEIGHT);
-        let tile_size = F32x4::splat(TILE_WIDTH as f32);
+        let tile_size = self.tile_size.to_f32().0.xyxy();
         let (min, max) = (
             F32x4::default(),
-            F32x4::splat((TILE_WIDTH * 256 - 1) as f32),
+            tile_size * F32x4::splat(256.0) - F32x4::splat(1.0),
         );
-        let shuffle_mask = I32x4::new(0x0c08_0400, 0x0d05_0901, 0, 0).as_u8x16();
+        let shuffle_mask = I32x4::new(0x0c08_0400, 0x0d09_0501, 0, 0).as_u8x16();
 
         let tile_upper_left = tile_coords.to_f32().0.xyxy() * tile_size;
 
@@ -191,11 +502,11 @@ impl BuiltObject {
             .as_i32x4();
 
         // Unpack whole and fractional pixels.
-        let px = LineSegmentU4((segment[1] | (segment[1] >> 12)) as u16);
+        let px = LineSegmentU8(segment[1] as u32);
         let subpx = LineSegmentU8(segment[0] as u32);
 
         // Cull degenerate fills.
-        if (px.0 & 0xf) as u8 == ((px.0 >> 8) & 0xf) as u8
+        if (px.0 & 0xff) as u8 == ((px.0 >> 16) & 0xff) as u8
             && (subpx.0 & 0xff) as u8 == ((subpx.0 >> 16) & 0xff) as u8
         {
             debug!("... culling!");
@@ -203,7 +514,7 @@ impl BuiltObject {
         }
 
         // Allocate global tile if necessary.
-        let alpha_tile_index = self.get_or_allocate_alpha_tile_index(builder, tile_coords);
+        let alpha_tile_index = self.get_or_allocate_alpha_tile_index(tile_coords);
 
         debug!("... OK, pushing");
         self.fills.push(FillBatchPrimitive {
@@ -213,33 +524,29 @@ impl BuiltObject {
         });
     }
 
-    fn get_or_allocate_alpha_tile_index(
-        &mut self,
-        builder: &SceneBuilder,
-        tile_coords: Point2DI32,
-    ) -> u16 {
+    fn get_or_allocate_alpha_tile_index(&mut self, tile_coords: Point2DI32) -> u16 {
         let local_tile_index = self.tiles.coords_to_index_unchecked(tile_coords);
         let alpha_tile_index = self.tiles.data[local_tile_index].alpha_tile_index;
         if alpha_tile_index != !0 {
             return alpha_tile_index;
         }
 
-        let alpha_tile_index = builder
-            .next_alpha_tile_index
-            .fetch_add(1, Ordering::Relaxed) as u16;
+        // An object with too many alpha tiles for the mask framebuffer wraps around here, but
+        // it's then rejected by `SceneBuilder::allocate_alpha_tiles()`.
+        let alpha_tile_index = self.alpha_tile_count as u16;
+        self.alpha_tile_count += 1;
         self.tiles.data[local_tile_index].alpha_tile_index = alpha_tile_index;
         alpha_tile_index
     }
 
     pub(crate) fn add_active_fill(
         &mut self,
-        builder: &SceneBuilder,
         left: f32,
         right: f32,
         mut winding: i32,
         tile_coords: Point2DI32,
     ) {
-        let tile_origin_y = (tile_coords.y() * TILE_HEIGHT as i32) as f32;
+        let tile_origin_y = (tile_coords.y() * self.tile_size.y()) as f32;
         let left = Point2DF32::new(left, tile_origin_y);
         let right = Point2DF32::new(right, tile_origin_y);
 
@@ -258,7 +565,7 @@ impl BuiltObject {
         );
 
         while winding != 0 {
-            self.add_fill(builder, &segment, tile_coords);
+            self.add_fill(&segment, tile_coords);
             if winding < 0 {
                 winding += 1
             } else {
@@ -269,7 +576,6 @@ impl BuiltObject {
 
     pub(crate) fn generate_fill_primitives_for_line(
         &mut self,
-        builder: &SceneBuilder,
         mut segment: LineSegmentF32,
         tile_y: i32,
     ) {
@@ -277,8 +583,8 @@ impl BuiltObject {
             "... generate_fill_primitives_for_line(): segment={:?} tile_y={} ({}-{})",
             segment,
             tile_y,
-            tile_y as f32 * TILE_HEIGHT as f32,
-            (tile_y + 1) as f32 * TILE_HEIGHT as f32
+            tile_y as f32 * self.tile_size.y() as f32,
+            (tile_y + 1) as f32 * self.tile_size.y() as f32
         );
 
         let winding = segment.from_x() > segment.to_x();
@@ -289,9 +595,9 @@ impl BuiltObject {
         };
 
         // FIXME(pcwalton): Optimize this.
-        let segment_tile_left = f32::floor(segment_left) as i32 / TILE_WIDTH as i32;
-        let segment_tile_right =
-            util::alignup_i32(f32::ceil(segment_right) as i32, TILE_WIDTH as i32);
+        let tile_width = self.tile_size.x();
+        let segment_tile_left = f32::floor(segment_left) as i32 / tile_width;
+        let segment_tile_right = util::alignup_i32(f32::ceil(segment_right) as i32, tile_width);
         debug!(
             "segment_tile_left={} segment_tile_right={} tile_rect={:?}",
             segment_tile_left,
@@ -301,8 +607,7 @@ impl BuiltObject {
 
         for subsegment_tile_x in segment_tile_left..segment_tile_right {
             let (mut fill_from, mut fill_to) = (segment.from(), segment.to());
-            let subsegment_tile_right =
-                ((i32::from(subsegment_tile_x) + 1) * TILE_HEIGHT as i32) as f32;
+            let subsegment_tile_right = ((i32::from(subsegment_tile_x) + 1) * tile_width) as f32;
             if subsegment_tile_right < segment_right {
                 let x = subsegment_tile_right;
                 let point = Point2DF32::new(x, segment.solve_y_for_x(x));
@@ -317,7 +622,7 @@ impl BuiltObject {
 
             let fill_segment = LineSegmentF32::new(fill_from, fill_to);
             let fill_tile_coords = Point2DI32::new(subsegment_tile_x, tile_y);
-            self.add_fill(builder, &fill_segment, fill_tile_coords);
+            self.add_fill(&fill_segment, fill_tile_coords);
         }
     }
 
@@ -331,3 +636,56 @@ impl BuiltObject {
         self.tiles.index_to_coords(tile_index as usize)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::concurrent::executor::SequentialExecutor;
+    use crate::gpu_data::RenderCommand;
+    use crate::options::RenderOptions;
+    use crate::paint::Paint;
+    use crate::scene::{ClipPath, PathObject, Scene};
+    use crate::test_util::rect_outline;
+    use pathfinder_geometry::basic::point::Point2DF32;
+    use pathfinder_geometry::basic::rect::RectF32;
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::fill::FillRule;
+    use pathfinder_geometry::outline::Outline;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_high_clip_tile_index() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::splat(4096.0)));
+        let paint = scene.push_paint(&Paint::Color(ColorU { r: 255, g: 0, b: 0, a: 255 }));
+
+        // Thin stripes across the view box, each needing a mask in every tile of its row, use up
+        // the first 0x8000 alpha tiles.
+        let stripes: String = (0..130).map(|row| {
+            let y = row as f32 * 16.0 + 4.0;
+            format!("M 0.5 {} H 4095.5 V {} H 0.5 Z ", y, y + 0.5)
+        }).collect();
+        let stripes = Outline::from_svg_path_data(&stripes).unwrap();
+        scene.push_clip_path(ClipPath::new(stripes, FillRule::Winding));
+        let clip_rect = RectF32::new(Point2DF32::splat(8.5), Point2DF32::splat(20.0));
+        let clip_path = scene.push_clip_path(ClipPath::new(rect_outline(clip_rect),
+                                                           FillRule::Winding));
+        let outline = rect_outline(RectF32::new(Point2DF32::default(), Point2DF32::splat(64.0)));
+        let mut path = PathObject::new(outline, paint, String::new());
+        path.set_clip_path(Some(clip_path));
+        scene.push_path(path);
+
+        let commands = Arc::new(Mutex::new(vec![]));
+        let listener_commands = commands.clone();
+        let listener = Box::new(move |command| listener_commands.lock().unwrap().push(command));
+        scene.build(RenderOptions::default(), listener, &SequentialExecutor).unwrap();
+
+        let mut clip_tile_indices = vec![];
+        for command in commands.lock().unwrap().iter() {
+            if let RenderCommand::AddAlphaTiles(ref alpha_tiles) = *command {
+                clip_tile_indices.extend(alpha_tiles.iter().map(|tile| tile.clip_tile_index));
+            }
+        }
+        assert!(!clip_tile_indices.is_empty());
+        assert!(clip_tile_indices.iter().any(|&index| index > 0x7fff && index != !0));
+    }
+}
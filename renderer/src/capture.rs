@@ -0,0 +1,199 @@
+// pathfinder/renderer/src/capture.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Recording the render commands that a scene builds to, and playing them back later.
+//!
+//! A capture lets a rendering problem be reproduced on another machine without the scene or the
+//! application that built it.
+
+use crate::gpu::renderer::Renderer;
+use crate::gpu_data::RenderCommand;
+use crate::options::RenderCommandListener;
+use crate::scene;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use pathfinder_gpu::Device;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::Mutex;
+
+const CAPTURE_FILE_MAGIC: &[u8; 4] = b"PFRC";
+const CAPTURE_FILE_VERSION: u32 = 1;
+
+/// A listener that writes every command it receives to a capture before passing it on.
+///
+/// Commands from several calls to `Scene::build()` can be recorded one after another; each ends
+/// with a `Finish` command.
+pub struct RenderCommandRecorder<W> where W: Write + Send {
+    writer: Mutex<W>,
+    listener: Box<dyn RenderCommandListener>,
+}
+
+impl<W> RenderCommandRecorder<W> where W: Write + Send {
+    /// Writes the capture header to `writer` and returns a recorder that forwards commands to
+    /// `listener`.
+    pub fn new(mut writer: W, listener: Box<dyn RenderCommandListener>)
+               -> io::Result<RenderCommandRecorder<W>> {
+        writer.write_all(CAPTURE_FILE_MAGIC)?;
+        writer.write_u32::<LittleEndian>(CAPTURE_FILE_VERSION)?;
+        Ok(RenderCommandRecorder { writer: Mutex::new(writer), listener })
+    }
+
+    fn record(&self, command: &RenderCommand) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        bincode::serialize_into(&mut *writer, command).map_err(|error| {
+            scene::bincode_error_to_io_error(*error)
+        })?;
+        if let RenderCommand::Finish { .. } = *command {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W> RenderCommandListener for RenderCommandRecorder<W> where W: Write + Send {
+    fn send(&self, command: RenderCommand) {
+        // Failing to record shouldn't stop the frame from rendering.
+        if let Err(error) = self.record(&command) {
+            error!("failed to record render command: {}", error);
+        }
+        self.listener.send(command);
+    }
+}
+
+/// Reads the commands of a capture written by `RenderCommandRecorder` back, one at a time.
+pub struct RenderCommandPlayer<R> where R: Read {
+    reader: BufReader<R>,
+}
+
+impl<R> RenderCommandPlayer<R> where R: Read {
+    /// Checks the capture header and returns a player positioned at the first command.
+    pub fn new(reader: R) -> io::Result<RenderCommandPlayer<R>> {
+        let mut reader = BufReader::new(reader);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != *CAPTURE_FILE_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      "not a Pathfinder render command capture"));
+        }
+        let version = reader.read_u32::<LittleEndian>()?;
+        if version != CAPTURE_FILE_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("unsupported capture version {}", version)));
+        }
+        Ok(RenderCommandPlayer { reader })
+    }
+
+    /// Returns the next command, or `None` at the end of the capture.
+    pub fn next_command(&mut self) -> io::Result<Option<RenderCommand>> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        bincode::deserialize_from(&mut self.reader).map(Some).map_err(|error| {
+            scene::bincode_error_to_io_error(*error)
+        })
+    }
+
+    /// Sends the commands of the next recorded frame, up to and including its `Finish` command,
+    /// to `renderer`, between calls to `begin_scene()` and `end_scene()`.
+    ///
+    /// Returns false if the capture has no more frames. Fails with `InvalidInput` if the frame
+    /// doesn't fit on the renderer's device.
+    pub fn replay_frame<D>(&mut self, renderer: &mut Renderer<D>) -> io::Result<bool>
+                           where D: Device {
+        let mut command = match self.next_command()? {
+            None => return Ok(false),
+            Some(command) => command,
+        };
+
+        renderer.begin_scene();
+        loop {
+            renderer.render_command(&command);
+            if let RenderCommand::Finish { .. } = command {
+                break;
+            }
+            command = match self.next_command()? {
+                None => break,
+                Some(command) => command,
+            };
+        }
+        renderer.end_scene().map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::concurrent::executor::SequentialExecutor;
+    use crate::cpu::renderer::Renderer;
+    use crate::gpu_data::RenderCommand;
+    use crate::options::RenderOptions;
+    use crate::paint::Paint;
+    use crate::scene::{PathObject, Scene};
+    use pathfinder_geometry::basic::point::{Point2DF32, Point2DI32};
+    use pathfinder_geometry::basic::rect::RectF32;
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::outline::Outline;
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use super::{RenderCommandPlayer, RenderCommandRecorder};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::splat(32.0)));
+        let paint = scene.push_paint(&Paint::Color(ColorU { r: 0, g: 128, b: 255, a: 255 }));
+        let outline = Outline::from_svg_path_data("M 2 2 L 30 4 Q 24 30 4 28 Z").unwrap();
+        scene.push_path(PathObject::new(outline, paint, String::new()));
+
+        let capture = SharedBuffer::default();
+        let commands = Arc::new(Mutex::new(vec![]));
+        let listener_commands = commands.clone();
+        let listener = Box::new(move |command| listener_commands.lock().unwrap().push(command));
+        let recorder = RenderCommandRecorder::new(capture.clone(), listener).unwrap();
+        scene.build(RenderOptions::default(), Box::new(recorder), &SequentialExecutor).unwrap();
+
+        let capture = capture.0.lock().unwrap().clone();
+        let mut player = RenderCommandPlayer::new(&capture[..]).unwrap();
+        let size = Point2DI32::splat(32);
+        let (mut renderer, mut replayed_commands) = (Renderer::new(size), vec![]);
+        renderer.begin_scene();
+        while let Some(command) = player.next_command().unwrap() {
+            renderer.render_command(&command);
+            replayed_commands.push(format!("{:?}", command));
+        }
+
+        let commands = commands.lock().unwrap();
+        let expected_commands: Vec<_> = commands.iter().map(|command| format!("{:?}", command))
+                                                       .collect();
+        assert_eq!(replayed_commands, expected_commands);
+        match commands.last() {
+            Some(RenderCommand::Finish { .. }) => {}
+            command => panic!("expected the capture to end with `Finish`, got {:?}", command),
+        }
+
+        let pixels =
+            scene.render_to_pixels(RenderOptions::default(), size, &SequentialExecutor).unwrap();
+        assert_eq!(renderer.read_pixels(), pixels);
+
+        assert!(RenderCommandPlayer::new(&b"PFSC\x01\x00\x00\x00"[..]).is_err());
+    }
+}
@@ -20,11 +20,14 @@
 //! You don't need to use this API to use Pathfinder; it's only a convenience.
 
 use crate::concurrent::executor::Executor;
-use crate::gpu::renderer::Renderer;
+use crate::gpu::renderer::{RenderError, Renderer};
 use crate::gpu_data::RenderCommand;
 use crate::options::{RenderCommandListener, RenderOptions};
-use crate::scene::Scene;
+use crate::paint::{Paint, PaintId};
+use crate::scene::{PathId, PathObject, Scene};
+use crate::tile_cache::TileCache;
 use pathfinder_geometry::basic::rect::RectF32;
+use pathfinder_geometry::basic::transform2d::Transform2DF32;
 use pathfinder_gpu::Device;
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::thread;
@@ -57,6 +60,63 @@ impl SceneProxy {
         self.sender.send(MainToWorkerMsg::SetViewBox(new_view_box)).unwrap();
     }
 
+    /// Adds a path on top of the scene and returns its ID. This waits for the scene thread to
+    /// finish whatever it's doing, such as building.
+    #[inline]
+    pub fn push_path(&self, path: PathObject) -> PathId {
+        let (sender, receiver) = mpsc::channel();
+        self.sender.send(MainToWorkerMsg::PushPath(path, sender)).unwrap();
+        receiver.recv().unwrap()
+    }
+
+    /// Like `Scene::insert_path_at()`. This waits for the scene thread, as `push_path()` does.
+    #[inline]
+    pub fn insert_path_at(&self, index: usize, path: PathObject) -> PathId {
+        let (sender, receiver) = mpsc::channel();
+        self.sender.send(MainToWorkerMsg::InsertPathAt(index, path, sender)).unwrap();
+        receiver.recv().unwrap()
+    }
+
+    #[inline]
+    pub fn remove_path(&self, path_id: PathId) {
+        self.sender.send(MainToWorkerMsg::RemovePath(path_id)).unwrap();
+    }
+
+    #[inline]
+    pub fn replace_path(&self, path_id: PathId, new_path: PathObject) {
+        self.sender.send(MainToWorkerMsg::ReplacePath(path_id, new_path)).unwrap();
+    }
+
+    #[inline]
+    pub fn move_path(&self, path_id: PathId, new_index: usize) {
+        self.sender.send(MainToWorkerMsg::MovePath(path_id, new_index)).unwrap();
+    }
+
+    /// Adds a paint, or finds an identical one, and returns its ID. This waits for the scene
+    /// thread, as `push_path()` does.
+    #[inline]
+    pub fn push_paint(&self, paint: Paint) -> PaintId {
+        let (sender, receiver) = mpsc::channel();
+        self.sender.send(MainToWorkerMsg::PushPaint(paint, sender)).unwrap();
+        receiver.recv().unwrap()
+    }
+
+    /// Like `Scene::set_path_paint()`, except that a request naming an unknown path or paint is
+    /// silently ignored, since this doesn't wait for the scene thread.
+    #[inline]
+    pub fn set_path_paint(&self, path_id: PathId, new_paint: PaintId) {
+        self.sender.send(MainToWorkerMsg::SetPathPaint(path_id, new_paint)).unwrap();
+    }
+
+    /// Like `Scene::set_path_transform()`. This sends only the transform to the scene thread,
+    /// so it's a cheap way to move a path around.
+    #[inline]
+    pub fn set_path_transform(&self, path_id: PathId, new_transform: Transform2DF32) {
+        self.sender.send(MainToWorkerMsg::SetPathTransform(path_id, new_transform)).unwrap();
+    }
+
+    /// Builds the scene on the scene thread, like `Scene::build()`. Build errors are logged, and
+    /// the listener gets no tiles.
     #[inline]
     pub fn build_with_listener(&self,
                                options: RenderOptions,
@@ -77,17 +137,20 @@ impl SceneProxy {
     ///
     /// Exactly equivalent to:
     ///
+    ///     renderer.begin_scene();
     ///     for command in scene_proxy.build_with_stream(options) {
     ///         renderer.render_command(&command)
     ///     }
+    ///     renderer.end_scene()
     #[inline]
     pub fn build_and_render<D>(&self, renderer: &mut Renderer<D>, options: RenderOptions)
+                               -> Result<(), RenderError>
                                where D: Device {
         renderer.begin_scene();
         for command in self.build_with_stream(options) {
             renderer.render_command(&command)
         }
-        renderer.end_scene();
+        renderer.end_scene()
     }
 
     pub fn as_svg(&self) -> Vec<u8> {
@@ -101,11 +164,41 @@ fn scene_thread<E>(mut scene: Scene,
                    executor: E,
                    main_to_worker_receiver: Receiver<MainToWorkerMsg>)
                    where E: Executor {
+    // Scenes built through a proxy tend to be redrawn with few changes, so keep their tiles.
+    let mut tile_cache = TileCache::new();
     while let Ok(msg) = main_to_worker_receiver.recv() {
         match msg {
             MainToWorkerMsg::ReplaceScene(new_scene) => scene = new_scene,
             MainToWorkerMsg::SetViewBox(new_view_box) => scene.set_view_box(new_view_box),
-            MainToWorkerMsg::Build(options, listener) => scene.build(options, listener, &executor),
+            MainToWorkerMsg::PushPath(path, sender) => sender.send(scene.push_path(path)).unwrap(),
+            MainToWorkerMsg::InsertPathAt(index, path, sender) => {
+                sender.send(scene.insert_path_at(index, path)).unwrap()
+            }
+            MainToWorkerMsg::RemovePath(path_id) => {
+                scene.remove_path(path_id);
+            }
+            MainToWorkerMsg::ReplacePath(path_id, new_path) => {
+                scene.replace_path(path_id, new_path);
+            }
+            MainToWorkerMsg::MovePath(path_id, new_index) => {
+                scene.move_path(path_id, new_index);
+            }
+            MainToWorkerMsg::PushPaint(paint, sender) => {
+                sender.send(scene.push_paint(&paint)).unwrap()
+            }
+            MainToWorkerMsg::SetPathPaint(path_id, new_paint) => {
+                scene.set_path_paint(path_id, new_paint);
+            }
+            MainToWorkerMsg::SetPathTransform(path_id, new_transform) => {
+                scene.set_path_transform(path_id, new_transform);
+            }
+            MainToWorkerMsg::Build(options, listener) => {
+                let result =
+                    scene.build_with_tile_cache(options, listener, &executor, &mut tile_cache);
+                if let Err(error) = result {
+                    error!("failed to build scene: {}", error);
+                }
+            }
             MainToWorkerMsg::GetSVG(sender) => {
                 let mut bytes = vec![];
                 scene.write_svg(&mut bytes).unwrap();
@@ -118,6 +211,14 @@ fn scene_thread<E>(mut scene: Scene,
 enum MainToWorkerMsg {
     ReplaceScene(Scene),
     SetViewBox(RectF32),
+    PushPath(PathObject, Sender<PathId>),
+    InsertPathAt(usize, PathObject, Sender<PathId>),
+    RemovePath(PathId),
+    ReplacePath(PathId, PathObject),
+    MovePath(PathId, usize),
+    PushPaint(Paint, Sender<PaintId>),
+    SetPathPaint(PathId, PaintId),
+    SetPathTransform(PathId, Transform2DF32),
     Build(RenderOptions, Box<dyn RenderCommandListener>),
     GetSVG(Sender<Vec<u8>>),
 }
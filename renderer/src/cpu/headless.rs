@@ -0,0 +1,115 @@
+// pathfinder/renderer/src/cpu/headless.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Renders scenes to pixels without a window or GPU context.
+
+use crate::concurrent::executor::Executor;
+use crate::cpu::renderer::Renderer;
+use crate::options::{RenderOptions, RenderTransform};
+use crate::scene::{BuildError, Scene};
+use image::ColorType;
+use image::png::PNGEncoder;
+use pathfinder_geometry::basic::point::{Point2DF32, Point2DI32};
+use pathfinder_geometry::basic::rect::RectF32;
+use pathfinder_geometry::basic::transform2d::Transform2DF32;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+impl Scene {
+    /// Renders this scene into a `size`-sized image and returns the pixels as RGBA8, top row
+    /// first.
+    ///
+    /// Paths are drawn with `options.transform` into pixel space and clipped to the view box,
+    /// which is in pixels too. The result is then scaled, keeping its aspect ratio, so that the
+    /// view box fits the image; any space left over on the right or at the bottom is
+    /// transparent. With subpixel AA, the view box counts as three times as wide. Perspective
+    /// transforms aren't scaled, so the view box should already match `size` with them.
+    pub fn render_to_pixels<E>(&self, options: RenderOptions, size: Point2DI32, executor: &E)
+                               -> Result<Vec<u8>, BuildError>
+                               where E: Executor {
+        Ok(self.render_on_cpu(options, size, executor)?.read_pixels())
+    }
+
+    /// Renders this scene into a `size`-sized image and writes it out as a PNG. The scene is
+    /// fitted to `size` as it is by `render_to_pixels()`.
+    pub fn write_png<W, E>(&self,
+                           writer: W,
+                           options: RenderOptions,
+                           size: Point2DI32,
+                           executor: &E)
+                           -> io::Result<()>
+                           where W: Write, E: Executor {
+        let pixels = self.render_to_pixels(options, size, executor).map_err(|error| {
+            io::Error::new(io::ErrorKind::InvalidInput, error)
+        })?;
+        PNGEncoder::new(writer).encode(&pixels,
+                                       size.x() as u32,
+                                       size.y() as u32,
+                                       ColorType::RGBA(8))
+    }
+
+    pub(crate) fn render_on_cpu<E>(&self, options: RenderOptions, size: Point2DI32, executor: &E)
+                                   -> Result<Renderer, BuildError>
+                                   where E: Executor {
+        let fitted = self.fit_to_size(&options, size);
+        let (scene, options) = match fitted {
+            None => (self, options),
+            Some((ref scene, ref options)) => (scene, options.clone()),
+        };
+
+        let commands = Arc::new(Mutex::new(vec![]));
+        let listener_commands = commands.clone();
+        scene.build(options,
+                    Box::new(move |command| listener_commands.lock().unwrap().push(command)),
+                    executor)?;
+
+        let mut renderer = Renderer::new(size);
+        renderer.begin_scene();
+        for command in commands.lock().unwrap().iter() {
+            renderer.render_command(command);
+        }
+        Ok(renderer)
+    }
+
+    // Returns a copy of this scene and the options to render it with so that the view box fills
+    // as much of a `size`-sized image as it can without being distorted, or `None` if it already
+    // does or can't be scaled.
+    fn fit_to_size(&self, options: &RenderOptions, size: Point2DI32)
+                   -> Option<(Scene, RenderOptions)> {
+        let transform = match options.transform {
+            RenderTransform::Transform2D(transform) => transform,
+            RenderTransform::Perspective(_) => return None,
+        };
+
+        let view_box = self.view_box();
+        let mut view_box_size = view_box.size();
+        if options.subpixel_aa_enabled {
+            view_box_size = view_box_size.scale_xy(Point2DF32::new(3.0, 1.0));
+        }
+        if view_box_size.x() <= 0.0 || view_box_size.y() <= 0.0 {
+            return None;
+        }
+        let size = size.to_f32();
+        let scale = f32::min(size.x() / view_box_size.x(), size.y() / view_box_size.y());
+        if scale == 1.0 {
+            return None;
+        }
+
+        let mut scene = self.clone();
+        scene.set_view_box(RectF32::new(view_box.origin().scale(scale),
+                                        view_box.size().scale(scale)));
+        let scale_transform = Transform2DF32::from_scale(Point2DF32::splat(scale));
+        let options = RenderOptions {
+            transform: RenderTransform::Transform2D(transform.post_mul(&scale_transform)),
+            ..options.clone()
+        };
+        Some((scene, options))
+    }
+}
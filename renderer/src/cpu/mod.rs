@@ -0,0 +1,14 @@
+// pathfinder/renderer/src/cpu/mod.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A software implementation of the GPU rendering stage, for testing and headless use.
+
+pub mod headless;
+pub mod renderer;
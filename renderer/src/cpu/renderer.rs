@@ -0,0 +1,819 @@
+// pathfinder/renderer/src/cpu/renderer.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A reference rasterizer that consumes the same render commands as the GPU renderer.
+//!
+//! Fills accumulate signed area into per-tile masks, which is what `fill.fs.glsl` computes with
+//! the area LUT, and alpha tiles resolve those masks against their backdrops the way
+//! `tile_alpha.fs.glsl` does. Only the multicolor render mode is supported, and the perspective
+//! bounding quad is ignored.
+
+use crate::blend::{self, BlendMode};
+use crate::gpu::renderer::RenderStats;
+use crate::gpu_data::{AlphaTileBatchPrimitive, FillBatchPrimitive, PaintData, RenderCommand};
+use crate::gpu_data::SolidTileBatchPrimitive;
+use crate::paint::{PAINT_KIND_COLOR, PAINT_KIND_LINEAR_GRADIENT, PAINT_KIND_PATTERN};
+use crate::tiles::{DEFAULT_TILE_HEIGHT, DEFAULT_TILE_WIDTH};
+use pathfinder_geometry::basic::point::{Point2DF32, Point2DI32};
+use pathfinder_geometry::basic::transform2d::Transform2DF32;
+use pathfinder_geometry::color::{ColorF, ColorU};
+use pathfinder_geometry::fill::FillRule;
+use pathfinder_geometry::util;
+use pathfinder_simd::default::F32x4;
+use std::mem;
+
+pub struct Renderer {
+    // Core data
+    framebuffer_size: Point2DI32,
+    framebuffer: Vec<ColorF>,
+    masks: Vec<f32>,
+    tile_size: Point2DI32,
+    paint_data: Option<PaintData>,
+    // The targets beneath each open group, innermost last.
+    group_framebuffers: Vec<Vec<ColorF>>,
+
+    // Rendering state
+    buffered_fills: Vec<FillBatchPrimitive>,
+    buffered_alpha_tiles: Vec<AlphaTileBatchPrimitive>,
+    buffered_solid_tiles: Vec<SolidTileBatchPrimitive>,
+
+    // Debug
+    pub stats: RenderStats,
+}
+
+impl Renderer {
+    pub fn new(framebuffer_size: Point2DI32) -> Renderer {
+        let pixel_count = framebuffer_size.x() as usize * framebuffer_size.y() as usize;
+        Renderer {
+            framebuffer_size,
+            framebuffer: vec![ColorF::transparent_black(); pixel_count],
+            masks: vec![],
+            tile_size: Point2DI32::new(DEFAULT_TILE_WIDTH as i32, DEFAULT_TILE_HEIGHT as i32),
+            paint_data: None,
+            group_framebuffers: vec![],
+
+            buffered_fills: vec![],
+            buffered_alpha_tiles: vec![],
+            buffered_solid_tiles: vec![],
+
+            stats: RenderStats::default(),
+        }
+    }
+
+    pub fn begin_scene(&mut self) {
+        self.masks.clear();
+        self.stats = RenderStats::default();
+    }
+
+    pub fn render_command(&mut self, command: &RenderCommand) {
+        match *command {
+            RenderCommand::Start { path_count, tile_size, .. } => {
+                self.tile_size = tile_size;
+                self.stats.path_count = path_count;
+            }
+            RenderCommand::AddPaintData(ref paint_data) => {
+                self.paint_data = Some((*paint_data).clone())
+            }
+            RenderCommand::AddFills(ref fills) => {
+                self.stats.fill_count += fills.len();
+                self.buffered_fills.extend_from_slice(fills);
+            }
+            RenderCommand::FlushFills => self.draw_buffered_fills(),
+            RenderCommand::AddSolidTiles(ref solid_tiles) => {
+                self.stats.solid_tile_count += solid_tiles.len();
+                self.buffered_solid_tiles.extend_from_slice(solid_tiles);
+            }
+            RenderCommand::FlushSolidTiles => self.draw_buffered_solid_tiles(),
+            RenderCommand::AddAlphaTiles(ref alpha_tiles) => {
+                self.stats.alpha_tile_count += alpha_tiles.len();
+                self.buffered_alpha_tiles.extend_from_slice(alpha_tiles);
+            }
+            RenderCommand::FlushAlphaTiles => self.draw_buffered_alpha_tiles(),
+            RenderCommand::PushGroup => {
+                let group_framebuffer = vec![ColorF::transparent_black(); self.framebuffer.len()];
+                let parent_framebuffer = mem::replace(&mut self.framebuffer, group_framebuffer);
+                self.group_framebuffers.push(parent_framebuffer);
+            }
+            RenderCommand::PopGroup { opacity, blend_mode } => {
+                let parent_framebuffer = self.group_framebuffers.pop().expect("No group to pop!");
+                let group_framebuffer = mem::replace(&mut self.framebuffer, parent_framebuffer);
+                for (dest, src) in self.framebuffer.iter_mut().zip(group_framebuffer) {
+                    composite_group(dest, src, opacity, blend_mode);
+                }
+            }
+            RenderCommand::Finish { .. } => {}
+        }
+    }
+
+    #[inline]
+    pub fn framebuffer_size(&self) -> Point2DI32 {
+        self.framebuffer_size
+    }
+
+    pub fn clear(&mut self, color: ColorF) {
+        for pixel in &mut self.framebuffer {
+            *pixel = color;
+        }
+    }
+
+    /// Returns the color of the pixel at the given position, with (0, 0) at the upper left.
+    #[inline]
+    pub fn pixel(&self, position: Point2DI32) -> ColorF {
+        self.framebuffer[self.pixel_index(position).unwrap()]
+    }
+
+    /// Returns the contents of the framebuffer as RGBA8, top row first.
+    pub fn read_pixels(&self) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(self.framebuffer.len() * 4);
+        for color in &self.framebuffer {
+            let color = color.to_u8();
+            pixels.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+        pixels
+    }
+
+    fn draw_buffered_fills(&mut self) {
+        let tile_area = self.tile_area();
+        for fill in mem::replace(&mut self.buffered_fills, vec![]) {
+            let mask_offset = fill.alpha_tile_index as usize * tile_area;
+            if self.masks.len() < mask_offset + tile_area {
+                self.masks.resize(mask_offset + tile_area, 0.0);
+            }
+            rasterize_fill(&fill,
+                           self.tile_size,
+                           &mut self.masks[mask_offset..(mask_offset + tile_area)]);
+        }
+    }
+
+    fn draw_buffered_solid_tiles(&mut self) {
+        for solid_tile in mem::replace(&mut self.buffered_solid_tiles, vec![]) {
+            let paint = Point2DI32::new(solid_tile.origin_u as i32, solid_tile.origin_v as i32);
+            let tile_coords = Point2DI32::new(solid_tile.tile_x as i32, solid_tile.tile_y as i32);
+            for (_, position) in tile_pixels(tile_coords, self.tile_size) {
+                if let Some(index) = self.pixel_index(position) {
+                    self.framebuffer[index] = self.paint_color(paint, position);
+                }
+            }
+        }
+    }
+
+    fn draw_buffered_alpha_tiles(&mut self) {
+        let tile_area = self.tile_area();
+        for alpha_tile in mem::replace(&mut self.buffered_alpha_tiles, vec![]) {
+            let paint = Point2DI32::new(alpha_tile.origin_u as i32, alpha_tile.origin_v as i32);
+            let mask_offset = alpha_tile.tile_index as usize * tile_area;
+            let fill_rule = fill_rule_from_u8(alpha_tile.fill_rule);
+            let clip_fill_rule = fill_rule_from_u8(alpha_tile.clip_fill_rule);
+            for (tile_pixel, position) in tile_pixels(alpha_tile.tile_coords(), self.tile_size) {
+                let index = match self.pixel_index(position) {
+                    None => continue,
+                    Some(index) => index,
+                };
+                let mask = self.masks.get(mask_offset + tile_pixel).cloned().unwrap_or(0.0);
+                let mut coverage = fill_rule.coverage(mask + alpha_tile.backdrop as f32);
+
+                // As in `tile_alpha_vertex.inc.glsl`, a clip tile index of `0xffff` means no mask.
+                let clip_mask = if alpha_tile.clip_tile_index == !0 {
+                    0.0
+                } else {
+                    let clip_mask_offset = alpha_tile.clip_tile_index as usize * tile_area;
+                    self.masks.get(clip_mask_offset + tile_pixel).cloned().unwrap_or(0.0)
+                };
+                coverage *= clip_fill_rule.coverage(clip_mask + alpha_tile.clip_backdrop as f32);
+                let color = self.paint_color(paint, position);
+                blend_src_alpha(&mut self.framebuffer[index], color, coverage);
+            }
+        }
+    }
+
+    #[inline]
+    fn tile_area(&self) -> usize {
+        self.tile_size.x() as usize * self.tile_size.y() as usize
+    }
+
+    // Decodes the paint record at `paint` and evaluates it at the center of the pixel at
+    // `position`, as `tile_multicolor.inc.glsl` and `tile_paint.inc.glsl` do.
+    fn paint_color(&self, paint: Point2DI32, position: Point2DI32) -> ColorF {
+        let paint_data = match self.paint_data {
+            None => return ColorF::transparent_black(),
+            Some(ref paint_data) => paint_data,
+        };
+
+        let header = fetch_texel(paint_data, paint);
+        if header[0] == PAINT_KIND_COLOR {
+            let color = fetch_texel(paint_data, paint + Point2DI32::new(1, 0));
+            return ColorU { r: color[0], g: color[1], b: color[2], a: color[3] }.to_f32();
+        }
+
+        let row = header[2] as i32 | (header[3] as i32) << 8;
+        let value = |index| {
+            f32::from_bits(u32::from_le_bytes(fetch_texel(paint_data, Point2DI32::new(index, row))))
+        };
+        let transform = Transform2DF32::row_major(value(0),
+                                                  value(2),
+                                                  value(1),
+                                                  value(3),
+                                                  value(4),
+                                                  value(5));
+        let pixel_center = position.to_f32() + Point2DF32::splat(0.5);
+        let paint_position = transform.transform_point(pixel_center);
+
+        if header[0] == PAINT_KIND_PATTERN {
+            let image_origin = Point2DI32::new(value(6) as i32, value(7) as i32);
+            let image_size = Point2DI32::new(value(8) as i32, value(9) as i32);
+            let color = sample_pattern(paint_data,
+                                       paint_position,
+                                       image_origin,
+                                       image_size,
+                                       header[1]);
+            return ColorF(color.0 * F32x4::new(1.0, 1.0, 1.0, value(10)));
+        }
+
+        let focus = Point2DF32::new(value(6), value(7));
+        let gradient_position = paint_position;
+        let mut t = if header[0] == PAINT_KIND_LINEAR_GRADIENT {
+            gradient_position.x()
+        } else {
+            let delta = gradient_position - focus;
+            let a = focus.square_length() - 1.0;
+            let b = delta.x() * focus.x() + delta.y() * focus.y();
+            let c = delta.square_length();
+            (-b - f32::sqrt(b * b - a * c)) / a
+        };
+
+        t = match header[1] {
+            0 => util::clamp(t, 0.0, 1.0),
+            1 => 1.0 - f32::abs(t - 2.0 * f32::floor(t * 0.5) - 1.0),
+            _ => t - f32::floor(t),
+        };
+
+        // Emulate linear filtering of the ramp.
+        let ramp_x = t * (paint_data.size.x() - 1) as f32;
+        let (left_x, fraction) = (f32::floor(ramp_x), ramp_x - f32::floor(ramp_x));
+        let right_x = f32::min(left_x + 1.0, (paint_data.size.x() - 1) as f32);
+        let sample = |x: f32| {
+            let texel = fetch_texel(paint_data, Point2DI32::new(x as i32, row + 1));
+            ColorU { r: texel[0], g: texel[1], b: texel[2], a: texel[3] }.to_f32()
+        };
+        sample(left_x).lerp(sample(right_x), fraction)
+    }
+
+    fn pixel_index(&self, position: Point2DI32) -> Option<usize> {
+        if position.x() < 0 || position.y() < 0 || position.x() >= self.framebuffer_size.x() ||
+                position.y() >= self.framebuffer_size.y() {
+            return None;
+        }
+        Some((position.y() * self.framebuffer_size.x() + position.x()) as usize)
+    }
+}
+
+fn fill_rule_from_u8(fill_rule: u8) -> FillRule {
+    if fill_rule == 0 {
+        FillRule::Winding
+    } else {
+        FillRule::EvenOdd
+    }
+}
+
+fn fetch_texel(paint_data: &PaintData, coords: Point2DI32) -> [u8; 4] {
+    let offset = (coords.y() * paint_data.size.x() + coords.x()) as usize * 4;
+    let mut texel = [0; 4];
+    texel.copy_from_slice(&paint_data.texels[offset..(offset + 4)]);
+    texel
+}
+
+// Bilinearly samples the image of a pattern at `position`, in image pixels, as
+// `getPatternColor()` in `tile_paint.inc.glsl` does.
+fn sample_pattern(paint_data: &PaintData,
+                  position: Point2DF32,
+                  image_origin: Point2DI32,
+                  image_size: Point2DI32,
+                  repeat_mode: u8)
+                  -> ColorF {
+    let fetch = |mut coords: Point2DI32| {
+        if repeat_mode & 1 != 0 {
+            coords.set_x(coords.x().rem_euclid(image_size.x()));
+        }
+        if repeat_mode & 2 != 0 {
+            coords.set_y(coords.y().rem_euclid(image_size.y()));
+        }
+        if coords.x() < 0 || coords.y() < 0 || coords.x() >= image_size.x() ||
+                coords.y() >= image_size.y() {
+            return ColorF::transparent_black();
+        }
+        let coords = image_origin + coords;
+        let offset = (coords.y() * paint_data.atlas_size.x() + coords.x()) as usize * 4;
+        let texel = &paint_data.atlas_texels[offset..(offset + 4)];
+        let color = ColorU { r: texel[0], g: texel[1], b: texel[2], a: texel[3] }.to_f32();
+        ColorF(color.0 * F32x4::new(color.a(), color.a(), color.a(), 1.0))
+    };
+
+    let position = position - Point2DF32::splat(0.5);
+    let (coords, t) = (position.floor().to_i32(), position - position.floor());
+    let upper = fetch(coords).lerp(fetch(coords + Point2DI32::new(1, 0)), t.x());
+    let lower = fetch(coords + Point2DI32::new(0, 1)).lerp(fetch(coords + Point2DI32::new(1, 1)),
+                                                           t.x());
+    let color = upper.lerp(lower, t.y());
+
+    // The texels were premultiplied so that transparent ones don't darken their neighbors, but
+    // paints are blended with straight alpha, so convert back.
+    if color.a() > 0.0 {
+        let alpha = color.a();
+        ColorF(color.0 * F32x4::new(1.0 / alpha, 1.0 / alpha, 1.0 / alpha, 1.0))
+    } else {
+        color
+    }
+}
+
+/// Iterates over the pixels of a tile, yielding the index of each pixel within the tile mask and
+/// its position in the framebuffer.
+fn tile_pixels(tile_coords: Point2DI32, tile_size: Point2DI32)
+               -> impl Iterator<Item = (usize, Point2DI32)> {
+    let tile_origin = tile_coords.scale_xy(tile_size);
+    let tile_width = tile_size.x() as usize;
+    (0..(tile_width * tile_size.y() as usize)).map(move |tile_pixel| {
+        let offset = Point2DI32::new((tile_pixel % tile_width) as i32,
+                                     (tile_pixel / tile_width) as i32);
+        (tile_pixel, tile_origin + offset)
+    })
+}
+
+fn rasterize_fill(fill: &FillBatchPrimitive, tile_size: Point2DI32, mask: &mut [f32]) {
+    // Unpack exactly as `fill.vs.glsl` does, including the normalization of the fractional parts.
+    let (px, subpx) = (fill.px.0, fill.subpx.0);
+    let unpack = |px: u32, subpx: u32| {
+        Point2DF32::new((px & 0xff) as f32 + (subpx & 0xff) as f32 / 255.0,
+                        ((px >> 8) & 0xff) as f32 + ((subpx >> 8) & 0xff) as f32 / 255.0)
+    };
+    let (from, to) = (unpack(px & 0xffff, subpx & 0xffff), unpack(px >> 16, subpx >> 16));
+
+    let tile_width = tile_size.x() as usize;
+    let left = f32::max(f32::floor(f32::min(from.x(), to.x())), 0.0) as usize;
+    let right = f32::min(f32::ceil(f32::max(from.x(), to.x())), tile_width as f32) as usize;
+    let top = f32::max(f32::floor(f32::min(from.y(), to.y())), 0.0) as usize;
+    for y in top..(tile_size.y() as usize) {
+        for x in left..right {
+            let pixel_origin = Point2DF32::new(x as f32, y as f32);
+            mask[y * tile_width + x] += signed_pixel_area(from, to, pixel_origin);
+        }
+    }
+}
+
+/// Returns the area of the unit pixel at `pixel_origin` that lies below the line from `from` to
+/// `to`, negated if the line points to the right. This is the quantity that the area LUT
+/// approximates.
+fn signed_pixel_area(from: Point2DF32, to: Point2DF32, pixel_origin: Point2DF32) -> f32 {
+    let left = f32::max(f32::min(from.x(), to.x()), pixel_origin.x());
+    let right = f32::min(f32::max(from.x(), to.x()), pixel_origin.x() + 1.0);
+    if right <= left {
+        return 0.0;
+    }
+
+    let slope = (to.y() - from.y()) / (to.x() - from.x());
+    let coverage_at = |x: f32| {
+        let y = from.y() + (x - from.x()) * slope;
+        util::clamp(pixel_origin.y() + 1.0 - y, 0.0, 1.0)
+    };
+
+    // Coverage is piecewise linear in x, with kinks where the line crosses the top and bottom of
+    // the pixel, so the trapezoid rule between those points is exact.
+    let mut xs = [left, right, left, right];
+    if slope != 0.0 {
+        xs[2] = util::clamp(from.x() + (pixel_origin.y() - from.y()) / slope, left, right);
+        xs[3] = util::clamp(from.x() + (pixel_origin.y() + 1.0 - from.y()) / slope, left, right);
+    }
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let mut area = 0.0;
+    for span in xs.windows(2) {
+        area += (coverage_at(span[0]) + coverage_at(span[1])) * 0.5 * (span[1] - span[0]);
+    }
+    if from.x() > to.x() { area } else { -area }
+}
+
+// Matches `BlendState::SrcOverStraight`.
+fn blend_src_alpha(dest: &mut ColorF, src: ColorF, coverage: f32) {
+    let alpha = src.a() * coverage;
+    let rgb = src.0 * F32x4::splat(alpha) + dest.0 * F32x4::splat(1.0 - alpha);
+    *dest = ColorF(F32x4::new(rgb[0], rgb[1], rgb[2], alpha + dest.a() * (1.0 - alpha)));
+}
+
+// Matches `composite.fs.glsl`, which uses `BlendState::SrcOverPremultiplied` for
+// source-over. Groups hold premultiplied color.
+fn composite_group(dest: &mut ColorF, src: ColorF, opacity: f32, blend_mode: BlendMode) {
+    let src = ColorF(src.0 * F32x4::splat(opacity));
+    if blend_mode.needs_backdrop() {
+        *dest = blend::composite(src, *dest, blend_mode);
+    } else {
+        *dest = ColorF(src.0 + dest.0 * F32x4::splat(1.0 - src.a()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blend::BlendMode;
+    use crate::concurrent::executor::SequentialExecutor;
+    use crate::options::RenderOptions;
+    use crate::paint::{Gradient, Image, Paint, Pattern, RepeatMode};
+    use crate::scene::{ClipPath, PathObject, Scene};
+    use crate::test_util::{rect_outline, render};
+    use pathfinder_geometry::basic::line_segment::LineSegmentF32;
+    use pathfinder_geometry::basic::point::{Point2DF32, Point2DI32};
+    use pathfinder_geometry::basic::rect::RectF32;
+    use pathfinder_geometry::basic::transform2d::Transform2DF32;
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::fill::FillRule;
+    use pathfinder_geometry::outline::Outline;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_rect_coverage() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::new(48.0, 48.0)));
+        let paint = scene.push_paint(&Paint::Color(ColorU { r: 255, g: 0, b: 0, a: 255 }));
+        let outline = rect_outline(RectF32::from_points(Point2DF32::new(2.0, 2.0),
+                                                        Point2DF32::new(40.0, 46.5)));
+        scene.push_path(PathObject::new(outline, paint, String::new()));
+
+        let renderer = render(&scene, RenderOptions::default(), None);
+        assert!(renderer.stats.solid_tile_count > 0 && renderer.stats.alpha_tile_count > 0);
+
+        // Solid tile.
+        assert_eq!(renderer.pixel(Point2DI32::new(20, 24)).to_u8(),
+                   ColorU { r: 255, g: 0, b: 0, a: 255 });
+        // Alpha tiles, fully covered and empty.
+        assert_eq!(renderer.pixel(Point2DI32::new(5, 5)).to_u8(),
+                   ColorU { r: 255, g: 0, b: 0, a: 255 });
+        assert_eq!(renderer.pixel(Point2DI32::new(45, 5)).a(), 0.0);
+        assert_eq!(renderer.pixel(Point2DI32::new(1, 20)).a(), 0.0);
+        // Half-covered bottom row.
+        assert!((renderer.pixel(Point2DI32::new(20, 46)).a() - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_overlapping_paths() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::new(32.0, 32.0)));
+        let red = scene.push_paint(&Paint::Color(ColorU { r: 255, g: 0, b: 0, a: 255 }));
+        let blue = scene.push_paint(&Paint::Color(ColorU { r: 0, g: 0, b: 255, a: 128 }));
+        let outline = rect_outline(RectF32::from_points(Point2DF32::new(0.0, 0.0),
+                                                        Point2DF32::new(32.0, 32.0)));
+        scene.push_path(PathObject::new(outline, red, String::new()));
+        let outline = rect_outline(RectF32::from_points(Point2DF32::new(8.0, 8.0),
+                                                        Point2DF32::new(24.0, 24.0)));
+        scene.push_path(PathObject::new(outline, blue, String::new()));
+
+        let renderer = render(&scene, RenderOptions::default(), None);
+        assert_eq!(renderer.pixel(Point2DI32::new(2, 2)).to_u8(),
+                   ColorU { r: 255, g: 0, b: 0, a: 255 });
+        let blended = renderer.pixel(Point2DI32::new(16, 16));
+        assert!((blended.r() - 0.498).abs() < 0.01 && (blended.b() - 0.502).abs() < 0.01);
+        assert_eq!(blended.a(), 1.0);
+    }
+
+    #[test]
+    fn test_even_odd_fill_rule() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::new(48.0, 48.0)));
+        let red = scene.push_paint(&Paint::Color(ColorU { r: 255, g: 0, b: 0, a: 255 }));
+        let mut outline = rect_outline(RectF32::from_points(Point2DF32::new(0.0, 0.0),
+                                                            Point2DF32::new(48.0, 48.0)));
+        let inner = rect_outline(RectF32::from_points(Point2DF32::new(12.0, 12.0),
+                                                      Point2DF32::new(36.0, 36.0)));
+        outline.push_contour(inner.contours()[0].clone());
+
+        let mut path = PathObject::new(outline.clone(), red, String::new());
+        path.set_fill_rule(FillRule::EvenOdd);
+        scene.push_path(path);
+        let renderer = render(&scene, RenderOptions::default(), None);
+        assert_eq!(renderer.pixel(Point2DI32::new(4, 4)).to_u8(),
+                   ColorU { r: 255, g: 0, b: 0, a: 255 });
+        assert_eq!(renderer.pixel(Point2DI32::new(14, 14)).a(), 0.0);
+        assert_eq!(renderer.pixel(Point2DI32::new(24, 24)).a(), 0.0);
+
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::new(48.0, 48.0)));
+        let red = scene.push_paint(&Paint::Color(ColorU { r: 255, g: 0, b: 0, a: 255 }));
+        scene.push_path(PathObject::new(outline, red, String::new()));
+        let renderer = render(&scene, RenderOptions::default(), None);
+        assert_eq!(renderer.pixel(Point2DI32::new(14, 14)).to_u8(),
+                   ColorU { r: 255, g: 0, b: 0, a: 255 });
+        assert_eq!(renderer.pixel(Point2DI32::new(24, 24)).to_u8(),
+                   ColorU { r: 255, g: 0, b: 0, a: 255 });
+    }
+
+    #[test]
+    fn test_clip_paths() {
+        let red = ColorU { r: 255, g: 0, b: 0, a: 255 };
+        let blue = ColorU { r: 0, g: 0, b: 255, a: 255 };
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::new(64.0, 64.0)));
+        let clip_path = scene.push_clip_path(ClipPath::new(
+            rect_outline(RectF32::from_points(Point2DF32::new(8.0, 8.0),
+                                              Point2DF32::new(40.5, 56.0))),
+            FillRule::Winding,
+        ));
+
+        let red_paint = scene.push_paint(&Paint::Color(red));
+        let outline = rect_outline(RectF32::from_points(Point2DF32::default(),
+                                                        Point2DF32::new(64.0, 58.0)));
+        let mut path = PathObject::new(outline, red_paint, String::new());
+        path.set_clip_path(Some(clip_path));
+        scene.push_path(path);
+
+        let blue_paint = scene.push_paint(&Paint::Color(blue));
+        let outline = rect_outline(RectF32::from_points(Point2DF32::new(0.0, 60.0),
+                                                        Point2DF32::new(64.0, 64.0)));
+        scene.push_path(PathObject::new(outline, blue_paint, String::new()));
+
+        let renderer = render(&scene, RenderOptions::default(), None);
+        assert_eq!(renderer.pixel(Point2DI32::new(4, 20)).a(), 0.0);
+        assert_eq!(renderer.pixel(Point2DI32::new(12, 20)).to_u8(), red);
+        assert_eq!(renderer.pixel(Point2DI32::new(20, 20)).to_u8(), red);
+        assert!((renderer.pixel(Point2DI32::new(40, 20)).a() - 0.5).abs() < 0.01);
+        assert_eq!(renderer.pixel(Point2DI32::new(60, 20)).a(), 0.0);
+        assert_eq!(renderer.pixel(Point2DI32::new(20, 57)).a(), 0.0);
+        assert_eq!(renderer.pixel(Point2DI32::new(4, 62)).to_u8(), blue);
+    }
+
+    #[test]
+    fn test_gradients() {
+        let (black, white) = (ColorU::black(), ColorU { r: 255, g: 255, b: 255, a: 255 });
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::new(32.0, 64.0)));
+
+        let mut linear = Gradient::linear(LineSegmentF32::new(Point2DF32::new(8.0, 0.0),
+                                                              Point2DF32::new(24.0, 0.0)));
+        linear.add_color_stop(0.0, black);
+        linear.add_color_stop(1.0, white);
+        let linear = scene.push_paint(&Paint::Gradient(linear));
+        let outline = rect_outline(RectF32::new(Point2DF32::default(),
+                                                Point2DF32::new(32.0, 32.0)));
+        scene.push_path(PathObject::new(outline, linear, String::new()));
+
+        let mut radial = Gradient::radial(Point2DF32::new(16.0, 48.0), 16.0);
+        radial.add_color_stop(0.0, white);
+        radial.add_color_stop(1.0, black);
+        let radial = scene.push_paint(&Paint::Gradient(radial));
+        let outline = rect_outline(RectF32::new(Point2DF32::new(0.0, 32.0),
+                                                Point2DF32::new(32.0, 32.0)));
+        scene.push_path(PathObject::new(outline, radial, String::new()));
+
+        let renderer = render(&scene, RenderOptions::default(), None);
+        assert_eq!(renderer.pixel(Point2DI32::new(2, 10)).to_u8(), black);
+        assert_eq!(renderer.pixel(Point2DI32::new(28, 10)).to_u8(), white);
+        let expected = (15.5 - 8.0) / 16.0;
+        assert!((renderer.pixel(Point2DI32::new(15, 10)).r() - expected).abs() < 0.01);
+
+        let expected = 1.0 - f32::sqrt(8.5 * 8.5 + 0.5 * 0.5) / 16.0;
+        assert!((renderer.pixel(Point2DI32::new(24, 48)).r() - expected).abs() < 0.01);
+        assert_eq!(renderer.pixel(Point2DI32::new(1, 33)).to_u8(), black);
+    }
+
+    #[test]
+    fn test_rotated_gradient_on_non_square_box() {
+        let (black, white) = (ColorU::black(), ColorU { r: 255, g: 255, b: 255, a: 255 });
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::new(64.0, 16.0)));
+
+        // A gradient across the unit square, turned to run downward and then stretched over a
+        // 64x16 bounding box, as an SVG `objectBoundingBox` gradient with a rotated
+        // `gradientTransform` is.
+        let mut gradient = Gradient::linear(LineSegmentF32::new(Point2DF32::default(),
+                                                                Point2DF32::new(1.0, 0.0)));
+        gradient.add_color_stop(0.0, black);
+        gradient.add_color_stop(1.0, white);
+        let bounding_box_transform = Transform2DF32::from_scale(Point2DF32::new(64.0, 16.0));
+        gradient.set_transform(Transform2DF32::from_rotation(FRAC_PI_2)
+                                              .post_mul(&bounding_box_transform));
+        let paint = scene.push_paint(&Paint::Gradient(gradient));
+        let outline = rect_outline(scene.view_box());
+        scene.push_path(PathObject::new(outline, paint, String::new()));
+
+        let renderer = render(&scene, RenderOptions::default(), None);
+        for &x in &[4, 60] {
+            for &y in &[2, 8, 13] {
+                let expected = (y as f32 + 0.5) / 16.0;
+                let actual = renderer.pixel(Point2DI32::new(x, y)).r();
+                assert!((actual - expected).abs() < 0.01, "{} != {}", actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_patterns() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::new(64.0, 32.0)));
+        let image = Image::new(Point2DI32::new(2, 1), vec![255, 0, 0, 255, 0, 0, 255, 255]);
+
+        let mut repeating = Pattern::new(image.clone(), RepeatMode::Repeat);
+        repeating.set_transform(Transform2DF32::from_scale(Point2DF32::splat(16.0)));
+        let repeating = scene.push_paint(&Paint::Pattern(repeating));
+        let outline = rect_outline(RectF32::new(Point2DF32::default(),
+                                                Point2DF32::new(64.0, 16.0)));
+        scene.push_path(PathObject::new(outline, repeating, String::new()));
+
+        let mut single = Pattern::new(image, RepeatMode::NoRepeat);
+        single.set_transform(Transform2DF32::from_scale(Point2DF32::splat(16.0)).post_mul(
+            &Transform2DF32::from_translation(Point2DF32::new(0.0, 16.0))));
+        let single = scene.push_paint(&Paint::Pattern(single));
+        let outline = rect_outline(RectF32::new(Point2DF32::new(0.0, 16.0),
+                                                Point2DF32::new(64.0, 16.0)));
+        scene.push_path(PathObject::new(outline, single, String::new()));
+
+        let renderer = render(&scene, RenderOptions::default(), None);
+        assert!(renderer.pixel(Point2DI32::new(8, 8)).r() > 0.95);
+        assert!(renderer.pixel(Point2DI32::new(24, 8)).b() > 0.95);
+        assert!(renderer.pixel(Point2DI32::new(40, 8)).r() > 0.95);
+
+        let inside = renderer.pixel(Point2DI32::new(8, 24));
+        assert!(inside.r() > 0.9 && inside.a() > 0.95);
+        assert_eq!(renderer.pixel(Point2DI32::new(40, 24)).a(), 0.0);
+    }
+
+    #[test]
+    fn test_translucent_patterns() {
+        // A translucent pattern should composite just like a solid paint of the same color,
+        // both over an opaque background and over nothing.
+        let color = ColorU { r: 100, g: 100, b: 100, a: 64 };
+        let texels = [color.r, color.g, color.b, color.a].repeat(4);
+        let image = Image::new(Point2DI32::splat(2), texels);
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::new(32.0, 16.0)));
+        let white = scene.push_paint(&Paint::Color(ColorU::from_u32(0xffffffff)));
+        let outline = rect_outline(RectF32::new(Point2DF32::default(), Point2DF32::new(32.0, 8.0)));
+        scene.push_path(PathObject::new(outline, white, String::new()));
+        let pattern = Paint::Pattern(Pattern::new(image, RepeatMode::Repeat));
+        for &(x, ref paint) in &[(0.0, pattern), (16.0, Paint::Color(color))] {
+            let paint = scene.push_paint(paint);
+            let outline = rect_outline(RectF32::new(Point2DF32::new(x, 0.0),
+                                                    Point2DF32::splat(16.0)));
+            scene.push_path(PathObject::new(outline, paint, String::new()));
+        }
+
+        let renderer = render(&scene, RenderOptions::default(), None);
+        for &position in &[Point2DI32::new(4, 4), Point2DI32::new(4, 12)] {
+            let pattern_color = renderer.pixel(position).to_u8();
+            let color = renderer.pixel(position + Point2DI32::new(16, 0)).to_u8();
+            let pattern_channels =
+                [pattern_color.r, pattern_color.g, pattern_color.b, pattern_color.a];
+            let channels = [color.r, color.g, color.b, color.a];
+            for (&pattern_channel, &channel) in pattern_channels.iter().zip(channels.iter()) {
+                assert!((pattern_channel as i32 - channel as i32).abs() <= 1,
+                        "{:?} != {:?}",
+                        pattern_color,
+                        color);
+            }
+        }
+    }
+
+    #[test]
+    fn test_group_opacity() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::new(48.0, 48.0)));
+        let white = scene.push_paint(&Paint::Color(ColorU::from_u32(0xffffffff)));
+        let red = scene.push_paint(&Paint::Color(ColorU { r: 255, g: 0, b: 0, a: 255 }));
+        let blue = scene.push_paint(&Paint::Color(ColorU { r: 0, g: 0, b: 255, a: 255 }));
+        let green = scene.push_paint(&Paint::Color(ColorU { r: 0, g: 255, b: 0, a: 255 }));
+        let outline = rect_outline(RectF32::new(Point2DF32::default(),
+                                                Point2DF32::new(48.0, 48.0)));
+        scene.push_path(PathObject::new(outline, white, String::new()));
+
+        scene.push_group(0.5);
+        let outline = rect_outline(RectF32::new(Point2DF32::default(),
+                                                Point2DF32::new(32.0, 32.0)));
+        scene.push_path(PathObject::new(outline, red, String::new()));
+        let outline = rect_outline(RectF32::new(Point2DF32::new(16.0, 16.0),
+                                                Point2DF32::new(32.0, 32.0)));
+        scene.push_path(PathObject::new(outline, blue, String::new()));
+        scene.pop_group();
+
+        let outline = rect_outline(RectF32::new(Point2DF32::default(),
+                                                Point2DF32::new(16.0, 16.0)));
+        scene.push_path(PathObject::new(outline, green, String::new()));
+
+        let renderer = render(&scene, RenderOptions::default(), None);
+        assert_eq!(renderer.pixel(Point2DI32::new(8, 8)).to_u8(), ColorU::from_u32(0x00ff00ff));
+        assert_eq!(renderer.pixel(Point2DI32::new(40, 8)).to_u8(), ColorU::from_u32(0xffffffff));
+        let red_over_white = renderer.pixel(Point2DI32::new(24, 8));
+        assert!(red_over_white.r() == 1.0 && (red_over_white.g() - 0.5).abs() < 0.01);
+        // The blue path hides the red one within the group, so no red shows through.
+        let blue_over_white = renderer.pixel(Point2DI32::new(24, 24));
+        assert!((blue_over_white.r() - 0.5).abs() < 0.01 && blue_over_white.b() == 1.0);
+        assert_eq!(blue_over_white.a(), 1.0);
+    }
+
+    #[test]
+    fn test_blend_modes() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::new(48.0, 16.0)));
+        let gray = scene.push_paint(&Paint::Color(ColorU { r: 128, g: 128, b: 128, a: 255 }));
+        let red = scene.push_paint(&Paint::Color(ColorU { r: 255, g: 0, b: 0, a: 255 }));
+        let outline = rect_outline(RectF32::new(Point2DF32::default(),
+                                                Point2DF32::new(32.0, 16.0)));
+        scene.push_path(PathObject::new(outline, gray, String::new()));
+
+        // Multiply over the gray, and over nothing on the right.
+        let outline = rect_outline(RectF32::new(Point2DF32::new(16.0, 0.0),
+                                                Point2DF32::new(32.0, 16.0)));
+        let mut path = PathObject::new(outline, red, String::new());
+        path.set_blend_mode(BlendMode::Multiply);
+        scene.push_path(path);
+
+        // Destination-out punches a hole through everything beneath.
+        let outline = rect_outline(RectF32::new(Point2DF32::default(),
+                                                Point2DF32::new(8.0, 16.0)));
+        let mut path = PathObject::new(outline, red, String::new());
+        path.set_blend_mode(BlendMode::DestOut);
+        scene.push_path(path);
+
+        let renderer = render(&scene, RenderOptions::default(), None);
+        assert_eq!(renderer.pixel(Point2DI32::new(4, 8)).a(), 0.0);
+        assert_eq!(renderer.pixel(Point2DI32::new(12, 8)).to_u8(),
+                   ColorU { r: 128, g: 128, b: 128, a: 255 });
+        assert_eq!(renderer.pixel(Point2DI32::new(24, 8)).to_u8(),
+                   ColorU { r: 128, g: 0, b: 0, a: 255 });
+        assert_eq!(renderer.pixel(Point2DI32::new(40, 8)).to_u8(),
+                   ColorU { r: 255, g: 0, b: 0, a: 255 });
+    }
+
+    #[test]
+    fn test_tile_sizes() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::new(96.0, 64.0)));
+        let paint = scene.push_paint(&Paint::Color(ColorU { r: 0, g: 64, b: 255, a: 255 }));
+        let outline = Outline::from_svg_path_data("M 3.5 2 L 90 10.25 Q 60 60 7 58 Z").unwrap();
+        scene.push_path(PathObject::new(outline, paint, String::new()));
+        let outline = rect_outline(RectF32::from_points(Point2DF32::new(40.5, 20.0),
+                                                        Point2DF32::new(88.0, 61.75)));
+        let mut path = PathObject::new(outline, paint, String::new());
+        path.set_fill_rule(FillRule::EvenOdd);
+        scene.push_path(path);
+
+        let size = scene.view_box().size().to_i32();
+        let expected =
+            scene.render_to_pixels(RenderOptions::default(), size, &SequentialExecutor).unwrap();
+        for &tile_size in &[Point2DI32::splat(8), Point2DI32::splat(32), Point2DI32::new(64, 4)] {
+            let options = RenderOptions { tile_size, ..RenderOptions::default() };
+            let pixels = scene.render_to_pixels(options, size, &SequentialExecutor).unwrap();
+            for (&pixel, &expected_pixel) in pixels.iter().zip(expected.iter()) {
+                assert!((pixel as i32 - expected_pixel as i32).abs() <= 1,
+                        "tile size {:?} renders differently",
+                        tile_size);
+            }
+        }
+
+        // A coarse tolerance flattens the curve visibly.
+        let options = RenderOptions { flattening_tolerance: 8.0, ..RenderOptions::default() };
+        assert_ne!(scene.render_to_pixels(options, size, &SequentialExecutor).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_render_to_other_sizes() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::splat(100.0)));
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+        let outline = rect_outline(RectF32::from_points(Point2DF32::splat(2.0),
+                                                        Point2DF32::splat(98.0)));
+        scene.push_path(PathObject::new(outline, paint, String::new()));
+        let alpha_at = |pixels: &[u8], width: usize, x: usize, y: usize| {
+            pixels[(y * width + x) * 4 + 3]
+        };
+
+        // The whole scene is shrunk into the thumbnail.
+        let pixels = scene.render_to_pixels(RenderOptions::default(),
+                                            Point2DI32::splat(50),
+                                            &SequentialExecutor).unwrap();
+        for y in 0..50 {
+            for x in 0..50 {
+                let inside = (1..49).contains(&x) && (1..49).contains(&y);
+                assert_eq!(alpha_at(&pixels, 50, x, y), if inside { 255 } else { 0 });
+            }
+        }
+
+        // An image of another shape keeps the scene's aspect ratio and leaves the rest empty.
+        let pixels = scene.render_to_pixels(RenderOptions::default(),
+                                            Point2DI32::new(50, 25),
+                                            &SequentialExecutor).unwrap();
+        for y in 0..25 {
+            assert!((25..50).all(|x| alpha_at(&pixels, 50, x, y) == 0));
+        }
+        assert_eq!(alpha_at(&pixels, 50, 12, 12), 255);
+
+        // A larger image magnifies the scene.
+        let pixels = scene.render_to_pixels(RenderOptions::default(),
+                                            Point2DI32::splat(200),
+                                            &SequentialExecutor).unwrap();
+        assert_eq!(alpha_at(&pixels, 200, 3, 3), 0);
+        assert_eq!(alpha_at(&pixels, 200, 5, 5), 255);
+        assert_eq!(alpha_at(&pixels, 200, 195, 195), 255);
+        assert_eq!(alpha_at(&pixels, 200, 197, 197), 0);
+    }
+}
@@ -8,11 +8,12 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use crate::blend::BlendMode;
 use crate::gpu::debug::DebugUIPresenter;
 use crate::gpu_data::{AlphaTileBatchPrimitive, FillBatchPrimitive, PaintData};
 use crate::gpu_data::{RenderCommand, SolidTileBatchPrimitive};
 use crate::post::DefringingKernel;
-use crate::tiles::{TILE_HEIGHT, TILE_WIDTH};
+use crate::tiles::{DEFAULT_TILE_HEIGHT, DEFAULT_TILE_WIDTH};
 use pathfinder_geometry::basic::point::{Point2DI32, Point3DF32};
 use pathfinder_geometry::basic::rect::RectI32;
 use pathfinder_geometry::basic::transform3d::Transform3DF32;
@@ -22,9 +23,11 @@ use pathfinder_gpu::{BlendState, BufferData, BufferTarget, BufferUploadMode, Cle
 use pathfinder_gpu::{DepthFunc, DepthState, Device, Primitive, RenderState, StencilFunc};
 use pathfinder_gpu::{StencilState, TextureFormat, UniformData, VertexAttrClass};
 use pathfinder_gpu::{VertexAttrDescriptor, VertexAttrType};
-use pathfinder_simd::default::{F32x4, I32x4};
+use pathfinder_simd::default::F32x4;
 use std::cmp;
 use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 use std::mem;
 use std::ops::{Add, Div};
 use std::time::Duration;
@@ -33,13 +36,13 @@ use std::u32;
 static QUAD_VERTEX_POSITIONS: [u8; 8] = [0, 0, 1, 0, 1, 1, 0, 1];
 
 // FIXME(pcwalton): Shrink this again!
-const MASK_FRAMEBUFFER_WIDTH: i32 = TILE_WIDTH as i32 * 256;
-const MASK_FRAMEBUFFER_HEIGHT: i32 = TILE_HEIGHT as i32 * 256;
+// The mask framebuffer holds this many alpha tiles across and down, whatever their size.
+const MASK_FRAMEBUFFER_SIZE_IN_TILES: i32 = 256;
 
 // TODO(pcwalton): Replace with `mem::size_of` calls?
-const FILL_INSTANCE_SIZE: usize = 8;
-const SOLID_TILE_INSTANCE_SIZE: usize = 10;
-const MASK_TILE_INSTANCE_SIZE: usize = 12;
+const FILL_INSTANCE_SIZE: usize = 10;
+const SOLID_TILE_INSTANCE_SIZE: usize = 12;
+const MASK_TILE_INSTANCE_SIZE: usize = 20;
 
 const MAX_FILLS_PER_BATCH: usize = 0x4000;
 const MAX_ALPHA_TILES_PER_BATCH: usize = 0x4000;
@@ -67,7 +70,9 @@ where
     quad_vertex_positions_buffer: D::Buffer,
     fill_vertex_array: FillVertexArray<D>,
     mask_framebuffer: D::Framebuffer,
+    tile_size: Point2DI32,
     paint_texture: Option<D::Texture>,
+    paint_atlas_texture: Option<D::Texture>,
 
     // Postprocessing shader
     postprocess_source_framebuffer: Option<D::Framebuffer>,
@@ -75,6 +80,12 @@ where
     postprocess_vertex_array: PostprocessVertexArray<D>,
     gamma_lut_texture: D::Texture,
 
+    // Group compositing shader
+    composite_program: CompositeProgram<D>,
+    composite_vertex_array: CompositeVertexArray<D>,
+    group_framebuffers: Vec<D::Framebuffer>,
+    free_group_framebuffers: Vec<D::Framebuffer>,
+
     // Stencil shader
     stencil_program: StencilProgram<D>,
     stencil_vertex_array: StencilVertexArray<D>,
@@ -85,6 +96,8 @@ where
 
     // Rendering state
     mask_framebuffer_cleared: bool,
+    // Set when the current scene needs larger textures than this device supports.
+    scene_error: Option<RenderError>,
     buffered_fills: Vec<FillBatchPrimitive>,
     buffered_alpha_tiles: Vec<AlphaTileBatchPrimitive>,
     buffered_solid_tiles: Vec<SolidTileBatchPrimitive>,
@@ -118,6 +131,7 @@ where
         let alpha_monochrome_tile_program = AlphaTileMonochromeProgram::new(&device, resources);
 
         let postprocess_program = PostprocessProgram::new(&device, resources);
+        let composite_program = CompositeProgram::new(&device, resources);
         let stencil_program = StencilProgram::new(&device, resources);
         let reprojection_program = ReprojectionProgram::new(&device, resources);
 
@@ -159,6 +173,11 @@ where
             &postprocess_program,
             &quad_vertex_positions_buffer,
         );
+        let composite_vertex_array = CompositeVertexArray::new(
+            &device,
+            &composite_program,
+            &quad_vertex_positions_buffer,
+        );
         let stencil_vertex_array = StencilVertexArray::new(&device, &stencil_program);
         let reprojection_vertex_array = ReprojectionVertexArray::new(
             &device,
@@ -166,11 +185,8 @@ where
             &quad_vertex_positions_buffer,
         );
 
-        let mask_framebuffer_size =
-            Point2DI32::new(MASK_FRAMEBUFFER_WIDTH, MASK_FRAMEBUFFER_HEIGHT);
-        let mask_framebuffer_texture =
-            device.create_texture(TextureFormat::R16F, mask_framebuffer_size);
-        let mask_framebuffer = device.create_framebuffer(mask_framebuffer_texture);
+        let tile_size = Point2DI32::new(DEFAULT_TILE_WIDTH as i32, DEFAULT_TILE_HEIGHT as i32);
+        let mask_framebuffer = create_mask_framebuffer(&device, tile_size);
 
         let window_size = dest_framebuffer.window_size(&device);
         let debug_ui_presenter = DebugUIPresenter::new(&device, resources, window_size);
@@ -192,13 +208,20 @@ where
             quad_vertex_positions_buffer,
             fill_vertex_array,
             mask_framebuffer,
+            tile_size,
             paint_texture: None,
+            paint_atlas_texture: None,
 
             postprocess_source_framebuffer: None,
             postprocess_program,
             postprocess_vertex_array,
             gamma_lut_texture,
 
+            composite_program,
+            composite_vertex_array,
+            group_framebuffers: vec![],
+            free_group_framebuffers: vec![],
+
             stencil_program,
             stencil_vertex_array,
 
@@ -212,6 +235,7 @@ where
             debug_ui_presenter,
 
             mask_framebuffer_cleared: false,
+            scene_error: None,
             buffered_fills: vec![],
             buffered_alpha_tiles: vec![],
             buffered_solid_tiles: vec![],
@@ -230,12 +254,24 @@ where
         self.init_postprocessing_framebuffer();
 
         self.mask_framebuffer_cleared = false;
+        self.scene_error = None;
         self.stats = RenderStats::default();
     }
 
     pub fn render_command(&mut self, command: &RenderCommand) {
+        // Tiles can't be drawn without their paints or masks, so the rest of the scene is skipped
+        // and the error is reported by `end_scene()`.
+        if self.scene_error.is_some() {
+            return;
+        }
+
         match *command {
-            RenderCommand::Start { bounding_quad, path_count } => {
+            RenderCommand::Start { bounding_quad, path_count, tile_size } => {
+                if tile_size != self.tile_size {
+                    self.mask_framebuffer = create_mask_framebuffer(&self.device, tile_size);
+                    self.mask_framebuffer_cleared = false;
+                    self.tile_size = tile_size;
+                }
                 if self.use_depth {
                     self.draw_stencil(&bounding_quad);
                 }
@@ -257,17 +293,28 @@ where
                 self.begin_composite_timer_query();
                 self.draw_buffered_alpha_tiles();
             }
+            RenderCommand::PushGroup => self.push_group(),
+            RenderCommand::PopGroup { opacity, blend_mode } => {
+                self.pop_group(opacity, blend_mode)
+            }
             RenderCommand::Finish { .. } => {}
         }
     }
 
-    pub fn end_scene(&mut self) {
+    /// Finishes drawing the scene. Fails if the scene needed more of the device than it has, in
+    /// which case only the part of the scene before the failure was drawn.
+    pub fn end_scene(&mut self) -> Result<(), RenderError> {
         if self.postprocessing_needed() {
             self.postprocess();
         }
 
         self.end_composite_timer_query();
         self.pending_timers.push_back(mem::replace(&mut self.current_timers, RenderTimers::new()));
+
+        match self.scene_error.take() {
+            None => Ok(()),
+            Some(error) => Err(error),
+        }
     }
 
     pub fn draw_debug_ui(&self) {
@@ -343,6 +390,13 @@ where
     }
 
     fn upload_paint_data(&mut self, paint_data: &PaintData) {
+        let max_texture_size = self.device.max_texture_size();
+        let fits = |size: Point2DI32| size.x() <= max_texture_size && size.y() <= max_texture_size;
+        if !fits(paint_data.size) || !fits(paint_data.atlas_size) {
+            self.scene_error = Some(RenderError::PaintDataTooLarge);
+            return;
+        }
+
         match self.paint_texture {
             Some(ref paint_texture) if
                 self.device.texture_size(paint_texture) == paint_data.size => {}
@@ -355,6 +409,24 @@ where
         self.device.upload_to_texture(self.paint_texture.as_ref().unwrap(),
                                       paint_data.size,
                                       &paint_data.texels);
+
+        match self.paint_atlas_texture {
+            Some(ref paint_atlas_texture) if
+                self.device.texture_size(paint_atlas_texture) == paint_data.atlas_size => {}
+            _ => {
+                let texture = self.device.create_texture(TextureFormat::RGBA8,
+                                                         paint_data.atlas_size);
+                self.paint_atlas_texture = Some(texture)
+            }
+        }
+
+        self.device.upload_to_texture(self.paint_atlas_texture.as_ref().unwrap(),
+                                      paint_data.atlas_size,
+                                      &paint_data.atlas_texels);
+    }
+
+    fn mask_framebuffer_size(&self) -> Point2DI32 {
+        self.device.texture_size(self.device.framebuffer_texture(&self.mask_framebuffer))
     }
 
     fn clear_mask_framebuffer(&mut self) {
@@ -372,6 +444,14 @@ where
             return;
         }
 
+        let mask_size = self.mask_framebuffer_size();
+        let mask_tile_count =
+            (mask_size.x() / self.tile_size.x()) * (mask_size.y() / self.tile_size.y());
+        if fills.iter().any(|fill| fill.alpha_tile_index as i32 >= mask_tile_count) {
+            self.scene_error = Some(RenderError::TooManyAlphaTiles);
+            return;
+        }
+
         let timer_query = self.allocate_timer_query();
         self.device.begin_timer_query(&timer_query);
 
@@ -468,13 +548,11 @@ where
         self.device.use_program(&self.fill_program.program);
         self.device.set_uniform(
             &self.fill_program.framebuffer_size_uniform,
-            UniformData::Vec2(
-                I32x4::new(MASK_FRAMEBUFFER_WIDTH, MASK_FRAMEBUFFER_HEIGHT, 0, 0).to_f32x4(),
-            ),
+            UniformData::Vec2(self.mask_framebuffer_size().to_f32().0),
         );
         self.device.set_uniform(
             &self.fill_program.tile_size_uniform,
-            UniformData::Vec2(I32x4::new(TILE_WIDTH as i32, TILE_HEIGHT as i32, 0, 0).to_f32x4()),
+            UniformData::Vec2(self.tile_size.to_f32().0),
         );
         self.device.bind_texture(&self.area_lut_texture, 0);
         self.device.set_uniform(
@@ -523,7 +601,7 @@ where
         );
         self.device.set_uniform(
             &alpha_tile_program.tile_size_uniform,
-            UniformData::Vec2(I32x4::new(TILE_WIDTH as i32, TILE_HEIGHT as i32, 0, 0).to_f32x4()),
+            UniformData::Vec2(self.tile_size.to_f32().0),
         );
         self.device
             .bind_texture(self.device.framebuffer_texture(&self.mask_framebuffer), 0);
@@ -533,9 +611,7 @@ where
         );
         self.device.set_uniform(
             &alpha_tile_program.stencil_texture_size_uniform,
-            UniformData::Vec2(
-                I32x4::new(MASK_FRAMEBUFFER_WIDTH, MASK_FRAMEBUFFER_HEIGHT, 0, 0).to_f32x4(),
-            ),
+            UniformData::Vec2(self.mask_framebuffer_size().to_f32().0),
         );
 
         match self.render_mode {
@@ -550,6 +626,11 @@ where
                     &self.alpha_multicolor_tile_program.paint_texture_size_uniform,
                     UniformData::Vec2(self.device.texture_size(paint_texture).0.to_f32x4())
                 );
+                self.device.bind_texture(self.paint_atlas_texture.as_ref().unwrap(), 2);
+                self.device.set_uniform(
+                    &self.alpha_multicolor_tile_program.paint_atlas_uniform,
+                    UniformData::TextureUnit(2),
+                );
             }
             RenderMode::Monochrome { .. } if self.postprocessing_needed() => {
                 self.device.set_uniform(
@@ -571,7 +652,7 @@ where
             UniformData::Vec2(F32x4::default()),
         );
         let render_state = RenderState {
-            blend: BlendState::RGBSrcAlphaAlphaOneMinusSrcAlpha,
+            blend: self.alpha_tile_blend_state(),
             stencil: self.stencil_state(),
             ..RenderState::default()
         };
@@ -613,7 +694,7 @@ where
         );
         self.device.set_uniform(
             &solid_tile_program.tile_size_uniform,
-            UniformData::Vec2(I32x4::new(TILE_WIDTH as i32, TILE_HEIGHT as i32, 0, 0).to_f32x4()),
+            UniformData::Vec2(self.tile_size.to_f32().0),
         );
 
         match self.render_mode {
@@ -632,6 +713,11 @@ where
                         .paint_texture_size_uniform,
                     UniformData::Vec2(self.device.texture_size(paint_texture).0.to_f32x4())
                 );
+                self.device.bind_texture(self.paint_atlas_texture.as_ref().unwrap(), 1);
+                self.device.set_uniform(
+                    &self.solid_multicolor_tile_program.paint_atlas_uniform,
+                    UniformData::TextureUnit(1),
+                );
             }
             RenderMode::Monochrome { .. } if self.postprocessing_needed() => {
                 self.device.set_uniform(
@@ -667,6 +753,108 @@ where
         self.buffered_solid_tiles.clear();
     }
 
+    fn alpha_tile_blend_state(&self) -> BlendState {
+        // A group's alpha channel is its coverage when it gets composited.
+        if self.group_framebuffers.is_empty() {
+            BlendState::RGBSrcAlphaAlphaOneMinusSrcAlpha
+        } else {
+            BlendState::SrcOverStraight
+        }
+    }
+
+    fn push_group(&mut self) {
+        self.draw_buffered_solid_tiles();
+        self.draw_buffered_alpha_tiles();
+
+        let framebuffer = self.allocate_group_framebuffer();
+        self.group_framebuffers.push(framebuffer);
+
+        self.bind_draw_framebuffer();
+        self.device.clear(&ClearParams {
+            color: Some(ColorF::transparent_black()),
+            ..ClearParams::default()
+        });
+    }
+
+    fn pop_group(&mut self, opacity: f32, blend_mode: BlendMode) {
+        self.draw_buffered_solid_tiles();
+        self.draw_buffered_alpha_tiles();
+
+        let source_framebuffer = self.group_framebuffers.pop().expect("No group to pop!");
+
+        // Source-over can be done with hardware blending. Everything else reads the backdrop, so
+        // the result goes into a new target that replaces it.
+        let backdrop_framebuffer = if blend_mode.needs_backdrop() {
+            let backdrop_framebuffer = self.group_framebuffers
+                                           .pop()
+                                           .expect("Blending needs a group to blend with!");
+            let dest_framebuffer = self.allocate_group_framebuffer();
+            self.group_framebuffers.push(dest_framebuffer);
+            Some(backdrop_framebuffer)
+        } else {
+            None
+        };
+
+        self.bind_draw_framebuffer();
+
+        self.device.bind_vertex_array(&self.composite_vertex_array.vertex_array);
+        self.device.use_program(&self.composite_program.program);
+        self.device.bind_texture(self.device.framebuffer_texture(&source_framebuffer), 0);
+        self.device.set_uniform(
+            &self.composite_program.source_uniform,
+            UniformData::TextureUnit(0),
+        );
+        if let Some(ref backdrop_framebuffer) = backdrop_framebuffer {
+            self.device.bind_texture(self.device.framebuffer_texture(backdrop_framebuffer), 1);
+        }
+        self.device.set_uniform(
+            &self.composite_program.backdrop_uniform,
+            UniformData::TextureUnit(1),
+        );
+        self.device.set_uniform(
+            &self.composite_program.opacity_uniform,
+            UniformData::Float(opacity),
+        );
+        self.device.set_uniform(
+            &self.composite_program.blend_mode_uniform,
+            UniformData::Int(blend_mode as i32),
+        );
+        let render_state = if blend_mode.needs_backdrop() {
+            RenderState::default()
+        } else {
+            // Groups are composited again later, so their alpha has to be exact.
+            let blend = if self.group_framebuffers.is_empty() {
+                BlendState::RGBOneAlphaOneMinusSrcAlpha
+            } else {
+                BlendState::SrcOverPremultiplied
+            };
+            RenderState {
+                blend,
+                stencil: self.stencil_state(),
+                ..RenderState::default()
+            }
+        };
+        self.device.draw_arrays(Primitive::TriangleFan, 4, &render_state);
+
+        self.free_group_framebuffers.push(source_framebuffer);
+        self.free_group_framebuffers.extend(backdrop_framebuffer);
+    }
+
+    fn allocate_group_framebuffer(&mut self) -> D::Framebuffer {
+        let framebuffer_size = self.draw_viewport().size();
+        let framebuffer_index = self.free_group_framebuffers.iter().position(|framebuffer| {
+            self.device.texture_size(self.device.framebuffer_texture(framebuffer)) ==
+                framebuffer_size
+        });
+        match framebuffer_index {
+            Some(framebuffer_index) => self.free_group_framebuffers.swap_remove(framebuffer_index),
+            None => {
+                let texture = self.device.create_texture(TextureFormat::RGBA8, framebuffer_size);
+                self.device.create_framebuffer(texture)
+            }
+        }
+    }
+
     fn postprocess(&mut self) {
         let (fg_color, bg_color, defringing_kernel, gamma_correction_enabled);
         match self.render_mode {
@@ -739,8 +927,10 @@ where
             &self.postprocess_program.gamma_correction_enabled_uniform,
             UniformData::Int(gamma_correction_enabled as i32),
         );
-        self.device
-            .draw_arrays(Primitive::TriangleFan, 4, &RenderState::default());
+        self.device.draw_arrays(Primitive::TriangleFan, 4, &RenderState {
+            stencil: self.stencil_state(),
+            ..RenderState::default()
+        });
     }
 
     fn solid_tile_program(&self) -> &SolidTileProgram<D> {
@@ -778,7 +968,10 @@ where
             BufferTarget::Vertex,
             BufferUploadMode::Dynamic,
         );
-        self.bind_draw_framebuffer();
+
+        // The postprocessing source framebuffer has no stencil buffer, so the quad always goes
+        // into the destination framebuffer, and postprocessing is masked to it instead.
+        self.bind_dest_framebuffer();
 
         self.device
             .bind_vertex_array(&self.stencil_vertex_array.vertex_array);
@@ -843,7 +1036,9 @@ where
     }
 
     pub fn bind_draw_framebuffer(&self) {
-        if self.postprocessing_needed() {
+        if let Some(group_framebuffer) = self.group_framebuffers.last() {
+            self.device.bind_framebuffer(group_framebuffer);
+        } else if self.postprocessing_needed() {
             self.device
                 .bind_framebuffer(self.postprocess_source_framebuffer.as_ref().unwrap());
         } else {
@@ -958,6 +1153,20 @@ where
     }
 }
 
+// The mask framebuffer holds `MASK_FRAMEBUFFER_SIZE_IN_TILES` tiles on a side, or as many as fit
+// in the largest texture the device supports.
+fn create_mask_framebuffer<D>(device: &D, tile_size: Point2DI32) -> D::Framebuffer
+                              where D: Device {
+    let max_texture_size = device.max_texture_size();
+    let size_in_tiles = Point2DI32::new(
+        cmp::min(max_texture_size / tile_size.x(), MASK_FRAMEBUFFER_SIZE_IN_TILES),
+        cmp::min(max_texture_size / tile_size.y(), MASK_FRAMEBUFFER_SIZE_IN_TILES),
+    );
+    let size = size_in_tiles.scale_xy(tile_size);
+    let texture = device.create_texture(TextureFormat::R16F, size);
+    device.create_framebuffer(texture)
+}
+
 struct FillVertexArray<D>
 where
     D: Device,
@@ -1007,7 +1216,7 @@ where
         });
         device.bind_buffer(&vertex_buffer, BufferTarget::Vertex);
         device.configure_vertex_attr(&from_px_attr, &VertexAttrDescriptor {
-            size: 1,
+            size: 2,
             class: VertexAttrClass::Int,
             attr_type: VertexAttrType::U8,
             stride: FILL_INSTANCE_SIZE,
@@ -1015,11 +1224,11 @@ where
             divisor: 1,
         });
         device.configure_vertex_attr(&to_px_attr, &VertexAttrDescriptor {
-            size: 1,
+            size: 2,
             class: VertexAttrClass::Int,
             attr_type: VertexAttrType::U8,
             stride: FILL_INSTANCE_SIZE,
-            offset: 1,
+            offset: 2,
             divisor: 1,
         });
         device.configure_vertex_attr(&from_subpx_attr, &VertexAttrDescriptor {
@@ -1027,7 +1236,7 @@ where
             class: VertexAttrClass::FloatNorm,
             attr_type: VertexAttrType::U8,
             stride: FILL_INSTANCE_SIZE,
-            offset: 2,
+            offset: 4,
             divisor: 1,
         });
         device.configure_vertex_attr(&to_subpx_attr, &VertexAttrDescriptor {
@@ -1035,7 +1244,7 @@ where
             class: VertexAttrClass::FloatNorm,
             attr_type: VertexAttrType::U8,
             stride: FILL_INSTANCE_SIZE,
-            offset: 4,
+            offset: 6,
             divisor: 1,
         });
         device.configure_vertex_attr(&tile_index_attr, &VertexAttrDescriptor {
@@ -1043,7 +1252,7 @@ where
             class: VertexAttrClass::Int,
             attr_type: VertexAttrType::U16,
             stride: FILL_INSTANCE_SIZE,
-            offset: 6,
+            offset: 8,
             divisor: 1,
         });
 
@@ -1086,6 +1295,13 @@ where
         let tile_index_attr = device.get_vertex_attr(&alpha_tile_program.program, "TileIndex");
         let color_tex_coord_attr = device.get_vertex_attr(&alpha_tile_program.program,
                                                           "ColorTexCoord");
+        let fill_rule_attr = device.get_vertex_attr(&alpha_tile_program.program, "FillRule");
+        let clip_fill_rule_attr = device.get_vertex_attr(&alpha_tile_program.program,
+                                                         "ClipFillRule");
+        let clip_tile_index_attr = device.get_vertex_attr(&alpha_tile_program.program,
+                                                          "ClipTileIndex");
+        let clip_backdrop_attr = device.get_vertex_attr(&alpha_tile_program.program,
+                                                        "ClipBackdrop");
 
         // NB: The object must be of type `I16`, not `U16`, to work around a macOS Radeon
         // driver bug.
@@ -1122,15 +1338,47 @@ where
             class: VertexAttrClass::Int,
             attr_type: VertexAttrType::I16,
             stride: MASK_TILE_INSTANCE_SIZE,
-            offset: 6,
+            offset: 8,
             divisor: 1,
         });
         device.configure_vertex_attr(&color_tex_coord_attr, &VertexAttrDescriptor {
             size: 2,
-            class: VertexAttrClass::FloatNorm,
+            class: VertexAttrClass::Float,
             attr_type: VertexAttrType::U16,
             stride: MASK_TILE_INSTANCE_SIZE,
-            offset: 8,
+            offset: 10,
+            divisor: 1,
+        });
+        device.configure_vertex_attr(&fill_rule_attr, &VertexAttrDescriptor {
+            size: 1,
+            class: VertexAttrClass::Int,
+            attr_type: VertexAttrType::U8,
+            stride: MASK_TILE_INSTANCE_SIZE,
+            offset: 14,
+            divisor: 1,
+        });
+        device.configure_vertex_attr(&clip_fill_rule_attr, &VertexAttrDescriptor {
+            size: 1,
+            class: VertexAttrClass::Int,
+            attr_type: VertexAttrType::U8,
+            stride: MASK_TILE_INSTANCE_SIZE,
+            offset: 15,
+            divisor: 1,
+        });
+        device.configure_vertex_attr(&clip_tile_index_attr, &VertexAttrDescriptor {
+            size: 1,
+            class: VertexAttrClass::Int,
+            attr_type: VertexAttrType::U16,
+            stride: MASK_TILE_INSTANCE_SIZE,
+            offset: 16,
+            divisor: 1,
+        });
+        device.configure_vertex_attr(&clip_backdrop_attr, &VertexAttrDescriptor {
+            size: 1,
+            class: VertexAttrClass::Int,
+            attr_type: VertexAttrType::I8,
+            stride: MASK_TILE_INSTANCE_SIZE,
+            offset: 18,
             divisor: 1,
         });
 
@@ -1196,7 +1444,7 @@ where
         });
         device.configure_vertex_attr(&color_tex_coord_attr, &VertexAttrDescriptor {
             size: 2,
-            class: VertexAttrClass::FloatNorm,
+            class: VertexAttrClass::Float,
             attr_type: VertexAttrType::U16,
             stride: SOLID_TILE_INSTANCE_SIZE,
             offset: 4,
@@ -1275,6 +1523,7 @@ where
     solid_tile_program: SolidTileProgram<D>,
     paint_texture_uniform: D::Uniform,
     paint_texture_size_uniform: D::Uniform,
+    paint_atlas_uniform: D::Uniform,
 }
 
 impl<D> SolidTileMulticolorProgram<D>
@@ -1287,10 +1536,12 @@ where
             device.get_uniform(&solid_tile_program.program, "PaintTexture");
         let paint_texture_size_uniform =
             device.get_uniform(&solid_tile_program.program, "PaintTextureSize");
+        let paint_atlas_uniform = device.get_uniform(&solid_tile_program.program, "PaintAtlas");
         SolidTileMulticolorProgram {
             solid_tile_program,
             paint_texture_uniform,
             paint_texture_size_uniform,
+            paint_atlas_uniform,
         }
     }
 }
@@ -1363,6 +1614,7 @@ where
     alpha_tile_program: AlphaTileProgram<D>,
     paint_texture_uniform: D::Uniform,
     paint_texture_size_uniform: D::Uniform,
+    paint_atlas_uniform: D::Uniform,
 }
 
 impl<D> AlphaTileMulticolorProgram<D>
@@ -1375,10 +1627,12 @@ where
             device.get_uniform(&alpha_tile_program.program, "PaintTexture");
         let paint_texture_size_uniform =
             device.get_uniform(&alpha_tile_program.program, "PaintTextureSize");
+        let paint_atlas_uniform = device.get_uniform(&alpha_tile_program.program, "PaintAtlas");
         AlphaTileMulticolorProgram {
             alpha_tile_program,
             paint_texture_uniform,
             paint_texture_size_uniform,
+            paint_atlas_uniform,
         }
     }
 }
@@ -1484,6 +1738,72 @@ where
     }
 }
 
+struct CompositeProgram<D>
+where
+    D: Device,
+{
+    program: D::Program,
+    source_uniform: D::Uniform,
+    backdrop_uniform: D::Uniform,
+    opacity_uniform: D::Uniform,
+    blend_mode_uniform: D::Uniform,
+}
+
+impl<D> CompositeProgram<D>
+where
+    D: Device,
+{
+    fn new(device: &D, resources: &dyn ResourceLoader) -> CompositeProgram<D> {
+        let program = device.create_program(resources, "composite");
+        let source_uniform = device.get_uniform(&program, "Source");
+        let backdrop_uniform = device.get_uniform(&program, "Backdrop");
+        let opacity_uniform = device.get_uniform(&program, "Opacity");
+        let blend_mode_uniform = device.get_uniform(&program, "BlendMode");
+        CompositeProgram {
+            program,
+            source_uniform,
+            backdrop_uniform,
+            opacity_uniform,
+            blend_mode_uniform,
+        }
+    }
+}
+
+struct CompositeVertexArray<D>
+where
+    D: Device,
+{
+    vertex_array: D::VertexArray,
+}
+
+impl<D> CompositeVertexArray<D>
+where
+    D: Device,
+{
+    fn new(
+        device: &D,
+        composite_program: &CompositeProgram<D>,
+        quad_vertex_positions_buffer: &D::Buffer,
+    ) -> CompositeVertexArray<D> {
+        let vertex_array = device.create_vertex_array();
+        let position_attr = device.get_vertex_attr(&composite_program.program, "Position");
+
+        device.bind_vertex_array(&vertex_array);
+        device.use_program(&composite_program.program);
+        device.bind_buffer(quad_vertex_positions_buffer, BufferTarget::Vertex);
+        device.configure_vertex_attr(&position_attr, &VertexAttrDescriptor {
+            size: 2,
+            class: VertexAttrClass::Float,
+            attr_type: VertexAttrType::U8,
+            stride: 0,
+            offset: 0,
+            divisor: 0,
+        });
+
+        CompositeVertexArray { vertex_array }
+    }
+}
+
 struct StencilProgram<D>
 where
     D: Device,
@@ -1649,6 +1969,31 @@ impl Default for RenderMode {
     }
 }
 
+/// Why a scene couldn't be drawn in full on this device, as reported by `end_scene()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderError {
+    /// The paint data or the pattern atlas is larger than the device's maximum texture size.
+    PaintDataTooLarge,
+    /// The scene uses more alpha tiles than the mask framebuffer holds at the device's maximum
+    /// texture size. Use smaller tiles or a smaller view box.
+    TooManyAlphaTiles,
+}
+
+impl Display for RenderError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            RenderError::PaintDataTooLarge => {
+                write!(formatter, "paint data exceeds the maximum texture size")
+            }
+            RenderError::TooManyAlphaTiles => {
+                write!(formatter, "too many alpha tiles for the mask framebuffer")
+            }
+        }
+    }
+}
+
+impl Error for RenderError {}
+
 #[derive(Clone, Copy, Debug, Default)]
 pub struct RenderStats {
     pub path_count: usize,
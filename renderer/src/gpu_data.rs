@@ -10,24 +10,30 @@
 
 //! Packed data ready to be sent to the GPU.
 
+use crate::blend::BlendMode;
 use crate::options::BoundingQuad;
 use crate::tile_map::DenseTileMap;
-use pathfinder_geometry::basic::line_segment::{LineSegmentU4, LineSegmentU8};
+use pathfinder_geometry::basic::line_segment::LineSegmentU8;
 use pathfinder_geometry::basic::point::Point2DI32;
 use pathfinder_geometry::basic::rect::RectF32;
+use pathfinder_geometry::fill::FillRule;
 use std::fmt::{Debug, Formatter, Result as DebugResult};
 use std::time::Duration;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct BuiltObject {
     pub bounds: RectF32,
+    pub fill_rule: FillRule,
     pub fills: Vec<FillBatchPrimitive>,
     pub alpha_tiles: Vec<AlphaTileBatchPrimitive>,
     pub tiles: DenseTileMap<TileObjectPrimitive>,
+    pub alpha_tile_count: u32,
+    pub tile_size: Point2DI32,
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum RenderCommand {
-    Start { path_count: usize, bounding_quad: BoundingQuad },
+    Start { path_count: usize, bounding_quad: BoundingQuad, tile_size: Point2DI32 },
     AddPaintData(PaintData),
     AddFills(Vec<FillBatchPrimitive>),
     FlushFills,
@@ -35,18 +41,24 @@ pub enum RenderCommand {
     FlushAlphaTiles,
     AddSolidTiles(Vec<SolidTileBatchPrimitive>),
     FlushSolidTiles,
+    /// Redirects subsequent tiles to a fresh, transparent intermediate target.
+    PushGroup,
+    /// Composites the innermost intermediate target onto the one below it.
+    PopGroup { opacity: f32, blend_mode: BlendMode },
     Finish { build_time: Duration },
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PaintData {
     pub size: Point2DI32,
     pub texels: Vec<u8>,
+    pub atlas_size: Point2DI32,
+    pub atlas_texels: Vec<u8>,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct FillObjectPrimitive {
-    pub px: LineSegmentU4,
+    pub px: LineSegmentU8,
     pub subpx: LineSegmentU8,
     pub tile_x: i16,
     pub tile_y: i16,
@@ -61,35 +73,43 @@ pub struct TileObjectPrimitive {
 }
 
 // FIXME(pcwalton): Move `subpx` before `px` and remove `repr(packed)`.
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
 #[repr(packed)]
 pub struct FillBatchPrimitive {
-    pub px: LineSegmentU4,
+    pub px: LineSegmentU8,
     pub subpx: LineSegmentU8,
     pub alpha_tile_index: u16,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 #[repr(C)]
 pub struct SolidTileBatchPrimitive {
     pub tile_x: i16,
     pub tile_y: i16,
     pub origin_u: u16,
     pub origin_v: u16,
-    pub object_index: u16,
+    pub object_index: u32,
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
 #[repr(C)]
 pub struct AlphaTileBatchPrimitive {
     pub tile_x_lo: u8,
     pub tile_y_lo: u8,
     pub tile_hi: u8,
     pub backdrop: i8,
-    pub object_index: u16,
+    pub object_index: u32,
     pub tile_index: u16,
     pub origin_u: u16,
     pub origin_v: u16,
+    /// 0 for the nonzero winding rule, 1 for even-odd.
+    pub fill_rule: u8,
+    /// The fill rule of the clip path, if any.
+    pub clip_fill_rule: u8,
+    /// The alpha tile of the clip path's mask, or `u16::MAX` if the clip path has no mask here.
+    /// Unclipped tiles use no mask and a backdrop of 1.
+    pub clip_tile_index: u16,
+    pub clip_backdrop: i8,
 }
 
 impl Debug for RenderCommand {
@@ -109,6 +129,10 @@ impl Debug for RenderCommand {
                 write!(formatter, "AddSolidTiles(x{})", tiles.len())
             }
             RenderCommand::FlushSolidTiles => write!(formatter, "FlushSolidTiles"),
+            RenderCommand::PushGroup => write!(formatter, "PushGroup"),
+            RenderCommand::PopGroup { opacity, blend_mode } => {
+                write!(formatter, "PopGroup({}, {:?})", opacity, blend_mode)
+            }
             RenderCommand::Finish { .. } => write!(formatter, "Finish"),
         }
     }
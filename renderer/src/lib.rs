@@ -12,17 +12,26 @@
 
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde_derive;
 
+pub mod blend;
+pub mod capture;
 pub mod concurrent;
+pub mod cpu;
 pub mod gpu;
 pub mod gpu_data;
 pub mod options;
 pub mod paint;
+pub mod pdf;
 pub mod post;
 pub mod scene;
+pub mod tile_cache;
 
 mod builder;
 mod sorted_vector;
+#[cfg(test)]
+mod test_util;
 mod tile_map;
 mod tiles;
 mod z_buffer;
@@ -11,7 +11,10 @@
 //! Options that control how rendering is to be performed.
 
 use crate::gpu_data::RenderCommand;
-use pathfinder_geometry::basic::point::{Point2DF32, Point3DF32};
+use crate::scene::BuildError;
+use crate::tiles::{DEFAULT_FLATTENING_TOLERANCE, DEFAULT_TILE_HEIGHT, DEFAULT_TILE_WIDTH};
+use crate::tiles::MAX_TILE_SIZE;
+use pathfinder_geometry::basic::point::{Point2DF32, Point2DI32, Point3DF32};
 use pathfinder_geometry::basic::rect::RectF32;
 use pathfinder_geometry::basic::transform2d::Transform2DF32;
 use pathfinder_geometry::basic::transform3d::Perspective;
@@ -31,20 +34,51 @@ where
     }
 }
 
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct RenderOptions {
     pub transform: RenderTransform,
     pub dilation: Point2DF32,
     pub subpixel_aa_enabled: bool,
+    /// The size of the tiles that paths are cut into, in device pixels. Each side must be
+    /// between 1 and `MAX_TILE_SIZE`, or building fails with `BuildError::InvalidTileSize`. The
+    /// renderer picks this up from the `Start` command.
+    pub tile_size: Point2DI32,
+    /// How far, in device pixels, curves may stray from the lines they're flattened into. Must be
+    /// positive, or building fails with `BuildError::InvalidFlatteningTolerance`.
+    pub flattening_tolerance: f32,
+}
+
+impl Default for RenderOptions {
+    #[inline]
+    fn default() -> RenderOptions {
+        RenderOptions {
+            transform: RenderTransform::default(),
+            dilation: Point2DF32::default(),
+            subpixel_aa_enabled: false,
+            tile_size: Point2DI32::new(DEFAULT_TILE_WIDTH as i32, DEFAULT_TILE_HEIGHT as i32),
+            flattening_tolerance: DEFAULT_FLATTENING_TOLERANCE,
+        }
+    }
 }
 
 impl RenderOptions {
-    pub(crate) fn prepare(self, bounds: RectF32) -> PreparedRenderOptions {
-        PreparedRenderOptions {
+    pub(crate) fn prepare(self, bounds: RectF32) -> Result<PreparedRenderOptions, BuildError> {
+        let tile_size_range = 1..=(MAX_TILE_SIZE as i32);
+        if !tile_size_range.contains(&self.tile_size.x()) ||
+                !tile_size_range.contains(&self.tile_size.y()) {
+            return Err(BuildError::InvalidTileSize);
+        }
+        if self.flattening_tolerance.is_nan() || self.flattening_tolerance <= 0.0 {
+            return Err(BuildError::InvalidFlatteningTolerance);
+        }
+
+        Ok(PreparedRenderOptions {
             transform: self.transform.prepare(bounds),
             dilation: self.dilation,
             subpixel_aa_enabled: self.subpixel_aa_enabled,
-        }
+            tile_size: self.tile_size,
+            flattening_tolerance: self.flattening_tolerance,
+        })
     }
 }
 
@@ -62,7 +96,7 @@ impl Default for RenderTransform {
 }
 
 impl RenderTransform {
-    fn prepare(&self, bounds: RectF32) -> PreparedRenderTransform {
+    pub(crate) fn prepare(&self, bounds: RectF32) -> PreparedRenderTransform {
         let perspective = match self {
             RenderTransform::Transform2D(ref transform) => {
                 if transform.is_identity() {
@@ -123,9 +157,27 @@ pub(crate) struct PreparedRenderOptions {
     pub(crate) transform: PreparedRenderTransform,
     pub(crate) dilation: Point2DF32,
     pub(crate) subpixel_aa_enabled: bool,
+    pub(crate) tile_size: Point2DI32,
+    pub(crate) flattening_tolerance: f32,
 }
 
 impl PreparedRenderOptions {
+    /// Returns the transform from scene space to tile space, for placing paints.
+    ///
+    /// Paints aren't projected under a perspective transform; they're placed untransformed.
+    pub(crate) fn transform_2d(&self) -> Transform2DF32 {
+        let transform = match self.transform {
+            PreparedRenderTransform::Transform2D(transform) => transform,
+            PreparedRenderTransform::None => Transform2DF32::default(),
+            PreparedRenderTransform::Perspective { .. } => return Transform2DF32::default(),
+        };
+        if self.subpixel_aa_enabled {
+            transform.post_mul(&Transform2DF32::from_scale(Point2DF32::new(3.0, 1.0)))
+        } else {
+            transform
+        }
+    }
+
     #[inline]
     pub(crate) fn bounding_quad(&self) -> BoundingQuad {
         match self.transform {
@@ -146,13 +198,3 @@ pub(crate) enum PreparedRenderTransform {
         quad: [Point3DF32; 4],
     },
 }
-
-impl PreparedRenderTransform {
-    #[inline]
-    pub(crate) fn is_2d(&self) -> bool {
-        match *self {
-            PreparedRenderTransform::Transform2D(_) => true,
-            _ => false,
-        }
-    }
-}
@@ -9,43 +9,763 @@
 // except according to those terms.
 
 use crate::gpu_data::PaintData;
-use crate::scene::Scene;
-use pathfinder_geometry::basic::point::Point2DI32;
+use crate::scene::{BuildError, Scene};
+use pathfinder_geometry::basic::line_segment::LineSegmentF32;
+use pathfinder_geometry::basic::point::{Point2DF32, Point2DI32};
+use pathfinder_geometry::basic::transform2d::Transform2DF32;
 use pathfinder_geometry::color::ColorU;
+use pathfinder_geometry::util;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
 const PAINT_TEXTURE_WIDTH: i32 = 256;
-const PAINT_TEXTURE_HEIGHT: i32 = 256;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-pub struct Paint {
+// Paint records store their data rows, and tiles the rows of their records, in 16 bits.
+const MAX_PAINT_TEXTURE_HEIGHT: i32 = 65536;
+
+// Pattern images are packed into rows of an atlas at least this wide.
+const MIN_ATLAS_WIDTH: i32 = 1024;
+
+// Each paint is described by a header texel followed by a color texel.
+const TEXELS_PER_PAINT: i32 = 2;
+
+// Keeps the focus strictly inside the circle so that the radial gradient equation stays solvable.
+const MAX_FOCUS_DISTANCE: f32 = 0.99;
+
+// These must match the values in `tile_multicolor.inc.glsl` and `tile_paint.inc.glsl`.
+pub(crate) const PAINT_KIND_COLOR: u8 = 0;
+pub(crate) const PAINT_KIND_LINEAR_GRADIENT: u8 = 1;
+pub(crate) const PAINT_KIND_RADIAL_GRADIENT: u8 = 2;
+pub(crate) const PAINT_KIND_PATTERN: u8 = 3;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Paint {
+    Color(ColorU),
+    Gradient(Gradient),
+    Pattern(Pattern),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PaintId(pub u32);
+
+/// A linear or radial gradient with any number of color stops.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Gradient {
+    geometry: GradientGeometry,
+    stops: Vec<ColorStop>,
+    spread: SpreadMethod,
+    transform: Transform2DF32,
+}
+
+/// The shape of a gradient, in gradient space.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GradientGeometry {
+    /// Offset 0 is at the start of the line and offset 1 is at its end.
+    Linear(LineSegmentF32),
+    /// Offset 0 is at the focus and offset 1 is on the circle.
+    Radial { center: Point2DF32, radius: f32, focus: Point2DF32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColorStop {
+    pub offset: f32,
     pub color: ColorU,
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-pub struct PaintId(pub u16);
+/// How a gradient is extended outside the [0, 1] offset range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SpreadMethod {
+    Pad = 0,
+    Reflect = 1,
+    Repeat = 2,
+}
+
+/// An RGBA8 raster image, with rows stored top to bottom.
+///
+/// Cloning an image is cheap, and paints that share an image share its space in the atlas.
+#[derive(Clone, Debug)]
+pub struct Image {
+    size: Point2DI32,
+    pixels: Arc<Vec<u8>>,
+    is_opaque: bool,
+}
+
+/// An image, optionally repeated, used as a paint.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Pattern {
+    image: Image,
+    transform: Transform2DF32,
+    repeat_mode: RepeatMode,
+    opacity: f32,
+}
+
+/// The directions in which a pattern's image is repeated. Outside the image in the other
+/// directions, the pattern is transparent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RepeatMode {
+    NoRepeat = 0,
+    RepeatX = 1,
+    RepeatY = 2,
+    Repeat = 3,
+}
 
 impl Paint {
     pub(crate) fn is_opaque(&self) -> bool {
-        self.color.a == 255
+        match *self {
+            Paint::Color(color) => color.a == 255,
+            Paint::Gradient(ref gradient) => gradient.is_opaque(),
+            Paint::Pattern(ref pattern) => pattern.is_opaque(),
+        }
+    }
+
+    // Checks the invariants that the constructors maintain but deserialization can't, namely that
+    // gradient stops lie in [0, 1] and are sorted. Images check their own size when deserialized.
+    pub(crate) fn is_valid(&self) -> bool {
+        match *self {
+            Paint::Color(_) | Paint::Pattern(_) => true,
+            Paint::Gradient(ref gradient) => {
+                gradient.stops.iter().all(|stop| (0.0..=1.0).contains(&stop.offset)) &&
+                    gradient.stops.windows(2).all(|stops| stops[0].offset <= stops[1].offset)
+            }
+        }
+    }
+
+    /// Multiplies the alpha of this paint by `opacity`.
+    pub fn apply_opacity(&mut self, opacity: f32) {
+        match *self {
+            Paint::Color(ref mut color) => *color = scale_alpha(*color, opacity),
+            Paint::Gradient(ref mut gradient) => {
+                for stop in &mut gradient.stops {
+                    stop.color = scale_alpha(stop.color, opacity);
+                }
+            }
+            Paint::Pattern(ref mut pattern) => pattern.opacity *= opacity,
+        }
+    }
+
+    /// Moves the paint along with the transform of the path it's applied to. Solid colors are
+    /// unaffected.
+    pub fn apply_transform(&mut self, transform: &Transform2DF32) {
+        match *self {
+            Paint::Color(_) => {}
+            Paint::Gradient(ref mut gradient) => {
+                gradient.transform = gradient.transform.post_mul(transform)
+            }
+            Paint::Pattern(ref mut pattern) => {
+                pattern.transform = pattern.transform.post_mul(transform)
+            }
+        }
+    }
+}
+
+impl Eq for Paint {}
+
+impl Eq for Gradient {}
+
+impl Eq for Pattern {}
+
+impl Hash for Paint {
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        match *self {
+            Paint::Color(color) => {
+                0u8.hash(state);
+                color.hash(state);
+            }
+            Paint::Gradient(ref gradient) => {
+                1u8.hash(state);
+                gradient.hash(state);
+            }
+            Paint::Pattern(ref pattern) => {
+                2u8.hash(state);
+                pattern.hash(state);
+            }
+        }
+    }
+}
+
+impl Gradient {
+    #[inline]
+    pub fn new(geometry: GradientGeometry) -> Gradient {
+        Gradient {
+            geometry,
+            stops: vec![],
+            spread: SpreadMethod::Pad,
+            transform: Transform2DF32::default(),
+        }
+    }
+
+    #[inline]
+    pub fn linear(line: LineSegmentF32) -> Gradient {
+        Gradient::new(GradientGeometry::Linear(line))
+    }
+
+    #[inline]
+    pub fn radial(center: Point2DF32, radius: f32) -> Gradient {
+        Gradient::new(GradientGeometry::Radial { center, radius, focus: center })
+    }
+
+    /// Adds a color stop. Stops at the same offset are kept in the order they were added, which
+    /// allows for hard transitions.
+    pub fn add_color_stop(&mut self, offset: f32, color: ColorU) {
+        let offset = util::clamp(offset, 0.0, 1.0);
+        let index = self.stops.iter().position(|stop| stop.offset > offset);
+        let index = index.unwrap_or(self.stops.len());
+        self.stops.insert(index, ColorStop { offset, color });
+    }
+
+    #[inline]
+    pub fn geometry(&self) -> &GradientGeometry {
+        &self.geometry
+    }
+
+    #[inline]
+    pub fn stops(&self) -> &[ColorStop] {
+        &self.stops
+    }
+
+    #[inline]
+    pub fn spread(&self) -> SpreadMethod {
+        self.spread
+    }
+
+    #[inline]
+    pub fn set_spread(&mut self, new_spread: SpreadMethod) {
+        self.spread = new_spread
+    }
+
+    /// The transform from gradient space to scene space.
+    #[inline]
+    pub fn transform(&self) -> Transform2DF32 {
+        self.transform
+    }
+
+    #[inline]
+    pub fn set_transform(&mut self, new_transform: Transform2DF32) {
+        self.transform = new_transform
+    }
+
+    /// Returns the color at the given offset, which is clamped to [0, 1].
+    pub fn sample(&self, offset: f32) -> ColorU {
+        let (first_stop, last_stop) = match (self.stops.first(), self.stops.last()) {
+            (Some(first_stop), Some(last_stop)) => (first_stop, last_stop),
+            _ => return ColorU::transparent_black(),
+        };
+        if offset <= first_stop.offset {
+            return first_stop.color;
+        }
+
+        let next_index = match self.stops.iter().position(|stop| stop.offset > offset) {
+            None => return last_stop.color,
+            Some(next_index) => next_index,
+        };
+        let (prev_stop, next_stop) = (&self.stops[next_index - 1], &self.stops[next_index]);
+        let t = (offset - prev_stop.offset) / (next_stop.offset - prev_stop.offset);
+        prev_stop.color.to_f32().lerp(next_stop.color.to_f32(), t).to_u8()
+    }
+
+    fn is_opaque(&self) -> bool {
+        !self.stops.is_empty() && self.stops.iter().all(|stop| stop.color.a == 255)
+    }
+
+    // Returns the transform from gradient space to a normalized space, in which linear gradients
+    // run from (0, 0) to (1, 0) and radial gradients are unit circles centered on the origin,
+    // together with the focus in that space. Returns `None` if the gradient covers no area, in
+    // which case it is drawn with the color of its last stop as SVG specifies.
+    pub(crate) fn normalizing_transform(&self) -> Option<(Transform2DF32, Point2DF32)> {
+        match self.geometry {
+            GradientGeometry::Linear(line) => {
+                let vector = line.to() - line.from();
+                let length_squared = vector.square_length();
+                if length_squared == 0.0 {
+                    return None;
+                }
+                let (x, y) = (vector.x() / length_squared, vector.y() / length_squared);
+                let transform = Transform2DF32::from_translation(-line.from())
+                    .post_mul(&Transform2DF32::row_major(x, y, -y, x, 0.0, 0.0));
+                Some((transform, Point2DF32::default()))
+            }
+            GradientGeometry::Radial { center, radius, focus } => {
+                if radius <= 0.0 {
+                    return None;
+                }
+                let scale = Point2DF32::splat(1.0 / radius);
+                let transform = Transform2DF32::from_translation(-center)
+                    .post_mul(&Transform2DF32::from_scale(scale));
+                let mut focus = transform.transform_point(focus);
+                let focus_distance = focus.length();
+                if focus_distance > MAX_FOCUS_DISTANCE {
+                    focus = focus.scale(MAX_FOCUS_DISTANCE / focus_distance);
+                }
+                Some((transform, focus))
+            }
+        }
+    }
+}
+
+impl Gradient {
+    // Returns the kind of geometry and the floats of the geometry and transform, which equality
+    // and hashing both go through.
+    fn float_key(&self) -> (u8, [f32; 11]) {
+        let (kind, geometry) = match self.geometry {
+            GradientGeometry::Linear(line) => {
+                (0, [line.from_x(), line.from_y(), line.to_x(), line.to_y(), 0.0])
+            }
+            GradientGeometry::Radial { center, radius, focus } => {
+                (1, [center.x(), center.y(), radius, focus.x(), focus.y()])
+            }
+        };
+        let mut key = [0.0; 11];
+        key[0..5].copy_from_slice(&geometry);
+        key[5..11].copy_from_slice(&transform_f32s(&self.transform));
+        (kind, key)
+    }
+}
+
+impl PartialEq for Gradient {
+    fn eq(&self, other: &Gradient) -> bool {
+        let ((kind, key), (other_kind, other_key)) = (self.float_key(), other.float_key());
+        kind == other_kind && f32s_eq(&key, &other_key) && self.spread == other.spread &&
+            self.stops.len() == other.stops.len() &&
+            self.stops.iter().zip(other.stops.iter()).all(|(stop, other_stop)| {
+                f32s_eq(&[stop.offset], &[other_stop.offset]) && stop.color == other_stop.color
+            })
+    }
+}
+
+impl Hash for Gradient {
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        let (kind, key) = self.float_key();
+        kind.hash(state);
+        hash_f32s(&key, state);
+        for stop in &self.stops {
+            hash_f32s(&[stop.offset], state);
+            stop.color.hash(state);
+        }
+        self.spread.hash(state);
+    }
+}
+
+impl Image {
+    /// Creates an image from RGBA8 pixels, which must number `size.x() * size.y()`.
+    pub fn new(size: Point2DI32, pixels: Vec<u8>) -> Image {
+        assert!(size.x() >= 0 && size.y() >= 0);
+        assert_eq!(pixels.len(), size.x() as usize * size.y() as usize * 4);
+        let is_opaque = pixels.chunks(4).all(|pixel| pixel[3] == 255);
+        Image { size, pixels: Arc::new(pixels), is_opaque }
+    }
+
+    #[inline]
+    pub fn size(&self) -> Point2DI32 {
+        self.size
+    }
+
+    #[inline]
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    #[inline]
+    pub fn is_opaque(&self) -> bool {
+        self.is_opaque
+    }
+}
+
+impl Serialize for Image {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        (self.size, &*self.pixels).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Image {
+    fn deserialize<D>(deserializer: D) -> Result<Image, D::Error> where D: Deserializer<'de> {
+        let (size, pixels): (Point2DI32, Vec<u8>) = Deserialize::deserialize(deserializer)?;
+        if size.x() < 0 || size.y() < 0 ||
+                pixels.len() != size.x() as usize * size.y() as usize * 4 {
+            return Err(D::Error::custom("image size doesn't match its pixels"));
+        }
+        Ok(Image::new(size, pixels))
+    }
+}
+
+impl PartialEq for Image {
+    fn eq(&self, other: &Image) -> bool {
+        self.size == other.size &&
+            (Arc::ptr_eq(&self.pixels, &other.pixels) || self.pixels == other.pixels)
+    }
+}
+
+impl Eq for Image {}
+
+impl Hash for Image {
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        self.size.x().hash(state);
+        self.size.y().hash(state);
+        self.pixels.hash(state);
+    }
+}
+
+impl Pattern {
+    /// Creates a pattern that draws one image pixel per unit of the space it is used in.
+    #[inline]
+    pub fn new(image: Image, repeat_mode: RepeatMode) -> Pattern {
+        Pattern { image, transform: Transform2DF32::default(), repeat_mode, opacity: 1.0 }
+    }
+
+    #[inline]
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    #[inline]
+    pub fn repeat_mode(&self) -> RepeatMode {
+        self.repeat_mode
+    }
+
+    #[inline]
+    pub fn set_repeat_mode(&mut self, new_repeat_mode: RepeatMode) {
+        self.repeat_mode = new_repeat_mode
+    }
+
+    /// The transform from image space, in which the image covers (0, 0) to its size in pixels,
+    /// to scene space.
+    #[inline]
+    pub fn transform(&self) -> Transform2DF32 {
+        self.transform
+    }
+
+    #[inline]
+    pub fn set_transform(&mut self, new_transform: Transform2DF32) {
+        self.transform = new_transform
+    }
+
+    #[inline]
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    fn is_opaque(&self) -> bool {
+        self.image.is_opaque() && self.repeat_mode == RepeatMode::Repeat && self.opacity == 1.0
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Pattern) -> bool {
+        self.image == other.image &&
+            f32s_eq(&transform_f32s(&self.transform), &transform_f32s(&other.transform)) &&
+            f32s_eq(&[self.opacity], &[other.opacity]) &&
+            self.repeat_mode == other.repeat_mode
+    }
+}
+
+impl Hash for Pattern {
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        self.image.hash(state);
+        hash_f32s(&transform_f32s(&self.transform), state);
+        hash_f32s(&[self.opacity], state);
+        self.repeat_mode.hash(state);
+    }
+}
+
+impl RepeatMode {
+    #[inline]
+    pub fn repeats_x(self) -> bool {
+        self == RepeatMode::RepeatX || self == RepeatMode::Repeat
+    }
+
+    #[inline]
+    pub fn repeats_y(self) -> bool {
+        self == RepeatMode::RepeatY || self == RepeatMode::Repeat
     }
 }
 
 impl Scene {
-    pub fn build_paint_data(&self) -> PaintData {
-        let size = Point2DI32::new(PAINT_TEXTURE_WIDTH, PAINT_TEXTURE_HEIGHT);
+    /// Packs the paints into a texture for the tile shaders, and the images of patterns into an
+    /// atlas.
+    ///
+    /// Each paint has a two-texel record at `paint_id_to_tex_coords()`. The first texel holds
+    /// the paint kind, the spread method or repeat mode, and the 16-bit row of the paint's data;
+    /// the second holds its color. Data rows follow the records, stored as little-endian `f32`s.
+    /// Each gradient gets two rows: the first stores the transform from tile space to normalized
+    /// gradient space followed by the focus, and the second stores the color ramp. Each pattern
+    /// gets one row, storing the transform from tile space to image space, the origin and size
+    /// of its image in the atlas, and its opacity.
+    ///
+    /// `transform` is the transform from scene space to tile space.
+    ///
+    /// Fails if the paints need more than 65,536 rows, which takes at least 32,768 gradients or
+    /// over eight million solid colors.
+    pub fn build_paint_data(&self, transform: &Transform2DF32)
+                            -> Result<PaintData, BuildError> {
+        let record_texel_count = self.paints.len() as i32 * TEXELS_PER_PAINT;
+        let record_rows = (record_texel_count + PAINT_TEXTURE_WIDTH - 1) / PAINT_TEXTURE_WIDTH;
+        let data_rows: i32 = self.paints.iter().map(|paint| {
+            match *paint {
+                Paint::Color(_) => 0,
+                Paint::Gradient(ref gradient) if gradient.normalizing_transform().is_some() => 2,
+                Paint::Gradient(_) => 0,
+                Paint::Pattern(_) => 1,
+            }
+        }).sum();
+
+        if record_rows + data_rows > MAX_PAINT_TEXTURE_HEIGHT {
+            return Err(BuildError::TooManyPaints);
+        }
+
+        let size = Point2DI32::new(PAINT_TEXTURE_WIDTH, i32::max(record_rows + data_rows, 1));
         let mut texels = vec![0; size.x() as usize * size.y() as usize * 4];
+        let inverse_transform = transform.inverse();
+        let mut next_data_row = record_rows;
+        let mut atlas = AtlasBuilder::new(&self.paints);
+
         for (paint_index, paint) in self.paints.iter().enumerate() {
-            texels[paint_index * 4 + 0] = paint.color.r;
-            texels[paint_index * 4 + 1] = paint.color.g;
-            texels[paint_index * 4 + 2] = paint.color.b;
-            texels[paint_index * 4 + 3] = paint.color.a;
+            let record_offset = texel_offset(paint_id_to_tex_coords(PaintId(paint_index as u32)));
+            let gradient = match *paint {
+                Paint::Color(color) => {
+                    put_color(&mut texels, record_offset + 4, color);
+                    continue;
+                }
+                Paint::Pattern(ref pattern) => {
+                    let row = next_data_row;
+                    next_data_row += 1;
+                    put_record(&mut texels,
+                               record_offset,
+                               PAINT_KIND_PATTERN,
+                               pattern.repeat_mode as u8,
+                               row);
+
+                    let image_origin = atlas.allocate(&pattern.image);
+                    let image_size = pattern.image.size();
+                    let tile_to_image = inverse_transform.post_mul(&pattern.transform.inverse());
+                    put_f32s(&mut texels, row, &[
+                        tile_to_image.m11(),
+                        tile_to_image.m21(),
+                        tile_to_image.m12(),
+                        tile_to_image.m22(),
+                        tile_to_image.translation().x(),
+                        tile_to_image.translation().y(),
+                        image_origin.x() as f32,
+                        image_origin.y() as f32,
+                        image_size.x() as f32,
+                        image_size.y() as f32,
+                        pattern.opacity,
+                    ]);
+                    continue;
+                }
+                Paint::Gradient(ref gradient) => gradient,
+            };
+
+            let (normalizing_transform, focus) = match gradient.normalizing_transform() {
+                Some(normalizing_transform) => normalizing_transform,
+                None => {
+                    let color = gradient.stops.last().map(|stop| stop.color);
+                    let color = color.unwrap_or(ColorU::transparent_black());
+                    put_color(&mut texels, record_offset + 4, color);
+                    continue;
+                }
+            };
+
+            let kind = match gradient.geometry {
+                GradientGeometry::Linear(..) => PAINT_KIND_LINEAR_GRADIENT,
+                GradientGeometry::Radial { .. } => PAINT_KIND_RADIAL_GRADIENT,
+            };
+            let row = next_data_row;
+            next_data_row += 2;
+            put_record(&mut texels, record_offset, kind, gradient.spread as u8, row);
+
+            let tile_to_gradient = inverse_transform.post_mul(&gradient.transform.inverse())
+                                                    .post_mul(&normalizing_transform);
+            put_f32s(&mut texels, row, &[
+                tile_to_gradient.m11(),
+                tile_to_gradient.m21(),
+                tile_to_gradient.m12(),
+                tile_to_gradient.m22(),
+                tile_to_gradient.translation().x(),
+                tile_to_gradient.translation().y(),
+                focus.x(),
+                focus.y(),
+            ]);
+
+            let ramp_offset = texel_offset(Point2DI32::new(0, row + 1));
+            for x in 0..PAINT_TEXTURE_WIDTH {
+                let offset = x as f32 / (PAINT_TEXTURE_WIDTH - 1) as f32;
+                put_color(&mut texels, ramp_offset + x as usize * 4, gradient.sample(offset));
+            }
         }
-        PaintData { size, texels }
+
+        let (atlas_size, atlas_texels) = atlas.finish();
+        Ok(PaintData { size, texels, atlas_size, atlas_texels })
     }
 }
 
+// Packs pattern images into rows ("shelves") of an atlas, from top to bottom. Images shared
+// between patterns are only stored once.
+struct AtlasBuilder {
+    width: i32,
+    images: Vec<(Image, Point2DI32)>,
+    shelf_origin: Point2DI32,
+    shelf_height: i32,
+}
+
+impl AtlasBuilder {
+    fn new(paints: &[Paint]) -> AtlasBuilder {
+        let widest_image = paints.iter().filter_map(|paint| {
+            match *paint {
+                Paint::Pattern(ref pattern) => Some(pattern.image.size().x()),
+                Paint::Color(_) | Paint::Gradient(_) => None,
+            }
+        }).max();
+        let width = match widest_image {
+            None => 1,
+            Some(widest_image) => i32::max(widest_image, MIN_ATLAS_WIDTH),
+        };
+        AtlasBuilder { width, images: vec![], shelf_origin: Point2DI32::default(), shelf_height: 0 }
+    }
+
+    fn allocate(&mut self, image: &Image) -> Point2DI32 {
+        if let Some(&(_, origin)) = self.images.iter().find(|(other_image, _)| {
+            Arc::ptr_eq(&image.pixels, &other_image.pixels)
+        }) {
+            return origin;
+        }
+
+        let size = image.size();
+        if self.shelf_origin.x() + size.x() > self.width {
+            self.shelf_origin = Point2DI32::new(0, self.shelf_origin.y() + self.shelf_height);
+            self.shelf_height = 0;
+        }
+        let origin = self.shelf_origin;
+        self.shelf_origin = Point2DI32::new(origin.x() + size.x(), origin.y());
+        self.shelf_height = i32::max(self.shelf_height, size.y());
+        self.images.push((image.clone(), origin));
+        origin
+    }
+
+    fn finish(self) -> (Point2DI32, Vec<u8>) {
+        let size = Point2DI32::new(self.width,
+                                   i32::max(self.shelf_origin.y() + self.shelf_height, 1));
+        let mut texels = vec![0; size.x() as usize * size.y() as usize * 4];
+        for (image, origin) in self.images {
+            let row_length = image.size().x() as usize * 4;
+            for y in 0..image.size().y() {
+                let src_offset = y as usize * row_length;
+                let dest_offset = ((origin.y() + y) * size.x() + origin.x()) as usize * 4;
+                texels[dest_offset..(dest_offset + row_length)]
+                    .copy_from_slice(&image.pixels[src_offset..(src_offset + row_length)]);
+            }
+        }
+        (size, texels)
+    }
+}
+
+/// Returns the texel coordinates of the record describing the given paint.
 pub(crate) fn paint_id_to_tex_coords(paint_id: PaintId) -> Point2DI32 {
-    let tex_coords = Point2DI32::new(paint_id.0 as i32 % PAINT_TEXTURE_WIDTH,
-                                     paint_id.0 as i32 / PAINT_TEXTURE_WIDTH);
-    tex_coords.scale(256) + Point2DI32::new(128, 128)
+    let texel_index = paint_id.0 as i32 * TEXELS_PER_PAINT;
+    Point2DI32::new(texel_index % PAINT_TEXTURE_WIDTH, texel_index / PAINT_TEXTURE_WIDTH)
+}
+
+fn texel_offset(coords: Point2DI32) -> usize {
+    (coords.y() * PAINT_TEXTURE_WIDTH + coords.x()) as usize * 4
+}
+
+fn put_record(texels: &mut [u8], offset: usize, kind: u8, mode: u8, row: i32) {
+    texels[offset..(offset + 4)].copy_from_slice(&[kind, mode, row as u8, (row >> 8) as u8]);
+}
+
+fn put_f32s(texels: &mut [u8], row: i32, values: &[f32]) {
+    let data_offset = texel_offset(Point2DI32::new(0, row));
+    for (value_index, value) in values.iter().enumerate() {
+        let value_offset = data_offset + value_index * 4;
+        texels[value_offset..(value_offset + 4)].copy_from_slice(&value.to_bits().to_le_bytes());
+    }
+}
+
+fn put_color(texels: &mut [u8], offset: usize, color: ColorU) {
+    texels[offset..(offset + 4)].copy_from_slice(&[color.r, color.g, color.b, color.a]);
+}
+
+fn scale_alpha(mut color: ColorU, opacity: f32) -> ColorU {
+    color.a = (color.a as f32 * opacity).round() as u8;
+    color
+}
+
+// Paints compare and hash their floats by bits, so that equality is reflexive, but with every
+// zero and every NaN counting as the same value.
+fn canonical_f32_bits(value: f32) -> u32 {
+    if value == 0.0 {
+        0
+    } else if value.is_nan() {
+        f32::NAN.to_bits()
+    } else {
+        value.to_bits()
+    }
+}
+
+fn f32s_eq(values: &[f32], other_values: &[f32]) -> bool {
+    values.len() == other_values.len() &&
+        values.iter().zip(other_values.iter()).all(|(&value, &other_value)| {
+            canonical_f32_bits(value) == canonical_f32_bits(other_value)
+        })
+}
+
+fn hash_f32s<H>(values: &[f32], state: &mut H) where H: Hasher {
+    for &value in values {
+        canonical_f32_bits(value).hash(state);
+    }
+}
+
+fn transform_f32s(transform: &Transform2DF32) -> [f32; 6] {
+    [
+        transform.m11(),
+        transform.m12(),
+        transform.m21(),
+        transform.m22(),
+        transform.translation().x(),
+        transform.translation().y(),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::scene::Scene;
+    use pathfinder_geometry::basic::line_segment::LineSegmentF32;
+    use pathfinder_geometry::basic::point::{Point2DF32, Point2DI32};
+    use pathfinder_geometry::basic::transform2d::Transform2DF32;
+    use pathfinder_geometry::color::ColorU;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use super::{Gradient, Image, Paint, Pattern, RepeatMode};
+
+    fn hash(paint: &Paint) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        paint.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_signed_zeros_and_nans() {
+        let gradient = |zero: f32| {
+            let line = LineSegmentF32::new(Point2DF32::new(zero, 0.0), Point2DF32::new(10.0, zero));
+            let mut gradient = Gradient::linear(line);
+            gradient.add_color_stop(zero, ColorU::black());
+            gradient.add_color_stop(1.0, ColorU::transparent_black());
+            gradient.set_transform(Transform2DF32::from_translation(Point2DF32::splat(zero)));
+            Paint::Gradient(gradient)
+        };
+        let (positive, negative) = (gradient(0.0), gradient(-0.0));
+        assert_eq!(positive, negative);
+        assert_eq!(hash(&positive), hash(&negative));
+        let mut scene = Scene::new();
+        assert_eq!(scene.push_paint(&positive), scene.push_paint(&negative));
+
+        // A paint with a NaN in it equals itself, so it's found again.
+        let image = Image::new(Point2DI32::splat(1), vec![0; 4]);
+        let mut pattern = Pattern::new(image, RepeatMode::Repeat);
+        pattern.set_transform(Transform2DF32::from_scale(Point2DF32::splat(f32::NAN)));
+        let pattern = Paint::Pattern(pattern);
+        assert_eq!(pattern, pattern.clone());
+        let pattern_id = scene.push_paint(&pattern);
+        assert_eq!(scene.push_paint(&pattern), pattern_id);
+    }
 }
@@ -0,0 +1,826 @@
+// pathfinder/renderer/src/pdf.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Exporting scenes as single-page PDF documents.
+
+use crate::blend::BlendMode;
+use crate::paint::{ColorStop, Gradient, GradientGeometry, Paint, Pattern, SpreadMethod};
+use crate::scene::{PathObject, Scene};
+use pathfinder_geometry::basic::point::Point2DF32;
+use pathfinder_geometry::basic::transform2d::Transform2DF32;
+use pathfinder_geometry::color::ColorU;
+use pathfinder_geometry::fill::FillRule;
+use pathfinder_geometry::outline::Outline;
+use pathfinder_geometry::segment::SegmentKind;
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+use std::ops::Range;
+
+// Repeating and reflecting gradients are written out one period at a time, up to this many.
+const MAX_GRADIENT_PERIODS: f32 = 256.0;
+
+/// What `Scene::write_pdf()` couldn't represent exactly.
+///
+/// PDF has no equivalent of the Porter-Duff operators other than source-over.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PdfWarnings {
+    /// The number of paths and groups left out because their blend modes only erase.
+    pub omitted: u32,
+    /// The number of paths and groups drawn with source-over instead of their blend modes, and of
+    /// repeating gradients whose repetitions were cut off.
+    pub approximated: u32,
+}
+
+impl PdfWarnings {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.omitted == 0 && self.approximated == 0
+    }
+}
+
+impl Scene {
+    /// Writes this scene as a PDF document with one page the size of the view box.
+    ///
+    /// Paths are written as vector paths, with their fill rules, clip paths, paints and blend
+    /// modes. Gradients become shadings, patterns become tiling patterns, and groups become
+    /// transparency groups. Whatever PDF can't represent is reported in the returned warnings.
+    pub fn write_pdf<W>(&self, writer: &mut W) -> io::Result<PdfWarnings> where W: Write {
+        let mut pdf_writer = PdfWriter::new(self);
+        let content = pdf_writer.write_paths(None, 0..(self.paths().len() as u32));
+        let warnings = pdf_writer.warnings;
+        writer.write_all(&pdf_writer.finish(content))?;
+        Ok(warnings)
+    }
+}
+
+// The numbers of the objects that every document has.
+const CATALOG_OBJECT: u32 = 1;
+const PAGES_OBJECT: u32 = 2;
+const PAGE_OBJECT: u32 = 3;
+const RESOURCES_OBJECT: u32 = 4;
+
+struct PdfWriter<'a> {
+    scene: &'a Scene,
+    document: PdfDocument,
+    // The transform from scene space to the default space of the page, in which PDF's y axis
+    // points up.
+    page_transform: Transform2DF32,
+    // The resources that the page and its groups share, in the order of their names.
+    graphics_states: Vec<String>,
+    patterns: Vec<u32>,
+    forms: Vec<u32>,
+    // The paints that have been written so far, by paint ID.
+    paints: Vec<Option<PdfPaint>>,
+    warnings: PdfWarnings,
+}
+
+#[derive(Clone)]
+struct PdfPaint {
+    // The operators that set the fill color, or `None` if the paint draws nothing.
+    fill: Option<String>,
+    alpha: f32,
+    soft_mask: Option<u32>,
+}
+
+impl<'a> PdfWriter<'a> {
+    fn new(scene: &'a Scene) -> PdfWriter<'a> {
+        let view_box = scene.view_box();
+        let page_transform = Transform2DF32::row_major(1.0,
+                                                       0.0,
+                                                       0.0,
+                                                       -1.0,
+                                                       -view_box.origin().x(),
+                                                       view_box.max_y());
+
+        let mut document = PdfDocument::new();
+        for _ in 0..RESOURCES_OBJECT {
+            document.reserve_object();
+        }
+
+        PdfWriter {
+            scene,
+            document,
+            page_transform,
+            graphics_states: vec![],
+            patterns: vec![],
+            forms: vec![],
+            paints: vec![None; scene.paints.len()],
+            warnings: PdfWarnings::default(),
+        }
+    }
+
+    fn finish(mut self, content: String) -> Vec<u8> {
+        let view_box = self.scene.view_box();
+
+        // The page content starts by moving into scene space, and groups inherit that.
+        let mut page_content = String::new();
+        write_pdf_matrix(&mut page_content, &self.page_transform);
+        page_content.push_str(" cm\n");
+        page_content.push_str(&content);
+        let content_object = self.document.push_stream("", page_content.as_bytes());
+
+        let mut resources = String::from("<< /ExtGState <<");
+        for (index, graphics_state) in self.graphics_states.iter().enumerate() {
+            write!(resources, " /GS{} {}", index, graphics_state).unwrap();
+        }
+        resources.push_str(" >> /Pattern <<");
+        for (index, pattern) in self.patterns.iter().enumerate() {
+            write!(resources, " /P{} {} 0 R", index, pattern).unwrap();
+        }
+        resources.push_str(" >> /XObject <<");
+        for (index, form) in self.forms.iter().enumerate() {
+            write!(resources, " /Fm{} {} 0 R", index, form).unwrap();
+        }
+        resources.push_str(" >> >>");
+
+        self.document.set_object(CATALOG_OBJECT,
+                                 format!("<< /Type /Catalog /Pages {} 0 R >>", PAGES_OBJECT));
+        self.document.set_object(PAGES_OBJECT,
+                                 format!("<< /Type /Pages /Kids [{} 0 R] /Count 1 >>",
+                                         PAGE_OBJECT));
+        self.document.set_object(PAGE_OBJECT,
+                                 format!("<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] \
+                                          /Resources {} 0 R /Contents {} 0 R >>",
+                                         PAGES_OBJECT,
+                                         view_box.size().x(),
+                                         view_box.size().y(),
+                                         RESOURCES_OBJECT,
+                                         content_object));
+        self.document.set_object(RESOURCES_OBJECT, resources);
+        self.document.finish()
+    }
+
+    // Returns the content stream for the paths in `path_range`, which belong to `group`. Groups
+    // nested in it are written as transparency groups.
+    fn write_paths(&mut self, group: Option<u32>, path_range: Range<u32>) -> String {
+        let scene = self.scene;
+        let mut children: Vec<u32> = (0..(scene.groups().len() as u32)).filter(|&child| {
+            scene.groups()[child as usize].parent().map(|parent| parent.0) == group
+        }).collect();
+        children.sort_by_key(|&child| scene.groups()[child as usize].path_range().start);
+
+        let mut content = String::new();
+        let mut path_index = path_range.start;
+        for child in children {
+            let child_range = scene.groups()[child as usize].path_range();
+            while path_index < child_range.start {
+                self.write_path(&mut content, &scene.paths()[path_index as usize]);
+                path_index += 1;
+            }
+            self.write_group(&mut content, child);
+            path_index = u32::max(path_index, child_range.end);
+        }
+        while path_index < path_range.end {
+            self.write_path(&mut content, &scene.paths()[path_index as usize]);
+            path_index += 1;
+        }
+        content
+    }
+
+    fn write_group(&mut self, content: &mut String, group_index: u32) {
+        let scene = self.scene;
+        let group = &scene.groups()[group_index as usize];
+        let blend_mode = match self.blend_mode(group.blend_mode()) {
+            None => return,
+            Some(blend_mode) => blend_mode,
+        };
+
+        let group_content = self.write_paths(Some(group_index), group.path_range());
+        let view_box = self.scene.view_box();
+        let dictionary = format!("/Type /XObject /Subtype /Form /BBox [{} {} {} {}] \
+                                  /Group << /S /Transparency /I true >> /Resources {} 0 R ",
+                                 view_box.min_x(),
+                                 view_box.min_y(),
+                                 view_box.max_x(),
+                                 view_box.max_y(),
+                                 RESOURCES_OBJECT);
+        let form = self.document.push_stream(&dictionary, group_content.as_bytes());
+        self.forms.push(form);
+
+        content.push_str("q\n");
+        self.write_graphics_state(content, group.opacity(), blend_mode, None);
+        writeln!(content, "/Fm{} Do\nQ", self.forms.len() - 1).unwrap();
+    }
+
+    fn write_path(&mut self, content: &mut String, path_object: &PathObject) {
+        let blend_mode = match self.blend_mode(path_object.blend_mode()) {
+            None => return,
+            Some(blend_mode) => blend_mode,
+        };
+        let paint = self.paint(path_object.paint().0);
+        let fill = match paint.fill {
+            None => return,
+            Some(ref fill) => fill,
+        };
+
+        content.push_str("q\n");
+        // The soft mask is in the space the graphics state is set in, which is scene space.
+        self.write_graphics_state(content, paint.alpha, blend_mode, paint.soft_mask);
+
+        if let Some(clip_path_id) = path_object.clip_path() {
+            let clip_path = &self.scene.clip_paths()[clip_path_id.0 as usize];
+            write_pdf_path(content, clip_path.outline());
+            let operator = match clip_path.fill_rule() {
+                FillRule::Winding => "W",
+                FillRule::EvenOdd => "W*",
+            };
+            writeln!(content, "{} n", operator).unwrap();
+        }
+
+        content.push_str(fill);
+
+        // The clip path is in scene coordinates, so it has to be set before this. Paints are
+        // unaffected, since pattern spaces are fixed to the page.
+        let transform = path_object.transform();
+        if !transform.is_identity() {
+            write_pdf_matrix(content, &transform);
+            content.push_str(" cm\n");
+        }
+        write_pdf_path(content, path_object.outline());
+        let operator = match path_object.fill_rule() {
+            FillRule::Winding => "f",
+            FillRule::EvenOdd => "f*",
+        };
+        writeln!(content, "{}\nQ", operator).unwrap();
+    }
+
+    fn write_graphics_state(&mut self,
+                            content: &mut String,
+                            alpha: f32,
+                            blend_mode: &'static str,
+                            soft_mask: Option<u32>) {
+        if alpha == 1.0 && blend_mode == "Normal" && soft_mask.is_none() {
+            return;
+        }
+
+        let mut graphics_state =
+            format!("<< /Type /ExtGState /ca {} /CA {} /BM /{}", alpha, alpha, blend_mode);
+        if let Some(soft_mask) = soft_mask {
+            write!(graphics_state,
+                   " /SMask << /Type /Mask /S /Luminosity /G {} 0 R >>",
+                   soft_mask).unwrap();
+        }
+        graphics_state.push_str(" >>");
+
+        let index = match self.graphics_states.iter().position(|state| *state == graphics_state) {
+            Some(index) => index,
+            None => {
+                self.graphics_states.push(graphics_state);
+                self.graphics_states.len() - 1
+            }
+        };
+        writeln!(content, "/GS{} gs", index).unwrap();
+    }
+
+    // Returns the name of the PDF blend mode for `blend_mode`, or `None` if whatever uses it
+    // should be left out.
+    fn blend_mode(&mut self, blend_mode: BlendMode) -> Option<&'static str> {
+        if blend_mode.erases() {
+            self.warnings.omitted += 1;
+            return None;
+        }
+        let pdf_blend_mode = pdf_blend_mode(blend_mode);
+        if pdf_blend_mode.is_none() {
+            self.warnings.approximated += 1;
+        }
+        Some(pdf_blend_mode.unwrap_or("Normal"))
+    }
+
+    fn paint(&mut self, paint_index: u32) -> PdfPaint {
+        if let Some(ref paint) = self.paints[paint_index as usize] {
+            return (*paint).clone();
+        }
+
+        let scene = self.scene;
+        let paint = match scene.paints[paint_index as usize] {
+            Paint::Color(color) => color_paint(color),
+            Paint::Gradient(ref gradient) => self.gradient_paint(gradient),
+            Paint::Pattern(ref pattern) => self.pattern_paint(pattern),
+        };
+        self.paints[paint_index as usize] = Some(paint.clone());
+        paint
+    }
+
+    fn gradient_paint(&mut self, gradient: &Gradient) -> PdfPaint {
+        let last_color = match gradient.stops().last() {
+            None => return PdfPaint { fill: None, alpha: 0.0, soft_mask: None },
+            Some(last_stop) => last_stop.color,
+        };
+        // Gradients that cover no area are drawn in the color of their last stop.
+        let (normalizing_transform, focus) = match gradient.normalizing_transform() {
+            None => return color_paint(last_color),
+            Some(normalized) => normalized,
+        };
+
+        // Shadings are written in the normalized space, so get from there to the page.
+        let shading_transform =
+            normalizing_transform.inverse().post_mul(&gradient.transform());
+        let range = self.gradient_range(gradient, &shading_transform, focus);
+
+        let shading = write_pdf_shading(gradient, focus, range.clone(), false);
+        let mut pattern = "<< /Type /Pattern /PatternType 2 /Shading ".to_owned();
+        pattern.push_str(&shading);
+        pattern.push_str(" /Matrix [");
+        write_pdf_matrix(&mut pattern, &shading_transform.post_mul(&self.page_transform));
+        pattern.push_str("] >>");
+        let fill = self.push_pattern(pattern);
+
+        // Shadings are opaque, so varying alpha goes into a soft mask with an alpha shading.
+        let first_alpha = gradient.stops()[0].color.a;
+        if gradient.stops().iter().all(|stop| stop.color.a == first_alpha) {
+            let alpha = first_alpha as f32 / 255.0;
+            return PdfPaint { fill: Some(fill), alpha, soft_mask: None };
+        }
+
+        let view_box = self.scene.view_box();
+        let mut mask_content = "q ".to_owned();
+        write_pdf_matrix(&mut mask_content, &shading_transform);
+        mask_content.push_str(" cm /Sh0 sh Q");
+        let dictionary = format!("/Type /XObject /Subtype /Form /BBox [{} {} {} {}] \
+                                  /Group << /S /Transparency /CS /DeviceGray >> \
+                                  /Resources << /Shading << /Sh0 {} >> >> ",
+                                 view_box.min_x(),
+                                 view_box.min_y(),
+                                 view_box.max_x(),
+                                 view_box.max_y(),
+                                 write_pdf_shading(gradient, focus, range, true));
+        let soft_mask = self.document.push_stream(&dictionary, mask_content.as_bytes());
+        PdfPaint { fill: Some(fill), alpha: 1.0, soft_mask: Some(soft_mask) }
+    }
+
+    // Returns the range of gradient offsets that the shading has to span. Padded gradients
+    // only need [0, 1], since shadings extend their ends, but repeating ones are written out
+    // over the whole page.
+    fn gradient_range(&mut self,
+                      gradient: &Gradient,
+                      shading_transform: &Transform2DF32,
+                      focus: Point2DF32)
+                      -> Range<f32> {
+        if gradient.spread() == SpreadMethod::Pad {
+            return 0.0..1.0;
+        }
+
+        let view_box = self.scene.view_box();
+        let inverse_transform = shading_transform.inverse();
+        let corners = [
+            view_box.origin(),
+            view_box.upper_right(),
+            view_box.lower_right(),
+            view_box.lower_left(),
+        ];
+        let offsets = corners.iter().map(|&corner| {
+            let point = inverse_transform.transform_point(corner);
+            match *gradient.geometry() {
+                GradientGeometry::Linear(_) => point.x(),
+                // Circles grow at least as fast as their centers move away from the focus.
+                GradientGeometry::Radial { .. } => {
+                    (point - focus).length() / (1.0 - focus.length())
+                }
+            }
+        });
+        let (mut start, mut end) = (0.0, 1.0);
+        for offset in offsets {
+            start = f32::min(start, offset.floor());
+            end = f32::max(end, offset.ceil());
+        }
+        if let GradientGeometry::Radial { .. } = *gradient.geometry() {
+            start = 0.0;
+        }
+
+        if end - start > MAX_GRADIENT_PERIODS || !(end - start).is_finite() {
+            self.warnings.approximated += 1;
+            start = f32::max(start, -MAX_GRADIENT_PERIODS * 0.5).floor();
+            end = start + MAX_GRADIENT_PERIODS;
+        }
+        start..end
+    }
+
+    fn pattern_paint(&mut self, pattern: &Pattern) -> PdfPaint {
+        let image = pattern.image();
+        let size = image.size();
+        if size.x() == 0 || size.y() == 0 {
+            return PdfPaint { fill: None, alpha: 0.0, soft_mask: None };
+        }
+
+        let mut rgb = Vec::with_capacity(image.pixels().len() / 4 * 3);
+        let mut alpha = Vec::with_capacity(image.pixels().len() / 4);
+        for pixel in image.pixels().chunks(4) {
+            rgb.extend_from_slice(&pixel[0..3]);
+            alpha.push(pixel[3]);
+        }
+        let image_dictionary = format!("/Type /XObject /Subtype /Image /Width {} /Height {} \
+                                        /BitsPerComponent 8 /Interpolate true ",
+                                       size.x(),
+                                       size.y());
+        let mut rgb_dictionary = image_dictionary.clone();
+        rgb_dictionary.push_str("/ColorSpace /DeviceRGB ");
+        if !image.is_opaque() {
+            let mut alpha_dictionary = image_dictionary;
+            alpha_dictionary.push_str("/ColorSpace /DeviceGray ");
+            let soft_mask = self.document.push_stream(&alpha_dictionary, &alpha);
+            write!(rgb_dictionary, "/SMask {} 0 R ", soft_mask).unwrap();
+        }
+        let image_object = self.document.push_stream(&rgb_dictionary, &rgb);
+
+        // A tile is one copy of the image. In directions in which the pattern doesn't repeat, the
+        // tiles are spaced far enough apart that only one of them lands on the page.
+        let size = size.to_f32();
+        let page_bounds = pattern.transform().inverse().transform_rect(&self.scene.view_box());
+        let step = |repeats, size: f32, min: f32, max: f32| {
+            if repeats {
+                size
+            } else {
+                f32::max(f32::max(max, size - min), size) + 1.0
+            }
+        };
+        let x_step = step(pattern.repeat_mode().repeats_x(),
+                          size.x(),
+                          page_bounds.min_x(),
+                          page_bounds.max_x());
+        let y_step = step(pattern.repeat_mode().repeats_y(),
+                          size.y(),
+                          page_bounds.min_y(),
+                          page_bounds.max_y());
+
+        // Image space has rows going down, but PDF images are drawn into the unit square with
+        // their first row at the top.
+        let tile_content = format!("q {} 0 0 {} 0 {} cm /Im0 Do Q",
+                                   size.x(),
+                                   -size.y(),
+                                   size.y());
+        let mut dictionary = format!("/Type /Pattern /PatternType 1 /PaintType 1 /TilingType 1 \
+                                      /BBox [0 0 {} {}] /XStep {} /YStep {} /Matrix [",
+                                     size.x(),
+                                     size.y(),
+                                     x_step,
+                                     y_step);
+        write_pdf_matrix(&mut dictionary, &pattern.transform().post_mul(&self.page_transform));
+        write!(dictionary, "] /Resources << /XObject << /Im0 {} 0 R >> >> ", image_object).unwrap();
+        let pattern_object = self.document.push_stream(&dictionary, tile_content.as_bytes());
+        self.patterns.push(pattern_object);
+
+        PdfPaint {
+            fill: Some(format!("/Pattern cs /P{} scn\n", self.patterns.len() - 1)),
+            alpha: pattern.opacity(),
+            soft_mask: None,
+        }
+    }
+
+    // Writes out a pattern object and returns the operators that fill with it.
+    fn push_pattern(&mut self, pattern: String) -> String {
+        let pattern_object = self.document.push_object(pattern.into_bytes());
+        self.patterns.push(pattern_object);
+        format!("/Pattern cs /P{} scn\n", self.patterns.len() - 1)
+    }
+}
+
+fn color_paint(color: ColorU) -> PdfPaint {
+    let mut fill = String::new();
+    write_pdf_color(&mut fill, color, false);
+    fill.push_str(" rg\n");
+    PdfPaint { fill: Some(fill), alpha: color.a as f32 / 255.0, soft_mask: None }
+}
+
+// Writes the shading dictionary for `gradient` in its normalized space, spanning the offsets in
+// `range`. If `alpha` is true, the shading is of the alpha of the stops, in gray.
+fn write_pdf_shading(gradient: &Gradient, focus: Point2DF32, range: Range<f32>, alpha: bool)
+                     -> String {
+    let mut shading = String::new();
+    let color_space = if alpha { "DeviceGray" } else { "DeviceRGB" };
+    match *gradient.geometry() {
+        GradientGeometry::Linear(_) => {
+            write!(shading,
+                   "<< /ShadingType 2 /ColorSpace /{} /Coords [{} 0 {} 0]",
+                   color_space,
+                   range.start,
+                   range.end).unwrap();
+        }
+        GradientGeometry::Radial { .. } => {
+            // The circles run from a point at the focus to the unit circle, and beyond that for
+            // repeating gradients.
+            let center = focus.scale(1.0 - range.end);
+            write!(shading,
+                   "<< /ShadingType 3 /ColorSpace /{} /Coords [{} {} 0 {} {} {}]",
+                   color_space,
+                   focus.x(),
+                   focus.y(),
+                   center.x(),
+                   center.y(),
+                   range.end).unwrap();
+        }
+    }
+    write!(shading, " /Domain [{} {}] /Function ", range.start, range.end).unwrap();
+
+    let period = write_pdf_stops_function(gradient.stops(), alpha);
+    if gradient.spread() == SpreadMethod::Pad {
+        shading.push_str(&period);
+    } else {
+        // One copy of the stops per period, with every other one reversed when reflecting.
+        let (start, end) = (range.start as i32, range.end as i32);
+        write!(shading, "<< /FunctionType 3 /Domain [{} {}] /Functions [", start, end).unwrap();
+        for _ in start..end {
+            write!(shading, "{} ", period).unwrap();
+        }
+        shading.push_str("] /Bounds [");
+        for bound in (start + 1)..end {
+            write!(shading, "{} ", bound).unwrap();
+        }
+        shading.push_str("] /Encode [");
+        for index in start..end {
+            let reversed = gradient.spread() == SpreadMethod::Reflect && index.rem_euclid(2) == 1;
+            shading.push_str(if reversed { "1 0 " } else { "0 1 " });
+        }
+        shading.push_str("] >>");
+    }
+    shading.push_str(" /Extend [true true] >>");
+    shading
+}
+
+// Writes a function from [0, 1] to the colors of `stops`, or to their alphas if `alpha` is true.
+fn write_pdf_stops_function(stops: &[ColorStop], alpha: bool) -> String {
+    // Stops don't have to cover [0, 1], but the function does.
+    let mut points: Vec<(f32, ColorU)> = stops.iter().map(|stop| (stop.offset, stop.color))
+                                                     .collect();
+    if points[0].0 > 0.0 {
+        points.insert(0, (0.0, points[0].1));
+    }
+    if points[points.len() - 1].0 < 1.0 {
+        points.push((1.0, points[points.len() - 1].1));
+    }
+
+    // Stops at the same offset make a hard transition, which needs no function of its own.
+    let pieces: Vec<_> = points.windows(2).filter(|pair| pair[1].0 > pair[0].0).collect();
+    let write_interpolation = |function: &mut String, from: ColorU, to: ColorU| {
+        function.push_str("<< /FunctionType 2 /Domain [0 1] /C0 [");
+        write_pdf_color(function, from, alpha);
+        function.push_str("] /C1 [");
+        write_pdf_color(function, to, alpha);
+        function.push_str("] /N 1 >>");
+    };
+
+    let mut function = String::new();
+    match pieces.len() {
+        0 => {
+            let color = points[points.len() - 1].1;
+            write_interpolation(&mut function, color, color);
+            return function;
+        }
+        1 => {
+            write_interpolation(&mut function, pieces[0][0].1, pieces[0][1].1);
+            return function;
+        }
+        _ => {}
+    }
+
+    function.push_str("<< /FunctionType 3 /Domain [0 1] /Functions [");
+    for piece in &pieces {
+        write_interpolation(&mut function, piece[0].1, piece[1].1);
+        function.push(' ');
+    }
+    function.push_str("] /Bounds [");
+    for piece in pieces.iter().skip(1) {
+        write!(function, "{} ", piece[0].0).unwrap();
+    }
+    function.push_str("] /Encode [");
+    for _ in &pieces {
+        function.push_str("0 1 ");
+    }
+    function.push_str("] >>");
+    function
+}
+
+fn write_pdf_color(content: &mut String, color: ColorU, alpha: bool) {
+    if alpha {
+        write!(content, "{}", color.a as f32 / 255.0).unwrap();
+    } else {
+        write!(content,
+               "{} {} {}",
+               color.r as f32 / 255.0,
+               color.g as f32 / 255.0,
+               color.b as f32 / 255.0).unwrap();
+    }
+}
+
+fn write_pdf_matrix(content: &mut String, transform: &Transform2DF32) {
+    write!(content,
+           "{} {} {} {} {} {}",
+           transform.m11(),
+           transform.m21(),
+           transform.m12(),
+           transform.m22(),
+           transform.translation().x(),
+           transform.translation().y()).unwrap();
+}
+
+// Accumulates numbered objects and the cross-reference table that locates them.
+struct PdfDocument {
+    objects: Vec<Vec<u8>>,
+}
+
+impl PdfDocument {
+    fn new() -> PdfDocument {
+        PdfDocument { objects: vec![] }
+    }
+
+    // Returns the number of a new object, whose contents are set later.
+    fn reserve_object(&mut self) -> u32 {
+        self.push_object(vec![])
+    }
+
+    fn set_object(&mut self, object: u32, contents: String) {
+        self.objects[object as usize - 1] = contents.into_bytes();
+    }
+
+    fn push_object(&mut self, contents: Vec<u8>) -> u32 {
+        self.objects.push(contents);
+        self.objects.len() as u32
+    }
+
+    // Pushes a stream object. The dictionary entries in `dictionary` must be followed by a space.
+    fn push_stream(&mut self, dictionary: &str, data: &[u8]) -> u32 {
+        let mut contents = format!("<< {}/Length {} >>\nstream\n", dictionary, data.len())
+            .into_bytes();
+        contents.extend_from_slice(data);
+        contents.extend_from_slice(b"\nendstream");
+        self.push_object(contents)
+    }
+
+    fn finish(self) -> Vec<u8> {
+        // The comment of high bytes tells tools that the file is binary.
+        let mut bytes = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut object_offsets = vec![];
+        for (index, contents) in self.objects.iter().enumerate() {
+            object_offsets.push(bytes.len());
+            bytes.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+            bytes.extend_from_slice(contents);
+            bytes.extend_from_slice(b"\nendobj\n");
+        }
+
+        let xref_offset = bytes.len();
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", object_offsets.len() + 1);
+        for offset in &object_offsets {
+            writeln!(xref, "{:010} 00000 n ", offset).unwrap();
+        }
+        write!(xref,
+               "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+               object_offsets.len() + 1,
+               CATALOG_OBJECT,
+               xref_offset).unwrap();
+        bytes.extend_from_slice(xref.as_bytes());
+        bytes
+    }
+}
+
+fn write_pdf_path(content: &mut String, outline: &Outline) {
+    for contour in outline.contours() {
+        for (segment_index, segment) in contour.iter().enumerate() {
+            if segment_index == 0 {
+                write_pdf_points(content, &[segment.baseline.from()], "m");
+            }
+            let to = segment.baseline.to();
+            match segment.kind {
+                SegmentKind::None => {}
+                SegmentKind::Line => write_pdf_points(content, &[to], "l"),
+                SegmentKind::Quadratic => {
+                    let segment = segment.to_cubic();
+                    write_pdf_points(content, &[segment.ctrl.from(), segment.ctrl.to(), to], "c");
+                }
+                SegmentKind::Cubic => {
+                    write_pdf_points(content, &[segment.ctrl.from(), segment.ctrl.to(), to], "c");
+                }
+            }
+        }
+        if contour.is_closed() {
+            content.push_str("h\n");
+        }
+    }
+}
+
+fn write_pdf_points(content: &mut String, points: &[Point2DF32], operator: &str) {
+    for point in points {
+        write!(content, "{} {} ", point.x(), point.y()).unwrap();
+    }
+    writeln!(content, "{}", operator).unwrap();
+}
+
+// PDF has the separable and non-separable blend modes, but of the Porter-Duff operators only
+// source-over.
+fn pdf_blend_mode(blend_mode: BlendMode) -> Option<&'static str> {
+    match blend_mode {
+        BlendMode::SrcOver => Some("Normal"),
+        BlendMode::Multiply => Some("Multiply"),
+        BlendMode::Screen => Some("Screen"),
+        BlendMode::Overlay => Some("Overlay"),
+        BlendMode::Darken => Some("Darken"),
+        BlendMode::Lighten => Some("Lighten"),
+        BlendMode::ColorDodge => Some("ColorDodge"),
+        BlendMode::ColorBurn => Some("ColorBurn"),
+        BlendMode::HardLight => Some("HardLight"),
+        BlendMode::SoftLight => Some("SoftLight"),
+        BlendMode::Difference => Some("Difference"),
+        BlendMode::Exclusion => Some("Exclusion"),
+        BlendMode::Hue => Some("Hue"),
+        BlendMode::Saturation => Some("Saturation"),
+        BlendMode::Color => Some("Color"),
+        BlendMode::Luminosity => Some("Luminosity"),
+        BlendMode::Clear | BlendMode::Copy | BlendMode::SrcIn | BlendMode::SrcOut |
+        BlendMode::SrcAtop | BlendMode::DestOver | BlendMode::DestIn | BlendMode::DestOut |
+        BlendMode::DestAtop | BlendMode::Xor | BlendMode::Lighter => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blend::BlendMode;
+    use crate::paint::{Gradient, Image, Paint, Pattern, RepeatMode, SpreadMethod};
+    use crate::scene::{PathObject, Scene};
+    use pathfinder_geometry::basic::line_segment::LineSegmentF32;
+    use pathfinder_geometry::basic::point::{Point2DF32, Point2DI32};
+    use pathfinder_geometry::basic::rect::RectF32;
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::outline::Outline;
+    use super::PdfWarnings;
+
+    fn write_pdf(scene: &Scene) -> (String, PdfWarnings) {
+        let mut pdf = vec![];
+        let warnings = scene.write_pdf(&mut pdf).unwrap();
+        let pdf = pdf.iter().map(|&byte| if byte.is_ascii() { byte as char } else { '?' })
+                            .collect();
+        (pdf, warnings)
+    }
+
+    #[test]
+    fn test_write_pdf() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::new(10.0, 20.0), Point2DF32::new(30.0, 40.0)));
+        let paint = scene.push_paint(&Paint::Color(ColorU { r: 255, g: 0, b: 0, a: 51 }));
+        let outline = Outline::from_svg_path_data("M 10 20 L 40 20 Q 40 60 10 60 Z").unwrap();
+        scene.push_path(PathObject::new(outline, paint, String::new()));
+
+        let (pdf, warnings) = write_pdf(&scene);
+        assert!(warnings.is_empty());
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.contains("/MediaBox [0 0 30 40]"));
+        assert!(pdf.contains("/GS0 << /Type /ExtGState /ca 0.2 /CA 0.2 /BM /Normal >>"));
+        assert!(pdf.contains("1 0 0 -1 -10 60 cm\nq\n/GS0 gs\n1 0 0 rg\n10 20 m\n40 20 l\n"));
+
+        // Every entry in the cross-reference table should point at its object.
+        let xref_offset: usize = pdf.lines().rev().nth(1).unwrap().parse().unwrap();
+        let xref = &pdf[xref_offset..];
+        let object_count: usize = xref.lines().nth(1).unwrap()[2..].parse().unwrap();
+        for (index, line) in xref.lines().skip(3).take(object_count - 1).enumerate() {
+            let offset: usize = line[0..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(&format!("{} 0 obj\n", index + 1)));
+        }
+    }
+
+    #[test]
+    fn test_write_pdf_paints_and_groups() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::splat(16.0)));
+        let outline = Outline::from_svg_path_data("M 0 0 L 16 0 L 16 16 L 0 16 Z").unwrap();
+
+        let line = LineSegmentF32::new(Point2DF32::default(), Point2DF32::new(4.0, 0.0));
+        let mut gradient = Gradient::linear(line);
+        gradient.add_color_stop(0.0, ColorU { r: 255, g: 0, b: 0, a: 255 });
+        gradient.add_color_stop(1.0, ColorU { r: 0, g: 0, b: 255, a: 128 });
+        gradient.set_spread(SpreadMethod::Reflect);
+        let gradient = scene.push_paint(&Paint::Gradient(gradient));
+        let mut radial_gradient = Gradient::radial(Point2DF32::splat(8.0), 8.0);
+        radial_gradient.add_color_stop(0.5, ColorU::black());
+        let radial_gradient = scene.push_paint(&Paint::Gradient(radial_gradient));
+        let image = Image::new(Point2DI32::splat(2), vec![255; 16]);
+        let pattern = scene.push_paint(&Paint::Pattern(Pattern::new(image, RepeatMode::Repeat)));
+
+        scene.push_group(0.5);
+        for &paint in &[gradient, radial_gradient, pattern] {
+            scene.push_path(PathObject::new(outline.clone(), paint, String::new()));
+        }
+        let mut erasing_path = PathObject::new(outline.clone(), pattern, String::new());
+        erasing_path.set_blend_mode(BlendMode::DestOut);
+        scene.push_path(erasing_path);
+        scene.pop_group();
+
+        let (pdf, warnings) = write_pdf(&scene);
+        assert_eq!(warnings, PdfWarnings { omitted: 1, approximated: 0 });
+        assert!(pdf.contains("/Group << /S /Transparency /I true >>"));
+        assert!(pdf.contains("/ca 0.5 /CA 0.5 /BM /Normal >>"));
+        // The reflected gradient spans the page, from offset 0 to 4, in four periods.
+        assert!(pdf.contains("/ShadingType 2 /ColorSpace /DeviceRGB /Coords [0 0 4 0]"));
+        assert!(pdf.contains("/Matrix [4 0 0 -4 0 16]"));
+        assert!(pdf.contains("/Bounds [1 2 3 ] /Encode [0 1 1 0 0 1 1 0 ]"));
+        assert!(pdf.contains("/SMask << /Type /Mask /S /Luminosity"));
+        assert!(pdf.contains("/ShadingType 3 /ColorSpace /DeviceRGB /Coords [0 0 0 0 0 1]"));
+        assert!(pdf.contains("/PatternType 1 /PaintType 1 /TilingType 1 /BBox [0 0 2 2] \
+                              /XStep 2 /YStep 2"));
+        assert!(pdf.contains("/Subtype /Image /Width 2 /Height 2"));
+        assert_eq!(pdf.matches("/Pattern cs").count(), 3);
+    }
+}
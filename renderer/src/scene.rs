@@ -10,23 +10,56 @@
 
 //! A set of paths to be rendered.
 
+use crate::blend::BlendMode;
 use crate::builder::SceneBuilder;
 use crate::concurrent::executor::Executor;
+use crate::gpu_data::RenderCommand;
 use crate::options::{PreparedRenderOptions, PreparedRenderTransform};
 use crate::options::{RenderCommandListener, RenderOptions};
-use crate::paint::{Paint, PaintId};
+use crate::paint::{Gradient, GradientGeometry, Paint, PaintId, Pattern};
+use crate::paint::SpreadMethod;
+use crate::tile_cache::{TileCache, TilingParams};
+use crate::tiles::{self, MAX_ALPHA_TILE_COORD};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use hashbrown::HashMap;
+use image::ColorType;
+use image::png::PNGEncoder;
 use pathfinder_geometry::basic::point::Point2DF32;
 use pathfinder_geometry::basic::rect::RectF32;
 use pathfinder_geometry::basic::transform2d::Transform2DF32;
+use pathfinder_geometry::boolean::BooleanOp;
 use pathfinder_geometry::color::ColorU;
+use pathfinder_geometry::fill::FillRule;
 use pathfinder_geometry::outline::Outline;
-use std::io::{self, Write};
+use serde::de::Error as DeserializeError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Duration;
 
-#[derive(Clone)]
+// The first bytes of a saved scene, followed by the version of the format.
+const SCENE_FILE_MAGIC: &[u8; 4] = b"PFSC";
+const SCENE_FILE_VERSION: u32 = 1;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Scene {
+    #[serde(serialize_with = "serialize_paths", deserialize_with = "deserialize_paths")]
     pub(crate) paths: Vec<PathObject>,
+    // The ID of each path, in the same order as `paths`, and the reverse mapping.
+    path_ids: Vec<PathId>,
+    #[serde(skip)]
+    path_indices: HashMap<PathId, usize>,
+    next_path_id: u32,
     pub(crate) paints: Vec<Paint>,
+    pub(crate) clip_paths: Vec<ClipPath>,
+    pub(crate) groups: Vec<Group>,
+    open_groups: Vec<GroupId>,
+    #[serde(skip)]
     paint_cache: HashMap<Paint, PaintId>,
     bounds: RectF32,
     view_box: RectF32,
@@ -37,30 +70,246 @@ impl Scene {
     pub fn new() -> Scene {
         Scene {
             paths: vec![],
+            path_ids: vec![],
+            path_indices: HashMap::new(),
+            next_path_id: 0,
             paints: vec![],
+            clip_paths: vec![],
+            groups: vec![],
+            open_groups: vec![],
             paint_cache: HashMap::new(),
             bounds: RectF32::default(),
             view_box: RectF32::default(),
         }
     }
 
-    pub fn push_path(&mut self, path: PathObject) {
-        self.bounds = self.bounds.union_rect(path.outline.bounds());
-        self.paths.push(path);
+    /// Adds a path on top of all the others and returns an ID that refers to it for as long as
+    /// it stays in the scene.
+    #[inline]
+    pub fn push_path(&mut self, path: PathObject) -> PathId {
+        let index = self.paths.len();
+        self.insert_path_at(index, path)
+    }
+
+    /// Adds a path so that it's drawn just below the path currently at `index`, or on top if
+    /// `index` is the number of paths. Like `Vec::insert()`, panics if `index` is out of bounds.
+    ///
+    /// The new path joins the groups that contain the path it's inserted below, and at the top
+    /// of the scene, the groups that are still open.
+    pub fn insert_path_at(&mut self, index: usize, path: PathObject) -> PathId {
+        let path_id = PathId(self.next_path_id);
+        self.next_path_id += 1;
+        self.insert_path_with_id(index, path, path_id);
+        path_id
+    }
+
+    /// Removes a path and returns it, or returns `None` if the scene has no path with this ID.
+    ///
+    /// If the path reached the edge of the scene bounds, they're recomputed to fit the remaining
+    /// paths, replacing any set with `set_bounds()`. That takes time proportional to the number of
+    /// paths, as does shifting the paths above this one down.
+    pub fn remove_path(&mut self, path_id: PathId) -> Option<PathObject> {
+        let index = self.path_index(path_id)?;
+        let path = self.remove_path_at(index);
+        self.path_bounds_removed(path.bounds());
+        Some(path)
+    }
+
+    /// Replaces a path, keeping its ID and its place in the drawing order, and returns the old
+    /// path. Returns `None` if the scene has no path with this ID.
+    ///
+    /// The scene bounds are updated as for `remove_path()`.
+    pub fn replace_path(&mut self, path_id: PathId, new_path: PathObject) -> Option<PathObject> {
+        let index = self.path_index(path_id)?;
+        self.bounds = self.bounds.union_rect(new_path.bounds());
+        let old_path = mem::replace(&mut self.paths[index], new_path);
+        self.path_bounds_removed(old_path.bounds());
+        Some(old_path)
+    }
+
+    /// Changes the paint of a path. Returns false if the scene has no path with this ID or no
+    /// paint with `new_paint`'s ID.
+    pub fn set_path_paint(&mut self, path_id: PathId, new_paint: PaintId) -> bool {
+        if new_paint.0 as usize >= self.paints.len() {
+            return false;
+        }
+        match self.path_index(path_id) {
+            None => false,
+            Some(index) => {
+                self.paths[index].paint = new_paint;
+                true
+            }
+        }
+    }
+
+    /// Changes the transform of a path, which places its outline in the scene without touching
+    /// the outline itself. Returns false if the scene has no path with this ID.
+    ///
+    /// The scene bounds are updated as for `replace_path()`, so moving a path within them is
+    /// cheap, but moving one that reaches their edge takes time proportional to the number of
+    /// paths.
+    pub fn set_path_transform(&mut self, path_id: PathId, new_transform: Transform2DF32) -> bool {
+        match self.path_index(path_id) {
+            None => false,
+            Some(index) => {
+                let old_bounds = self.paths[index].bounds();
+                self.paths[index].transform = new_transform;
+                self.bounds = self.bounds.union_rect(self.paths[index].bounds());
+                self.path_bounds_removed(old_bounds);
+                true
+            }
+        }
+    }
+
+    /// Moves a path in the drawing order, so that it ends up at `new_index`, or on top if that's
+    /// past the end. Group membership follows the new position, as with `insert_path_at()`.
+    /// Returns false if the scene has no path with this ID.
+    pub fn move_path(&mut self, path_id: PathId, new_index: usize) -> bool {
+        let index = match self.path_index(path_id) {
+            None => return false,
+            Some(index) => index,
+        };
+        let path = self.remove_path_at(index);
+        let new_index = cmp::min(new_index, self.paths.len());
+        self.insert_path_with_id(new_index, path, path_id);
+        true
+    }
+
+    /// Returns the position of a path in the drawing order, bottommost first.
+    #[inline]
+    pub fn path_index(&self, path_id: PathId) -> Option<usize> {
+        self.path_indices.get(&path_id).cloned()
+    }
+
+    /// Returns the ID of the path at `index` in the drawing order.
+    #[inline]
+    pub fn path_id(&self, index: usize) -> PathId {
+        self.path_ids[index]
+    }
+
+    #[inline]
+    pub fn path(&self, path_id: PathId) -> Option<&PathObject> {
+        self.path_index(path_id).map(|index| &self.paths[index])
+    }
+
+    fn insert_path_with_id(&mut self, index: usize, path: PathObject, path_id: PathId) {
+        let (index_u32, path_count) = (index as u32, self.paths.len() as u32);
+        for (group_index, group) in self.groups.iter_mut().enumerate() {
+            let range = &mut group.path_range;
+            let is_open = self.open_groups.contains(&GroupId(group_index as u32));
+            let contains = (range.start <= index_u32 && index_u32 < range.end) ||
+                (is_open && index_u32 == path_count);
+            if contains {
+                range.end += 1;
+            } else if range.start >= index_u32 {
+                // The group lies wholly above the new path, even if it's empty.
+                range.start += 1;
+                range.end += 1;
+            }
+        }
+
+        self.bounds = self.bounds.union_rect(path.bounds());
+        self.paths.insert(index, path);
+        self.path_ids.insert(index, path_id);
+        for (path_index, &path_id) in self.path_ids.iter().enumerate().skip(index) {
+            self.path_indices.insert(path_id, path_index);
+        }
+    }
+
+    fn remove_path_at(&mut self, index: usize) -> PathObject {
+        let index_u32 = index as u32;
+        for group in &mut self.groups {
+            let range = &mut group.path_range;
+            if range.start > index_u32 {
+                range.start -= 1;
+            }
+            if range.end > index_u32 {
+                range.end -= 1;
+            }
+        }
+
+        self.path_indices.remove(&self.path_ids.remove(index));
+        for (path_index, path_id) in self.path_ids.iter().enumerate().skip(index) {
+            self.path_indices.insert(*path_id, path_index);
+        }
+        self.paths.remove(index)
+    }
+
+    // Call this when a path with the given bounds leaves the scene. Unless that path reached an
+    // edge of the scene bounds, other paths still do, so they only need recomputing if it did.
+    fn path_bounds_removed(&mut self, old_bounds: RectF32) {
+        let bounds = self.bounds;
+        if old_bounds.min_x() <= bounds.min_x() || old_bounds.min_y() <= bounds.min_y() ||
+                old_bounds.max_x() >= bounds.max_x() || old_bounds.max_y() >= bounds.max_y() {
+            self.recompute_bounds();
+        }
+    }
+
+    fn recompute_bounds(&mut self) {
+        self.bounds = self.paths.iter().fold(RectF32::default(), |bounds, path| {
+            bounds.union_rect(path.bounds())
+        });
     }
 
-    #[allow(clippy::trivially_copy_pass_by_ref)]
     pub fn push_paint(&mut self, paint: &Paint) -> PaintId {
         if let Some(paint_id) = self.paint_cache.get(paint) {
             return *paint_id;
         }
 
-        let paint_id = PaintId(self.paints.len() as u16);
-        self.paint_cache.insert(*paint, paint_id);
-        self.paints.push(*paint);
+        let paint_id = PaintId(self.paints.len() as u32);
+        self.paint_cache.insert((*paint).clone(), paint_id);
+        self.paints.push((*paint).clone());
         paint_id
     }
 
+    /// Adds a clip path, which paths can then be clipped to with `PathObject::set_clip_path()`.
+    pub fn push_clip_path(&mut self, clip_path: ClipPath) -> ClipPathId {
+        let clip_path_id = ClipPathId(self.clip_paths.len() as u32);
+        self.clip_paths.push(clip_path);
+        clip_path_id
+    }
+
+    #[inline]
+    pub fn clip_paths(&self) -> &[ClipPath] {
+        &self.clip_paths
+    }
+
+    /// Begins a group. Paths pushed until the matching `pop_group()` are rendered together into
+    /// an intermediate target, which is then composited onto whatever lies below with the given
+    /// opacity. Groups may be nested.
+    pub fn push_group(&mut self, opacity: f32) -> GroupId {
+        let group_id = GroupId(self.groups.len() as u32);
+        let path_count = self.paths.len() as u32;
+        self.groups.push(Group {
+            path_range: path_count..path_count,
+            opacity,
+            blend_mode: BlendMode::SrcOver,
+            parent: self.open_groups.last().cloned(),
+        });
+        self.open_groups.push(group_id);
+        group_id
+    }
+
+    /// Ends the innermost open group.
+    pub fn pop_group(&mut self) {
+        self.open_groups.pop().expect("pop_group() called without a matching push_group()!");
+    }
+
+    #[inline]
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    #[inline]
+    pub fn group_mut(&mut self, group_id: GroupId) -> &mut Group {
+        &mut self.groups[group_id.0 as usize]
+    }
+
+    #[inline]
+    pub fn paths(&self) -> &[PathObject] {
+        &self.paths
+    }
+
     #[inline]
     pub fn path_count(&self) -> usize {
         self.paths.len()
@@ -86,57 +335,177 @@ impl Scene {
         self.view_box = new_view_box;
     }
 
+    /// Transforms `original_outline` by `path_transform` and then by the render transform, and
+    /// clips it, ready for tiling.
     pub(crate) fn apply_render_options(
         &self,
         original_outline: &Outline,
+        path_transform: &Transform2DF32,
         options: &PreparedRenderOptions,
+        clip_rect: Option<RectF32>,
     ) -> Outline {
         let effective_view_box = self.effective_view_box(options);
 
-        let mut outline;
+        let mut outline = self.place_outline(original_outline, path_transform, options)
+                              .unwrap_or_else(Outline::new);
+        match options.transform {
+            PreparedRenderTransform::Perspective { .. } => {}
+            _ => outline.clip_against_rect(effective_view_box),
+        }
+
+        // Anything outside the tiles of the clip path can't be visible, so cut it off here. Clip
+        // paths themselves take care of the rest at the tile level.
+        if let Some(clip_rect) = clip_rect {
+            outline.clip_against_rect(clip_rect);
+        }
+
+        if !options.dilation.is_zero() {
+            outline.dilate(options.dilation);
+        }
+
+        // TODO(pcwalton): Fold this into previous passes to avoid unnecessary clones during
+        // monotonic conversion.
+        outline.prepare_for_tiling(self.effective_view_box(options));
+        outline
+    }
+
+    // Transforms `original_outline` by `path_transform` and then by the render transform.
+    // Returns `None` if a perspective transform puts it entirely out of view.
+    fn place_outline(&self,
+                     original_outline: &Outline,
+                     path_transform: &Transform2DF32,
+                     options: &PreparedRenderOptions)
+                     -> Option<Outline> {
+        let mut outline = (*original_outline).clone();
         match options.transform {
             PreparedRenderTransform::Perspective {
                 ref perspective,
                 ref clip_polygon,
                 ..
             } => {
-                if original_outline.is_outside_polygon(clip_polygon) {
-                    outline = Outline::new();
-                } else {
-                    outline = (*original_outline).clone();
-                    outline.clip_against_polygon(clip_polygon);
-                    outline.apply_perspective(perspective);
-
-                    // TODO(pcwalton): Support subpixel AA in 3D.
+                outline.transform(path_transform);
+                if outline.is_outside_polygon(clip_polygon) {
+                    return None;
+                }
+                outline.clip_against_polygon(clip_polygon);
+                outline.apply_perspective(perspective);
+
+                // The projection lands in device pixels, so stretch it afterward, just as
+                // `effective_transform_2d()` does in 2D.
+                if options.subpixel_aa_enabled {
+                    outline.transform(&subpixel_aa_transform());
                 }
             }
             _ => {
                 // TODO(pcwalton): Short circuit.
-                outline = (*original_outline).clone();
-                if options.transform.is_2d() || options.subpixel_aa_enabled {
-                    let mut transform = match options.transform {
-                        PreparedRenderTransform::Transform2D(transform) => transform,
-                        PreparedRenderTransform::None => Transform2DF32::default(),
-                        PreparedRenderTransform::Perspective { .. } => unreachable!(),
-                    };
-                    if options.subpixel_aa_enabled {
-                        transform = transform
-                            .post_mul(&Transform2DF32::from_scale(Point2DF32::new(3.0, 1.0)))
-                    }
-                    outline.transform(&transform);
-                }
-                outline.clip_against_rect(effective_view_box);
+                outline.transform(&self.effective_transform_2d(path_transform, options)?)
             }
         }
+        Some(outline)
+    }
 
-        if !options.dilation.is_zero() {
-            outline.dilate(options.dilation);
+    // The transform that `apply_render_options()` applies to outlines placed with
+    // `path_transform`, or `None` if it projects them with a perspective transform.
+    fn effective_transform_2d(&self,
+                              path_transform: &Transform2DF32,
+                              options: &PreparedRenderOptions)
+                              -> Option<Transform2DF32> {
+        let mut transform = match options.transform {
+            PreparedRenderTransform::Transform2D(transform) => transform,
+            PreparedRenderTransform::None => Transform2DF32::default(),
+            PreparedRenderTransform::Perspective { .. } => return None,
+        };
+        if options.subpixel_aa_enabled {
+            transform = transform.post_mul(&subpixel_aa_transform())
         }
+        Some(path_transform.post_mul(&transform))
+    }
 
-        // TODO(pcwalton): Fold this into previous passes to avoid unnecessary clones during
-        // monotonic conversion.
-        outline.prepare_for_tiling(self.effective_view_box(options));
-        outline
+    // Describes what `apply_render_options()` does to `outline`, for the tile cache.
+    pub(crate) fn tiling_params(&self,
+                                outline: &Outline,
+                                path_transform: &Transform2DF32,
+                                options: &PreparedRenderOptions,
+                                clip_rect: Option<RectF32>)
+                                -> Option<TilingParams> {
+        let transform = self.effective_transform_2d(path_transform, options)?;
+        let view_box = self.effective_view_box(options);
+        let bounds = transform.transform_rect(&outline.bounds()).dilate(options.dilation);
+        Some(TilingParams {
+            transform,
+            dilation: options.dilation,
+            view_box,
+            clip_rect,
+            tile_size: options.tile_size,
+            flattening_tolerance: options.flattening_tolerance,
+            unclipped: clip_rect.is_none() && view_box.contains_rect(bounds),
+        })
+    }
+
+    /// Returns the index of the topmost path under `point`, as the scene would be rendered with
+    /// `options`. `point` is in the same space as the view box. Path transforms, clip paths,
+    /// subpixel AA and dilation are all accounted for, and paths that only erase what's below
+    /// them don't count. Paint opacity isn't, though: transparent parts of paths still count as
+    /// hits. `path_id()` turns the index into a handle.
+    ///
+    /// Fails if the options are invalid, as `build()` would.
+    pub fn hit_test(&self, point: Point2DF32, options: &RenderOptions)
+                    -> Result<Option<usize>, BuildError> {
+        let options = options.clone().prepare(self.bounds)?;
+        if !self.view_box.contains_point(point) {
+            return Ok(None);
+        }
+
+        let point = if options.subpixel_aa_enabled {
+            point.scale_xy(Point2DF32::new(3.0, 1.0))
+        } else {
+            point
+        };
+        let contains_point = |outline: &Outline, path_transform: &Transform2DF32, fill_rule| {
+            if let Some(transform) = self.effective_transform_2d(path_transform, &options) {
+                if options.dilation.is_zero() {
+                    let path_point = transform.inverse().transform_point(point);
+                    return outline.contains_point(path_point, fill_rule);
+                }
+                let bounds = transform.transform_rect(&outline.bounds());
+                if !bounds.dilate(options.dilation).contains_point(point) {
+                    return false;
+                }
+            }
+
+            // Otherwise, place the outline just as rendering does. Perspective transforms can't
+            // be inverted in general, and dilation doesn't commute with transforms.
+            let mut outline = match self.place_outline(outline, path_transform, &options) {
+                Some(outline) => outline,
+                None => return false,
+            };
+            if !options.dilation.is_zero() {
+                outline.dilate(options.dilation);
+            }
+            outline.contains_point(point, fill_rule)
+        };
+
+        let identity = Transform2DF32::default();
+        Ok(self.paths.iter().enumerate().rposition(|(path_index, path_object)| {
+            if path_object.blend_mode.erases() || self.group_erases(path_index as u32) {
+                return false;
+            }
+            if let Some(clip_path_id) = path_object.clip_path {
+                let clip_path = &self.clip_paths[clip_path_id.0 as usize];
+                if !contains_point(&clip_path.outline, &identity, clip_path.fill_rule) {
+                    return false;
+                }
+            }
+            contains_point(&path_object.outline, &path_object.transform, path_object.fill_rule)
+        }))
+    }
+
+    // Whether the path at `path_index` is in a group that only erases what's below it.
+    fn group_erases(&self, path_index: u32) -> bool {
+        self.groups.iter().any(|group| {
+            group.blend_mode.erases() && group.path_range.start <= path_index &&
+                path_index < group.path_range.end
+        })
     }
 
     pub fn monochrome_color(&self) -> Option<ColorU> {
@@ -152,7 +521,10 @@ impl Scene {
             .any(|path_object| path_object.paint != first_paint_id) {
             return None;
         }
-        Some(self.paints[first_paint_id.0 as usize].color)
+        match self.paints[first_paint_id.0 as usize] {
+            Paint::Color(color) => Some(color),
+            Paint::Gradient(_) | Paint::Pattern(_) => None,
+        }
     }
 
     #[inline]
@@ -164,14 +536,55 @@ impl Scene {
         }
     }
 
+    /// Builds the scene, sending the commands that render it to `listener`.
+    ///
+    /// On error, `listener` only gets a `Finish` command, or none of the tiles if the error was
+    /// found while tiling.
     #[inline]
     pub fn build<E>(&self,
                     options: RenderOptions,
                     listener: Box<dyn RenderCommandListener>,
                     executor: &E)
+                    -> Result<(), BuildError>
                     where E: Executor {
-        let prepared_options = options.prepare(self.bounds);
-        SceneBuilder::new(self, &prepared_options, listener).build(executor)
+        let prepared_options = match self.prepare_build_options(options) {
+            Ok(prepared_options) => prepared_options,
+            Err(error) => return abort_build(listener, error),
+        };
+        SceneBuilder::new(self, &prepared_options, listener, None).build(executor)
+    }
+
+    /// Builds the scene like `build()`, but reuses the tiles of paths that haven't changed since
+    /// the last build with `tile_cache`. The cache is then filled with the tiles of this build.
+    pub fn build_with_tile_cache<E>(&self,
+                                    options: RenderOptions,
+                                    listener: Box<dyn RenderCommandListener>,
+                                    executor: &E,
+                                    tile_cache: &mut TileCache)
+                                    -> Result<(), BuildError>
+                                    where E: Executor {
+        let prepared_options = match self.prepare_build_options(options) {
+            Ok(prepared_options) => prepared_options,
+            Err(error) => return abort_build(listener, error),
+        };
+        let mut builder = SceneBuilder::new(self, &prepared_options, listener, Some(tile_cache));
+        let result = builder.build(executor);
+        let (entries, reused_path_count) = builder.into_tile_cache_entries();
+        tile_cache.replace_entries(entries, reused_path_count);
+        result
+    }
+
+    // Also checks that alpha tiles, which pack their coordinates into 12 bits each, can address
+    // every tile of the view box.
+    fn prepare_build_options(&self, options: RenderOptions)
+                             -> Result<PreparedRenderOptions, BuildError> {
+        let options = options.prepare(self.bounds)?;
+        let view_box = self.effective_view_box(&options);
+        let tile_rect = tiles::round_rect_out_to_tile_bounds(view_box, options.tile_size);
+        if tile_rect.max_x() > MAX_ALPHA_TILE_COORD || tile_rect.max_y() > MAX_ALPHA_TILE_COORD {
+            return Err(BuildError::ViewBoxTooLarge);
+        }
+        Ok(options)
     }
 
     pub fn write_svg<W>(&self, writer: &mut W) -> io::Result<()> where W: Write {
@@ -183,34 +596,560 @@ impl Scene {
             self.view_box.size().x(),
             self.view_box.size().y()
         )?;
-        for path_object in &self.paths {
-            let paint = &self.paints[path_object.paint.0 as usize];
+        for (paint_index, paint) in self.paints.iter().enumerate() {
+            match *paint {
+                Paint::Color(_) => {}
+                Paint::Gradient(ref gradient) => write_svg_gradient(writer, paint_index, gradient)?,
+                Paint::Pattern(ref pattern) => write_svg_pattern(writer, paint_index, pattern)?,
+            }
+        }
+        for (clip_path_index, clip_path) in self.clip_paths.iter().enumerate() {
+            writeln!(writer,
+                     "    <clipPath id=\"clip{}\"><path clip-rule=\"{}\" d=\"{}\" />\
+                      </clipPath>",
+                     clip_path_index,
+                     svg_fill_rule(clip_path.fill_rule),
+                     clip_path.outline.to_svg_path_data())?;
+        }
+        let mut open_groups: Vec<GroupId> = vec![];
+        for (path_index, path_object) in self.paths.iter().enumerate() {
+            let path_index = path_index as u32;
+            while let Some(&group_id) = open_groups.last() {
+                if self.groups[group_id.0 as usize].path_range.end > path_index {
+                    break;
+                }
+                writeln!(writer, "    </g>")?;
+                open_groups.pop();
+            }
+            // Groups are stored parents first, so this opens nested groups in order.
+            for (group_index, group) in self.groups.iter().enumerate() {
+                if group.path_range.start == path_index && !group.path_range.is_empty() &&
+                        group.parent == open_groups.last().cloned() {
+                    write!(writer, "    <g opacity=\"{}\"", group.opacity)?;
+                    write_svg_blend_mode(writer, group.blend_mode)?;
+                    writeln!(writer, ">")?;
+                    open_groups.push(GroupId(group_index as u32));
+                }
+            }
+
             write!(writer, "    <path")?;
             if !path_object.name.is_empty() {
-                write!(writer, " id=\"{}\"", path_object.name)?;
+                write!(writer, " id=\"{}\"", escape_xml(&path_object.name))?;
+            }
+            if let Some(clip_path) = path_object.clip_path {
+                write!(writer, " clip-path=\"url(#clip{})\"", clip_path.0)?;
+            }
+            write_svg_blend_mode(writer, path_object.blend_mode)?;
+            match self.paints[path_object.paint.0 as usize] {
+                Paint::Color(color) => write_svg_color(writer, "fill", color)?,
+                Paint::Gradient(_) | Paint::Pattern(_) => {
+                    write!(writer, " fill=\"url(#paint{})\"", path_object.paint.0)?
+                }
+            }
+            if path_object.fill_rule != FillRule::Winding {
+                write!(writer, " fill-rule=\"{}\"", svg_fill_rule(path_object.fill_rule))?;
             }
-            writeln!(
-                writer,
-                " fill=\"{:?}\" d=\"{:?}\" />",
-                paint.color, path_object.outline
-            )?;
+            // A `transform` attribute would move the clip path and the paint along with the path,
+            // so transform the outline instead.
+            let path_data = if path_object.transform.is_identity() {
+                path_object.outline.to_svg_path_data()
+            } else {
+                let mut outline = (*path_object.outline).clone();
+                outline.transform(&path_object.transform);
+                outline.to_svg_path_data()
+            };
+            writeln!(writer, " d=\"{}\" />", path_data)?;
+        }
+        for _ in open_groups {
+            writeln!(writer, "    </g>")?;
         }
         writeln!(writer, "</svg>")?;
         Ok(())
     }
 }
 
-#[derive(Clone, Debug)]
+impl Scene {
+    /// Writes this scene in a compact binary format that `load()` reads back.
+    ///
+    /// The format begins with a magic number and a version number, so that files written by
+    /// other versions of Pathfinder are rejected instead of misread.
+    pub fn save<W>(&self, writer: &mut W) -> io::Result<()> where W: Write {
+        writer.write_all(SCENE_FILE_MAGIC)?;
+        writer.write_u32::<LittleEndian>(SCENE_FILE_VERSION)?;
+        bincode::serialize_into(writer, self).map_err(|error| bincode_error_to_io_error(*error))
+    }
+
+    /// Reads a scene written by `save()`.
+    pub fn load<R>(reader: &mut R) -> io::Result<Scene> where R: Read {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != *SCENE_FILE_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a Pathfinder scene"));
+        }
+        let version = reader.read_u32::<LittleEndian>()?;
+        if version != SCENE_FILE_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("unsupported scene version {}", version)));
+        }
+
+        let mut scene: Scene = bincode::deserialize_from(reader).map_err(|error| {
+            bincode_error_to_io_error(*error)
+        })?;
+        scene.validate().map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?;
+        for (paint_index, paint) in scene.paints.iter().enumerate() {
+            scene.paint_cache.insert((*paint).clone(), PaintId(paint_index as u32));
+        }
+        Ok(scene)
+    }
+
+    // Checks that a deserialized scene upholds the invariants that the methods that build scenes
+    // maintain, so that a corrupt file is rejected here rather than causing a panic or a bogus
+    // render later. Bounds aren't trusted; they're recomputed from the points.
+    fn validate(&mut self) -> Result<(), &'static str> {
+        if !self.paints.iter().all(Paint::is_valid) {
+            return Err("invalid gradient stops");
+        }
+
+        for clip_path in &mut self.clip_paths {
+            if !clip_path.outline.validate() {
+                return Err("malformed clip path outline");
+            }
+        }
+
+        let (paint_count, clip_path_count) = (self.paints.len(), self.clip_paths.len());
+        for path in &self.paths {
+            if path.paint.0 as usize >= paint_count {
+                return Err("path refers to a nonexistent paint");
+            }
+            if let Some(clip_path) = path.clip_path {
+                if clip_path.0 as usize >= clip_path_count {
+                    return Err("path refers to a nonexistent clip path");
+                }
+            }
+            // Path outlines were validated as they were read, before they were shared.
+            if !transform_is_finite(&path.transform) {
+                return Err("path transform isn't finite");
+            }
+        }
+
+        if self.path_ids.len() != self.paths.len() {
+            return Err("path ID count doesn't match path count");
+        }
+        self.path_indices = HashMap::with_capacity(self.path_ids.len());
+        for (path_index, &path_id) in self.path_ids.iter().enumerate() {
+            if path_id.0 >= self.next_path_id ||
+                    self.path_indices.insert(path_id, path_index).is_some() {
+                return Err("invalid or duplicate path ID");
+            }
+        }
+
+        // Groups must nest: each lies within its parent, which was pushed before it, and follows
+        // its earlier siblings without overlapping them. Index 0 holds the top level.
+        let path_count = self.paths.len() as u32;
+        let mut last_child_ends = vec![0; self.groups.len() + 1];
+        for (group_index, group) in self.groups.iter().enumerate() {
+            let range = &group.path_range;
+            if range.start > range.end || range.end > path_count {
+                return Err("group path range out of bounds");
+            }
+            let parent_index = match group.parent {
+                None => 0,
+                Some(parent) if (parent.0 as usize) < group_index => {
+                    let parent_range = &self.groups[parent.0 as usize].path_range;
+                    if range.start < parent_range.start || range.end > parent_range.end {
+                        return Err("group extends outside its parent");
+                    }
+                    parent.0 as usize + 1
+                }
+                Some(_) => return Err("group parent must precede the group"),
+            };
+            if range.start < last_child_ends[parent_index] {
+                return Err("group overlaps an earlier sibling");
+            }
+            last_child_ends[parent_index] = range.end;
+        }
+
+        // Open groups form a chain from the top level inward.
+        let mut parent = None;
+        for &group_id in &self.open_groups {
+            match self.groups.get(group_id.0 as usize) {
+                Some(group) if group.parent == parent => parent = Some(group_id),
+                _ => return Err("invalid open group"),
+            }
+        }
+
+        self.recompute_bounds();
+        Ok(())
+    }
+}
+
+// A path as it's saved, with its outline replaced by an index into a table of outlines.
+#[derive(Serialize, Deserialize)]
+struct SavedPath<N> {
+    outline: u32,
+    transform: Transform2DF32,
+    paint: PaintId,
+    fill_rule: FillRule,
+    clip_path: Option<ClipPathId>,
+    blend_mode: BlendMode,
+    name: N,
+}
+
+// Saves the outlines of the paths in a table before the paths themselves, so that an outline
+// shared between paths is only written once.
+fn serialize_paths<S>(paths: &[PathObject], serializer: S) -> Result<S::Ok, S::Error>
+                      where S: Serializer {
+    let mut outline_indices = HashMap::new();
+    let mut outlines: Vec<&Outline> = vec![];
+    let saved_paths: Vec<_> = paths.iter().map(|path| {
+        let outline = &*path.outline;
+        let outline_index = *outline_indices.entry(outline as *const Outline).or_insert_with(|| {
+            outlines.push(outline);
+            outlines.len() as u32 - 1
+        });
+        SavedPath {
+            outline: outline_index,
+            transform: path.transform,
+            paint: path.paint,
+            fill_rule: path.fill_rule,
+            clip_path: path.clip_path,
+            blend_mode: path.blend_mode,
+            name: &*path.name,
+        }
+    }).collect();
+    (outlines, saved_paths).serialize(serializer)
+}
+
+// Reads back what `serialize_paths()` wrote, validating the outlines before they're shared.
+fn deserialize_paths<'de, D>(deserializer: D) -> Result<Vec<PathObject>, D::Error>
+                             where D: Deserializer<'de> {
+    let (outlines, saved_paths): (Vec<Outline>, Vec<SavedPath<String>>) =
+        Deserialize::deserialize(deserializer)?;
+    let mut shared_outlines = Vec::with_capacity(outlines.len());
+    for mut outline in outlines {
+        if !outline.validate() {
+            return Err(D::Error::custom("malformed path outline"));
+        }
+        shared_outlines.push(Arc::new(outline));
+    }
+
+    saved_paths.into_iter().map(|saved_path| {
+        let outline = match shared_outlines.get(saved_path.outline as usize) {
+            None => return Err(D::Error::custom("path refers to a nonexistent outline")),
+            Some(outline) => outline.clone(),
+        };
+        Ok(PathObject {
+            outline,
+            transform: saved_path.transform,
+            paint: saved_path.paint,
+            fill_rule: saved_path.fill_rule,
+            clip_path: saved_path.clip_path,
+            blend_mode: saved_path.blend_mode,
+            name: saved_path.name,
+        })
+    }).collect()
+}
+
+pub(crate) fn bincode_error_to_io_error(error: bincode::ErrorKind) -> io::Error {
+    match error {
+        bincode::ErrorKind::Io(error) => error,
+        error => io::Error::new(io::ErrorKind::InvalidData, error),
+    }
+}
+
+fn transform_is_finite(transform: &Transform2DF32) -> bool {
+    let translation = transform.translation();
+    let components = [
+        transform.m11(), transform.m12(), transform.m21(), transform.m22(),
+        translation.x(), translation.y(),
+    ];
+    components.iter().all(|component| component.is_finite())
+}
+
+// Fails a build before it starts. The listener still gets the `Finish` command that ends every
+// build.
+fn abort_build(listener: Box<dyn RenderCommandListener>, error: BuildError)
+               -> Result<(), BuildError> {
+    listener.send(RenderCommand::Finish { build_time: Duration::default() });
+    Err(error)
+}
+
+// Subpixel AA renders into a target three times as wide, one pixel per color channel.
+fn subpixel_aa_transform() -> Transform2DF32 {
+    Transform2DF32::from_scale(Point2DF32::new(3.0, 1.0))
+}
+
+fn write_svg_gradient<W>(writer: &mut W, paint_index: usize, gradient: &Gradient)
+                         -> io::Result<()>
+                         where W: Write {
+    let element = match *gradient.geometry() {
+        GradientGeometry::Linear(line) => {
+            write!(writer,
+                   "    <linearGradient x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"",
+                   line.from_x(),
+                   line.from_y(),
+                   line.to_x(),
+                   line.to_y())?;
+            "linearGradient"
+        }
+        GradientGeometry::Radial { center, radius, focus } => {
+            write!(writer,
+                   "    <radialGradient cx=\"{}\" cy=\"{}\" r=\"{}\" fx=\"{}\" fy=\"{}\"",
+                   center.x(),
+                   center.y(),
+                   radius,
+                   focus.x(),
+                   focus.y())?;
+            "radialGradient"
+        }
+    };
+
+    let spread = match gradient.spread() {
+        SpreadMethod::Pad => "pad",
+        SpreadMethod::Reflect => "reflect",
+        SpreadMethod::Repeat => "repeat",
+    };
+    let transform = gradient.transform();
+    writeln!(writer,
+             " id=\"paint{}\" gradientUnits=\"userSpaceOnUse\" spreadMethod=\"{}\" \
+               gradientTransform=\"matrix({} {} {} {} {} {})\">",
+             paint_index,
+             spread,
+             transform.m11(),
+             transform.m21(),
+             transform.m12(),
+             transform.m22(),
+             transform.translation().x(),
+             transform.translation().y())?;
+    for stop in gradient.stops() {
+        write!(writer, "        <stop offset=\"{}\"", stop.offset)?;
+        write_svg_color(writer, "stop-color", stop.color)?;
+        writeln!(writer, " />")?;
+    }
+    writeln!(writer, "    </{}>", element)
+}
+
+// SVG patterns always repeat in both directions, so patterns that don't are written out as a
+// single tile large enough that the repetition is out of sight. A shape more than a million units
+// across will still show the image again.
+fn write_svg_pattern<W>(writer: &mut W, paint_index: usize, pattern: &Pattern)
+                        -> io::Result<()>
+                        where W: Write {
+    const NO_REPEAT_SIZE: f32 = 1.0e6;
+
+    let image = pattern.image();
+    let size = image.size().to_f32();
+    let tile_width = if pattern.repeat_mode().repeats_x() { size.x() } else { NO_REPEAT_SIZE };
+    let tile_height = if pattern.repeat_mode().repeats_y() { size.y() } else { NO_REPEAT_SIZE };
+    let transform = pattern.transform();
+    writeln!(writer,
+             "    <pattern id=\"paint{}\" patternUnits=\"userSpaceOnUse\" width=\"{}\" \
+               height=\"{}\" patternTransform=\"matrix({} {} {} {} {} {})\">",
+             paint_index,
+             tile_width,
+             tile_height,
+             transform.m11(),
+             transform.m21(),
+             transform.m12(),
+             transform.m22(),
+             transform.translation().x(),
+             transform.translation().y())?;
+
+    let mut png = vec![];
+    PNGEncoder::new(&mut png).encode(image.pixels(),
+                                     image.size().x() as u32,
+                                     image.size().y() as u32,
+                                     ColorType::RGBA(8))?;
+    writeln!(writer,
+             "        <image width=\"{}\" height=\"{}\" opacity=\"{}\" \
+               href=\"data:image/png;base64,{}\" />",
+             size.x(),
+             size.y(),
+             pattern.opacity(),
+             encode_base64(&png))?;
+    writeln!(writer, "    </pattern>")
+}
+
+// SVG only has the blend modes proper. The other Porter-Duff operators would need filters, so
+// paths and groups using them are written out with normal blending.
+fn write_svg_blend_mode<W>(writer: &mut W, blend_mode: BlendMode) -> io::Result<()>
+                           where W: Write {
+    let name = match blend_mode {
+        BlendMode::Multiply => "multiply",
+        BlendMode::Screen => "screen",
+        BlendMode::Overlay => "overlay",
+        BlendMode::Darken => "darken",
+        BlendMode::Lighten => "lighten",
+        BlendMode::ColorDodge => "color-dodge",
+        BlendMode::ColorBurn => "color-burn",
+        BlendMode::HardLight => "hard-light",
+        BlendMode::SoftLight => "soft-light",
+        BlendMode::Difference => "difference",
+        BlendMode::Exclusion => "exclusion",
+        BlendMode::Hue => "hue",
+        BlendMode::Saturation => "saturation",
+        BlendMode::Color => "color",
+        BlendMode::Luminosity => "luminosity",
+        BlendMode::SrcOver | BlendMode::Clear | BlendMode::Copy | BlendMode::SrcIn |
+        BlendMode::SrcOut | BlendMode::SrcAtop | BlendMode::DestOver | BlendMode::DestIn |
+        BlendMode::DestOut | BlendMode::DestAtop | BlendMode::Xor | BlendMode::Lighter => {
+            return Ok(())
+        }
+    };
+    write!(writer, " style=\"mix-blend-mode: {}\"", name)
+}
+
+// Writes a color attribute, and an opacity attribute alongside it if the color isn't opaque. Not
+// every SVG viewer understands `rgba()` colors.
+fn write_svg_color<W>(writer: &mut W, attribute: &str, color: ColorU) -> io::Result<()>
+                      where W: Write {
+    write!(writer, " {}=\"rgb({}, {}, {})\"", attribute, color.r, color.g, color.b)?;
+    if color.a != 255 {
+        // `fill` goes with `fill-opacity`, and `stop-color` with `stop-opacity`.
+        let opacity_attribute = attribute.trim_end_matches("-color");
+        write!(writer, " {}-opacity=\"{}\"", opacity_attribute, color.a as f32 / 255.0)?;
+    }
+    Ok(())
+}
+
+fn escape_xml(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for ch in string.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+fn svg_fill_rule(fill_rule: FillRule) -> &'static str {
+    match fill_rule {
+        FillRule::Winding => "nonzero",
+        FillRule::EvenOdd => "evenodd",
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut string = String::new();
+    for chunk in bytes.chunks(3) {
+        let bits = (chunk[0] as u32) << 16 |
+            (*chunk.get(1).unwrap_or(&0) as u32) << 8 |
+            *chunk.get(2).unwrap_or(&0) as u32;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                string.push(ALPHABET[(bits >> (18 - index * 6)) as usize & 63] as char);
+            } else {
+                string.push('=');
+            }
+        }
+    }
+    string
+}
+
+/// A filled outline. The outline is shared, so that placing it many times with different
+/// transforms doesn't copy its geometry.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PathObject {
-    outline: Outline,
+    outline: Arc<Outline>,
+    /// Maps the outline into the scene. It's applied before the render transform. Clip paths and
+    /// paints are in scene coordinates, so it doesn't move them.
+    transform: Transform2DF32,
     paint: PaintId,
+    fill_rule: FillRule,
+    clip_path: Option<ClipPathId>,
+    blend_mode: BlendMode,
     name: String,
 }
 
+/// An outline that clips the paths that refer to it. Paths are visible only where they are
+/// inside it according to its fill rule.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClipPath {
+    outline: Outline,
+    fill_rule: FillRule,
+}
+
+/// A handle to a path in a scene, which stays valid while paths around it are added, removed
+/// and reordered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PathId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ClipPathId(pub u32);
+
+/// A contiguous run of paths that is composited as a unit.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Group {
+    path_range: Range<u32>,
+    opacity: f32,
+    blend_mode: BlendMode,
+    parent: Option<GroupId>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GroupId(pub u32);
+
+/// Why a scene couldn't be built. Nothing is drawn for a scene that fails to build.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BuildError {
+    /// The paints need more rows of paint data than the paint texture can address.
+    TooManyPaints,
+    /// The paths and clip paths cover more partially filled tiles than fit in the mask
+    /// framebuffer.
+    TooManyAlphaTiles,
+    /// `RenderOptions::tile_size` is out of range.
+    InvalidTileSize,
+    /// `RenderOptions::flattening_tolerance` isn't positive.
+    InvalidFlatteningTolerance,
+    /// The view box spans more tiles than alpha tiles can address, which is 4,096 in each
+    /// direction. Use larger tiles or a smaller view box.
+    ViewBoxTooLarge,
+}
+
+impl Display for BuildError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            BuildError::TooManyPaints => write!(formatter, "too many paints"),
+            BuildError::TooManyAlphaTiles => write!(formatter, "too many alpha tiles"),
+            BuildError::InvalidTileSize => write!(formatter, "tile size out of range"),
+            BuildError::InvalidFlatteningTolerance => {
+                write!(formatter, "flattening tolerance isn't positive")
+            }
+            BuildError::ViewBoxTooLarge => write!(formatter, "view box spans too many tiles"),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
 impl PathObject {
     #[inline]
     pub fn new(outline: Outline, paint: PaintId, name: String) -> PathObject {
-        PathObject { outline, paint, name }
+        PathObject::from_shared_outline(Arc::new(outline), paint, name)
+    }
+
+    /// Creates a path that shares its outline with other paths. Give each of them its own
+    /// transform with `set_transform()` to draw the outline in several places.
+    #[inline]
+    pub fn from_shared_outline(outline: Arc<Outline>, paint: PaintId, name: String)
+                               -> PathObject {
+        PathObject {
+            outline,
+            transform: Transform2DF32::default(),
+            paint,
+            fill_rule: FillRule::Winding,
+            clip_path: None,
+            blend_mode: BlendMode::SrcOver,
+            name,
+        }
     }
 
     #[inline]
@@ -218,8 +1157,541 @@ impl PathObject {
         &self.outline
     }
 
+    #[inline]
+    pub fn shared_outline(&self) -> &Arc<Outline> {
+        &self.outline
+    }
+
+    #[inline]
+    pub fn transform(&self) -> Transform2DF32 {
+        self.transform
+    }
+
+    #[inline]
+    pub fn set_transform(&mut self, new_transform: Transform2DF32) {
+        self.transform = new_transform
+    }
+
+    /// Returns the bounds of the outline once it's placed in the scene with the transform.
+    #[inline]
+    pub fn bounds(&self) -> RectF32 {
+        self.transform.transform_rect(&self.outline.bounds())
+    }
+
     #[inline]
     pub(crate) fn paint(&self) -> PaintId {
         self.paint
     }
+
+    #[inline]
+    pub fn fill_rule(&self) -> FillRule {
+        self.fill_rule
+    }
+
+    #[inline]
+    pub fn set_fill_rule(&mut self, new_fill_rule: FillRule) {
+        self.fill_rule = new_fill_rule
+    }
+
+    #[inline]
+    pub fn clip_path(&self) -> Option<ClipPathId> {
+        self.clip_path
+    }
+
+    #[inline]
+    pub fn set_clip_path(&mut self, new_clip_path: Option<ClipPathId>) {
+        self.clip_path = new_clip_path
+    }
+
+    #[inline]
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    /// Sets how the path is combined with what's beneath it. Paths that aren't drawn with
+    /// source-over are rendered as if they were in a group of their own.
+    #[inline]
+    pub fn set_blend_mode(&mut self, new_blend_mode: BlendMode) {
+        self.blend_mode = new_blend_mode
+    }
+}
+
+impl ClipPath {
+    #[inline]
+    pub fn new(outline: Outline, fill_rule: FillRule) -> ClipPath {
+        ClipPath { outline, fill_rule }
+    }
+
+    #[inline]
+    pub fn outline(&self) -> &Outline {
+        &self.outline
+    }
+
+    #[inline]
+    pub fn fill_rule(&self) -> FillRule {
+        self.fill_rule
+    }
+
+    /// Returns a clip path covering the area inside both this clip path and `other`.
+    pub fn intersection(&self, other: &ClipPath) -> ClipPath {
+        self.boolean_op(other, BooleanOp::Intersection)
+    }
+
+    /// Returns a clip path covering the area inside either this clip path or `other`.
+    pub fn union(&self, other: &ClipPath) -> ClipPath {
+        self.boolean_op(other, BooleanOp::Union)
+    }
+
+    fn boolean_op(&self, other: &ClipPath, op: BooleanOp) -> ClipPath {
+        if self.fill_rule == other.fill_rule {
+            let outline = self.outline.boolean_op(&other.outline, op, self.fill_rule);
+            return ClipPath::new(outline, self.fill_rule);
+        }
+
+        // Boolean operations take a single fill rule, so resolve each operand's own rule first.
+        // The results fill the same way under either rule.
+        let empty = Outline::new();
+        let this_outline = self.outline.union(&empty, self.fill_rule);
+        let other_outline = other.outline.union(&empty, other.fill_rule);
+        ClipPath::new(this_outline.boolean_op(&other_outline, op, FillRule::Winding),
+                      FillRule::Winding)
+    }
+}
+
+impl Group {
+    /// The indices of the paths in this group, including those in nested groups.
+    #[inline]
+    pub fn path_range(&self) -> Range<u32> {
+        self.path_range.clone()
+    }
+
+    #[inline]
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    #[inline]
+    pub fn set_opacity(&mut self, new_opacity: f32) {
+        self.opacity = new_opacity
+    }
+
+    #[inline]
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    #[inline]
+    pub fn set_blend_mode(&mut self, new_blend_mode: BlendMode) {
+        self.blend_mode = new_blend_mode
+    }
+
+    #[inline]
+    pub fn parent(&self) -> Option<GroupId> {
+        self.parent
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blend::BlendMode;
+    use crate::concurrent::executor::SequentialExecutor;
+    use crate::options::{RenderOptions, RenderTransform};
+    use crate::paint::{Gradient, Paint, PaintId};
+    use pathfinder_geometry::basic::line_segment::LineSegmentF32;
+    use pathfinder_geometry::basic::point::{Point2DF32, Point2DI32};
+    use pathfinder_geometry::basic::rect::RectF32;
+    use pathfinder_geometry::basic::transform2d::Transform2DF32;
+    use pathfinder_geometry::basic::transform3d::{Perspective, Transform3DF32};
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::fill::FillRule;
+    use pathfinder_geometry::outline::Outline;
+    use std::io;
+    use std::sync::Arc;
+    use super::{BuildError, ClipPath, ClipPathId, GroupId, PathObject, Scene};
+
+    #[test]
+    fn test_write_svg() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::splat(10.0)));
+        let paint = scene.push_paint(&Paint::Color(ColorU { r: 255, g: 0, b: 128, a: 51 }));
+        let outline = Outline::from_svg_path_data("M 1 1 L 9 1 L 5 9 Z").unwrap();
+        let mut path = PathObject::new(outline, paint, "\"a\" & <b>".to_owned());
+        path.set_fill_rule(FillRule::EvenOdd);
+        scene.push_path(path);
+
+        let mut svg = vec![];
+        scene.write_svg(&mut svg).unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.contains(" id=\"&quot;a&quot; &amp; &lt;b&gt;\""), "{}", svg);
+        assert!(svg.contains(" fill=\"rgb(255, 0, 128)\" fill-opacity=\"0.2\""), "{}", svg);
+        assert!(svg.contains(" fill-rule=\"evenodd\" d=\"M 1 1 L 9 1 L 5 9 Z\""), "{}", svg);
+    }
+
+    #[test]
+    fn test_edit_paths() {
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Color(ColorU { r: 0, g: 0, b: 0, a: 255 }));
+        let other_paint = scene.push_paint(&Paint::Color(ColorU { r: 255, g: 0, b: 0, a: 255 }));
+        let square = |x: f32| {
+            let data = format!("M {} 0 L {} 0 L {} 10 Z", x, x + 10.0, x + 10.0);
+            PathObject::new(Outline::from_svg_path_data(&data).unwrap(), paint, String::new())
+        };
+
+        let a = scene.push_path(square(0.0));
+        let group = scene.push_group(0.5);
+        let b = scene.push_path(square(10.0));
+        let c = scene.push_path(square(20.0));
+        scene.pop_group();
+        assert_eq!(scene.bounds(), RectF32::from_points(Point2DF32::default(),
+                                                        Point2DF32::new(30.0, 10.0)));
+
+        // Removing a path shifts the group and shrinks the bounds.
+        assert!(scene.remove_path(c).is_some());
+        assert!(scene.remove_path(c).is_none());
+        assert_eq!(scene.groups()[group.0 as usize].path_range, 1..2);
+        assert_eq!(scene.bounds().max_x(), 20.0);
+
+        // Inserting below a path in the group joins the group; inserting below it doesn't.
+        let d = scene.insert_path_at(1, square(30.0));
+        assert_eq!(scene.groups()[group.0 as usize].path_range, 1..3);
+        let e = scene.insert_path_at(0, square(40.0));
+        assert_eq!(scene.groups()[group.0 as usize].path_range, 2..4);
+        assert_eq!(scene.bounds().max_x(), 50.0);
+
+        assert!(scene.move_path(e, 100));
+        assert!(scene.set_path_paint(a, other_paint));
+        assert_eq!(scene.replace_path(d, square(0.0)).unwrap().outline().bounds().min_x(), 30.0);
+        let order: Vec<_> = (0..scene.path_count()).map(|index| scene.path_id(index)).collect();
+        assert_eq!(order, vec![a, d, b, e]);
+        assert_eq!(scene.groups()[group.0 as usize].path_range, 1..3);
+        assert_eq!(scene.path(a).unwrap().paint(), other_paint);
+        assert_eq!(scene.bounds().max_x(), 50.0);
+        assert!(!scene.set_path_paint(a, PaintId(2)));
+        assert_eq!(scene.path(a).unwrap().paint(), other_paint);
+
+        // An empty group moves up past a path inserted at its position.
+        let empty_group = scene.push_group(1.0);
+        scene.pop_group();
+        scene.insert_path_at(4, square(0.0));
+        assert_eq!(scene.groups()[empty_group.0 as usize].path_range, 5..5);
+        scene.insert_path_at(1, square(0.0));
+        assert_eq!(scene.groups()[empty_group.0 as usize].path_range, 6..6);
+        assert_eq!(scene.groups()[group.0 as usize].path_range, 1..4);
+        assert_eq!(scene.path_index(e), Some(4));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::new(1.0, 2.0), Point2DF32::splat(10.0)));
+        let color = Paint::Color(ColorU { r: 12, g: 34, b: 56, a: 78 });
+        let paint = scene.push_paint(&color);
+        let outline = Outline::from_svg_path_data("M 1 1 L 9 1 Q 9 9 5 9 Z").unwrap();
+        let mut path = PathObject::new(outline.clone(), paint, "triangle".to_owned());
+        path.set_fill_rule(FillRule::EvenOdd);
+        let shared_outline = path.shared_outline().clone();
+        scene.push_path(path);
+
+        let mut data = vec![];
+        scene.save(&mut data).unwrap();
+        let single_path_size = data.len();
+
+        // A second path with the same outline only adds its own fields to the file.
+        let mut copy = PathObject::from_shared_outline(shared_outline, paint, String::new());
+        copy.set_transform(Transform2DF32::from_translation(Point2DF32::splat(1.0)));
+        scene.push_path(copy);
+        data.clear();
+        scene.save(&mut data).unwrap();
+        assert!(data.len() < single_path_size + 64);
+
+        let mut loaded = Scene::load(&mut &data[..]).unwrap();
+        assert_eq!(loaded.view_box(), scene.view_box());
+        assert_eq!(loaded.paths().len(), 2);
+        assert_eq!(loaded.paths()[0].name, "triangle");
+        assert_eq!(loaded.paths()[0].fill_rule(), FillRule::EvenOdd);
+        assert_eq!(loaded.paths()[0].outline().to_svg_path_data(), outline.to_svg_path_data());
+        assert!(Arc::ptr_eq(loaded.paths()[0].shared_outline(),
+                            loaded.paths()[1].shared_outline()));
+        assert_eq!(loaded.paths()[1].transform(), scene.paths()[1].transform());
+        assert_eq!(loaded.push_paint(&color), paint);
+
+        data[0] = b'X';
+        assert!(Scene::load(&mut &data[..]).is_err());
+    }
+
+    #[test]
+    fn test_load_validates_scene() {
+        fn reload(scene: &Scene) -> io::Result<Scene> {
+            let mut data = vec![];
+            scene.save(&mut data).unwrap();
+            Scene::load(&mut &data[..])
+        }
+        fn assert_invalid(scene: &Scene) {
+            let error = reload(scene).err().expect("Corrupt scene was loaded!");
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+        let outline = Outline::from_svg_path_data("M 1 1 L 9 1 L 9 9 Z").unwrap();
+        scene.push_path(PathObject::new(outline.clone(), paint, String::new()));
+        let group = scene.push_group(0.5);
+        scene.push_path(PathObject::new(outline, paint, String::new()));
+        scene.pop_group();
+
+        // Bounds are recomputed rather than read from the file.
+        let mut corrupt = scene.clone();
+        corrupt.bounds = RectF32::new(Point2DF32::splat(-1000.0), Point2DF32::splat(1.0));
+        assert_eq!(reload(&corrupt).unwrap().bounds(), scene.bounds());
+
+        let mut corrupt = scene.clone();
+        corrupt.paths[1].paint = PaintId(1);
+        assert_invalid(&corrupt);
+
+        let mut corrupt = scene.clone();
+        corrupt.paths[0].clip_path = Some(ClipPathId(0));
+        assert_invalid(&corrupt);
+
+        let mut corrupt = scene.clone();
+        corrupt.paths[0].transform = Transform2DF32::from_scale(Point2DF32::splat(f32::NAN));
+        assert_invalid(&corrupt);
+
+        let mut corrupt = scene.clone();
+        corrupt.path_ids.pop();
+        assert_invalid(&corrupt);
+
+        let mut corrupt = scene.clone();
+        corrupt.path_ids[1] = corrupt.path_ids[0];
+        assert_invalid(&corrupt);
+
+        let mut corrupt = scene.clone();
+        corrupt.groups[group.0 as usize].path_range = 1..3;
+        assert_invalid(&corrupt);
+
+        let mut corrupt = scene.clone();
+        corrupt.groups[group.0 as usize].parent = Some(group);
+        assert_invalid(&corrupt);
+
+        let mut corrupt = scene.clone();
+        corrupt.push_group(1.0);
+        corrupt.groups[1].path_range = 1..2;
+        assert_invalid(&corrupt);
+
+        let mut corrupt = scene.clone();
+        corrupt.open_groups.push(GroupId(1));
+        assert_invalid(&corrupt);
+
+        assert!(reload(&scene).is_ok());
+    }
+
+    #[test]
+    fn test_more_than_65536_paths_and_paints() {
+        const PATH_COUNT: u32 = 70000;
+
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::splat(16.0)));
+        let outline = Outline::from_svg_path_data("M 0 0 L 16 0 L 16 16 L 0 16 Z").unwrap();
+        let mut last_color = ColorU::black();
+        for path_index in 0..PATH_COUNT {
+            last_color = ColorU {
+                r: path_index as u8,
+                g: (path_index >> 8) as u8,
+                b: (path_index >> 16) as u8,
+                a: 255,
+            };
+            let paint = scene.push_paint(&Paint::Color(last_color));
+            assert_eq!(paint.0, path_index);
+            // Only the topmost path is visible, so leave the others empty to keep this quick.
+            let is_top = path_index + 1 == PATH_COUNT;
+            let outline = if is_top { outline.clone() } else { Outline::new() };
+            scene.push_path(PathObject::new(outline, paint, String::new()));
+        }
+
+        let pixels = scene.render_to_pixels(RenderOptions::default(),
+                                            Point2DI32::splat(16),
+                                            &SequentialExecutor).unwrap();
+        assert_eq!(&pixels[0..4], &[last_color.r, last_color.g, last_color.b, 255]);
+    }
+
+    #[test]
+    fn test_build_errors() {
+        fn build(scene: &Scene) -> Result<(), BuildError> {
+            scene.build(RenderOptions::default(), Box::new(|_| {}), &SequentialExecutor)
+        }
+
+        // Each of these triangles needs an alpha tile, and the mask framebuffer holds fewer.
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::splat(16.0)));
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+        let outline = Arc::new(Outline::from_svg_path_data("M 1 1 L 9 1 L 5 9 Z").unwrap());
+        for _ in 0..0x10000 {
+            scene.push_path(PathObject::from_shared_outline(outline.clone(), paint, String::new()));
+        }
+        assert_eq!(build(&scene), Err(BuildError::TooManyAlphaTiles));
+
+        // Each gradient takes two rows of paint data.
+        let mut scene = Scene::new();
+        for gradient_index in 0..0x8000 {
+            let to = Point2DF32::new(gradient_index as f32 + 1.0, 0.0);
+            let mut gradient = Gradient::linear(LineSegmentF32::new(Point2DF32::default(), to));
+            gradient.add_color_stop(0.0, ColorU::black());
+            scene.push_paint(&Paint::Gradient(gradient));
+        }
+        assert_eq!(build(&scene), Err(BuildError::TooManyPaints));
+
+        // Alpha tiles can't address tiles this far out.
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::new(4097.0, 1.0)));
+        let options = RenderOptions { tile_size: Point2DI32::splat(1), ..RenderOptions::default() };
+        let result = scene.build(options, Box::new(|_| {}), &SequentialExecutor);
+        assert_eq!(result, Err(BuildError::ViewBoxTooLarge));
+
+        let options = RenderOptions { flattening_tolerance: 0.0, ..RenderOptions::default() };
+        let result = scene.build(options, Box::new(|_| {}), &SequentialExecutor);
+        assert_eq!(result, Err(BuildError::InvalidFlatteningTolerance));
+    }
+
+    #[test]
+    fn test_subpixel_aa_with_perspective() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::new(32.0, 16.0)));
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+        let outline = Outline::from_svg_path_data("M 2.25 1 L 29 3.5 Q 20 15 5 14 Z").unwrap();
+        scene.push_path(PathObject::new(outline, paint, String::new()));
+
+        // A projection that maps the view box onto the window exactly, so it should render just
+        // like the identity transform does in 2D.
+        let window_size = Point2DI32::new(32, 16);
+        let transform = Transform3DF32::row_major(2.0 / 32.0, 0.0, 0.0, -1.0,
+                                                  0.0, -2.0 / 16.0, 0.0, 1.0,
+                                                  0.0, 0.0, 1.0, 0.0,
+                                                  0.0, 0.0, 0.0, 1.0);
+        let perspective = Perspective::new(&transform, window_size);
+
+        let size = Point2DI32::new(96, 16);
+        let options = RenderOptions { subpixel_aa_enabled: true, ..RenderOptions::default() };
+        let expected = scene.render_to_pixels(options.clone(), size, &SequentialExecutor).unwrap();
+        let options = RenderOptions {
+            transform: RenderTransform::Perspective(perspective),
+            ..options
+        };
+        let pixels = scene.render_to_pixels(options, size, &SequentialExecutor).unwrap();
+        for (&pixel, &expected_pixel) in pixels.iter().zip(expected.iter()) {
+            assert!((pixel as i32 - expected_pixel as i32).abs() <= 2);
+        }
+    }
+
+    #[test]
+    fn test_path_transforms() {
+        let outline = Outline::from_svg_path_data("M 0 0 L 8 1 Q 6 7 1 6 Z").unwrap();
+        let shared_outline = Arc::new(outline.clone());
+        let transforms = [
+            Transform2DF32::from_translation(Point2DF32::new(3.0, 4.0)),
+            Transform2DF32::from_scale_rotation_translation(Point2DF32::new(1.5, 0.5),
+                                                            0.7,
+                                                            Point2DF32::new(20.0, 6.0)),
+            Transform2DF32::from_scale_rotation_translation(Point2DF32::splat(2.0),
+                                                            -2.0,
+                                                            Point2DF32::new(12.0, 28.0)),
+        ];
+
+        // One scene places the shared outline with path transforms, and the other bakes the
+        // transforms into copies of it.
+        let (mut placed_scene, mut baked_scene) = (Scene::new(), Scene::new());
+        for scene in &mut [&mut placed_scene, &mut baked_scene] {
+            scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::splat(64.0)));
+        }
+        let placed_paint = placed_scene.push_paint(&Paint::Color(ColorU::black()));
+        let baked_paint = baked_scene.push_paint(&Paint::Color(ColorU::black()));
+        for transform in &transforms {
+            let mut path = PathObject::from_shared_outline(shared_outline.clone(),
+                                                           placed_paint,
+                                                           String::new());
+            path.set_transform(*transform);
+            placed_scene.push_path(path);
+
+            let mut baked_outline = outline.clone();
+            baked_outline.transform(transform);
+            baked_scene.push_path(PathObject::new(baked_outline, baked_paint, String::new()));
+        }
+        assert!(Arc::ptr_eq(placed_scene.paths()[0].shared_outline(), &shared_outline));
+        // Transforming the bounds of the outline can only make them looser.
+        assert!(placed_scene.bounds().contains_rect(baked_scene.bounds()));
+
+        // The render transform doesn't commute with the path transforms, so this checks that
+        // they're applied in the right order.
+        let transform = Transform2DF32::from_scale(Point2DF32::new(1.5, 1.0))
+            .post_mul(&Transform2DF32::from_translation(Point2DF32::new(-5.0, 2.0)));
+        let options = RenderOptions {
+            transform: RenderTransform::Transform2D(transform),
+            ..RenderOptions::default()
+        };
+        let size = Point2DI32::splat(64);
+        let pixels = placed_scene.render_to_pixels(options.clone(), size, &SequentialExecutor);
+        let expected = baked_scene.render_to_pixels(options.clone(), size, &SequentialExecutor);
+        let (pixels, expected) = (pixels.unwrap(), expected.unwrap());
+        for (&pixel, &expected_pixel) in pixels.iter().zip(expected.iter()) {
+            assert!((pixel as i32 - expected_pixel as i32).abs() <= 1);
+        }
+
+        for &(x, y) in &[(10.0, 8.0), (28.0, 9.0), (20.0, 40.0), (40.0, 40.0)] {
+            let point = Point2DF32::new(x, y);
+            assert_eq!(placed_scene.hit_test(point, &options).unwrap(),
+                       baked_scene.hit_test(point, &options).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_hit_test() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::splat(32.0)));
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+        for &blend_mode in &[BlendMode::SrcOver, BlendMode::DestOut] {
+            let outline = Outline::from_svg_path_data("M 0 0 L 10 0 L 10 10 L 0 10 Z").unwrap();
+            let mut path = PathObject::new(outline, paint, String::new());
+            path.set_blend_mode(blend_mode);
+            scene.push_path(path);
+        }
+
+        // The erasing path on top doesn't count.
+        let (point, outside) = (Point2DF32::new(9.5, 5.0), Point2DF32::new(11.0, 5.0));
+        assert_eq!(scene.hit_test(point, &RenderOptions::default()).unwrap(), Some(0));
+        assert_eq!(scene.hit_test(outside, &RenderOptions::default()).unwrap(), None);
+
+        // Subpixel AA stretches the scene internally, but `point` stays in view box space.
+        let options = RenderOptions { subpixel_aa_enabled: true, ..RenderOptions::default() };
+        assert_eq!(scene.hit_test(point, &options).unwrap(), Some(0));
+        assert_eq!(scene.hit_test(outside, &options).unwrap(), None);
+
+        let options = RenderOptions {
+            dilation: Point2DF32::splat(2.0),
+            ..RenderOptions::default()
+        };
+        assert_eq!(scene.hit_test(outside, &options).unwrap(), Some(0));
+
+        let options = RenderOptions { tile_size: Point2DI32::splat(0), ..RenderOptions::default() };
+        assert_eq!(scene.hit_test(point, &options), Err(BuildError::InvalidTileSize));
+    }
+
+    #[test]
+    fn test_combine_clip_paths_with_different_fill_rules() {
+        // Under even-odd this is a ring; under nonzero it would be the whole outer square.
+        let ring = Outline::from_svg_path_data("M 0 0 L 10 0 L 10 10 L 0 10 Z \
+                                                M 3 3 L 7 3 L 7 7 L 3 7 Z").unwrap();
+        let ring = ClipPath::new(ring, FillRule::EvenOdd);
+        let band = Outline::from_svg_path_data("M 0 4 L 10 4 L 10 6 L 0 6 Z").unwrap();
+        let band = ClipPath::new(band, FillRule::Winding);
+
+        let contains = |clip_path: &ClipPath, x, y| {
+            clip_path.outline().contains_point(Point2DF32::new(x, y), clip_path.fill_rule())
+        };
+        let intersection = ring.intersection(&band);
+        assert!(contains(&intersection, 1.0, 5.0));
+        assert!(!contains(&intersection, 5.0, 5.0));
+        assert!(!contains(&intersection, 1.0, 1.0));
+        let union = ring.union(&band);
+        assert!(contains(&union, 1.0, 1.0));
+        assert!(contains(&union, 5.0, 5.0));
+        assert!(!contains(&union, 5.0, 3.5));
+    }
 }
@@ -0,0 +1,55 @@
+// pathfinder/renderer/src/test_util.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Helpers shared by the unit tests.
+
+use crate::concurrent::executor::SequentialExecutor;
+use crate::cpu::renderer::Renderer;
+use crate::options::RenderOptions;
+use crate::scene::Scene;
+use crate::tile_cache::TileCache;
+use pathfinder_geometry::basic::rect::RectF32;
+use pathfinder_geometry::outline::{Contour, Outline};
+use std::sync::{Arc, Mutex};
+
+pub(crate) fn rect_outline(rect: RectF32) -> Outline {
+    let mut contour = Contour::new();
+    contour.push_endpoint(rect.origin());
+    contour.push_endpoint(rect.upper_right());
+    contour.push_endpoint(rect.lower_right());
+    contour.push_endpoint(rect.lower_left());
+    contour.close();
+    let mut outline = Outline::new();
+    outline.push_contour(contour);
+    outline
+}
+
+// Builds the scene, through the tile cache if one is given, and draws it on the CPU into an image
+// the size of the view box.
+pub(crate) fn render(scene: &Scene, options: RenderOptions, tile_cache: Option<&mut TileCache>)
+                     -> Renderer {
+    let commands = Arc::new(Mutex::new(vec![]));
+    let listener_commands = commands.clone();
+    let listener = Box::new(move |command| listener_commands.lock().unwrap().push(command));
+    let result = match tile_cache {
+        None => scene.build(options, listener, &SequentialExecutor),
+        Some(tile_cache) => {
+            scene.build_with_tile_cache(options, listener, &SequentialExecutor, tile_cache)
+        }
+    };
+    result.unwrap();
+
+    let mut renderer = Renderer::new(scene.view_box().size().to_i32());
+    renderer.begin_scene();
+    for command in commands.lock().unwrap().iter() {
+        renderer.render_command(command);
+    }
+    renderer
+}
@@ -0,0 +1,212 @@
+// pathfinder/renderer/src/tile_cache.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Keeps the tiles of paths from one build to the next, so that paths that haven't changed
+//! needn't be clipped and tiled again.
+
+use crate::gpu_data::BuiltObject;
+use crate::scene::PathId;
+use hashbrown::HashMap;
+use pathfinder_geometry::basic::point::{Point2DF32, Point2DI32};
+use pathfinder_geometry::basic::rect::{RectF32, RectI32};
+use pathfinder_geometry::basic::transform2d::Transform2DF32;
+use pathfinder_geometry::outline::Outline;
+use std::sync::Arc;
+
+// How far from a whole number of tiles a translation may be, in pixels, for cached tiles to be
+// moved instead of rebuilt. Fills aren't any more precise than this.
+const TRANSLATION_EPSILON: f32 = 1.0 / 256.0;
+
+/// The tiles of the paths of the last build, for `Scene::build_with_tile_cache()`.
+///
+/// The tiles of a path are reused if neither its outline nor the transform, tile size and
+/// flattening tolerance it's rendered with have changed. The transform combines the path's own
+/// transform with the render transform. Tiles are also reused if the transform has only moved by
+/// a whole number of tiles and the path lies entirely inside the view box, with no clip path.
+/// Perspective transforms aren't cached.
+#[derive(Default)]
+pub struct TileCache {
+    entries: HashMap<PathId, Arc<CachedTiles>>,
+    reused_path_count: usize,
+}
+
+impl TileCache {
+    #[inline]
+    pub fn new() -> TileCache {
+        TileCache::default()
+    }
+
+    /// Discards all cached tiles.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.reused_path_count = 0;
+    }
+
+    /// Returns the number of paths whose tiles the last build reused.
+    #[inline]
+    pub fn reused_path_count(&self) -> usize {
+        self.reused_path_count
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, path_id: PathId) -> Option<&Arc<CachedTiles>> {
+        self.entries.get(&path_id)
+    }
+
+    pub(crate) fn replace_entries(&mut self,
+                                  entries: Vec<(PathId, Arc<CachedTiles>)>,
+                                  reused_path_count: usize) {
+        self.entries = entries.into_iter().collect();
+        self.reused_path_count = reused_path_count;
+    }
+}
+
+/// How an outline is transformed and clipped before it's tiled.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct TilingParams {
+    pub(crate) transform: Transform2DF32,
+    pub(crate) dilation: Point2DF32,
+    pub(crate) view_box: RectF32,
+    pub(crate) clip_rect: Option<RectF32>,
+    pub(crate) tile_size: Point2DI32,
+    pub(crate) flattening_tolerance: f32,
+    /// True if neither the view box nor the clip rect cuts anything off the outline.
+    pub(crate) unclipped: bool,
+}
+
+pub(crate) struct CachedTiles {
+    pub(crate) outline: Arc<Outline>,
+    pub(crate) params: TilingParams,
+    /// The fills and tiles, with the alpha tiles numbered from zero.
+    pub(crate) built_object: BuiltObject,
+}
+
+impl CachedTiles {
+    /// Returns the number of tiles that these tiles must be moved by to be those of `outline`
+    /// tiled with `params`, or `None` if they can't be reused.
+    pub(crate) fn tile_offset(&self, outline: &Arc<Outline>, params: &TilingParams)
+                              -> Option<Point2DI32> {
+        let (old_transform, new_transform) = (&self.params.transform, &params.transform);
+        if self.params.dilation != params.dilation ||
+                self.params.tile_size != params.tile_size ||
+                self.params.flattening_tolerance != params.flattening_tolerance ||
+                old_transform.m11() != new_transform.m11() ||
+                old_transform.m12() != new_transform.m12() ||
+                old_transform.m21() != new_transform.m21() ||
+                old_transform.m22() != new_transform.m22() ||
+                !(Arc::ptr_eq(&self.outline, outline) || self.outline == *outline) {
+            return None;
+        }
+
+        if self.params == *params {
+            return Some(Point2DI32::default());
+        }
+        if !self.params.unclipped || !params.unclipped {
+            return None;
+        }
+
+        let offset = new_transform.translation() - old_transform.translation();
+        let tile_size = params.tile_size.to_f32();
+        let tile_offset = Point2DI32::new((offset.x() / tile_size.x()).round() as i32,
+                                          (offset.y() / tile_size.y()).round() as i32);
+        let error = offset - tile_offset.to_f32().scale_xy(tile_size);
+        if f32::abs(error.x()) > TRANSLATION_EPSILON || f32::abs(error.y()) > TRANSLATION_EPSILON {
+            return None;
+        }
+        Some(tile_offset)
+    }
+
+    /// Returns a copy of the cached fills and tiles, moved by `tile_offset` tiles.
+    pub(crate) fn to_built_object(&self, tile_offset: Point2DI32) -> BuiltObject {
+        let mut built_object = self.built_object.clone();
+        if tile_offset != Point2DI32::default() {
+            let tile_rect = built_object.tiles.rect;
+            built_object.tiles.rect = RectI32::new(tile_rect.origin() + tile_offset,
+                                                   tile_rect.size());
+            let tile_size = built_object.tile_size.to_f32();
+            let bounds = built_object.bounds;
+            built_object.bounds = RectF32::new(bounds.origin() +
+                                               tile_offset.to_f32().scale_xy(tile_size),
+                                               bounds.size());
+        }
+        built_object
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::options::{RenderOptions, RenderTransform};
+    use crate::paint::Paint;
+    use crate::scene::{PathObject, Scene};
+    use crate::test_util::render;
+    use pathfinder_geometry::basic::point::Point2DF32;
+    use pathfinder_geometry::basic::rect::RectF32;
+    use pathfinder_geometry::basic::transform2d::Transform2DF32;
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::outline::Outline;
+    use super::TileCache;
+
+    fn render_translated(scene: &Scene,
+                         translation: Point2DF32,
+                         tile_cache: Option<&mut TileCache>)
+                         -> Vec<u8> {
+        let transform = Transform2DF32::from_translation(translation);
+        let options = RenderOptions {
+            transform: RenderTransform::Transform2D(transform),
+            ..RenderOptions::default()
+        };
+        render(scene, options, tile_cache).read_pixels()
+    }
+
+    #[test]
+    fn test_tile_cache() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF32::new(Point2DF32::default(), Point2DF32::splat(128.0)));
+        let red = scene.push_paint(&Paint::Color(ColorU { r: 255, g: 0, b: 0, a: 255 }));
+        let blue = scene.push_paint(&Paint::Color(ColorU { r: 0, g: 0, b: 255, a: 128 }));
+        let outline = Outline::from_svg_path_data("M 10.5 10 L 60 14 Q 50 50 12 40 Z").unwrap();
+        scene.push_path(PathObject::new(outline, red, String::new()));
+        let outline = Outline::from_svg_path_data("M 30 20 L 70 30 L 40 60 Z").unwrap();
+        let path_id = scene.push_path(PathObject::new(outline, blue, String::new()));
+
+        let mut tile_cache = TileCache::new();
+        let origin = Point2DF32::default();
+        let uncached = render_translated(&scene, origin, None);
+        assert_eq!(render_translated(&scene, origin, Some(&mut tile_cache)), uncached);
+        assert_eq!(tile_cache.reused_path_count(), 0);
+        assert_eq!(render_translated(&scene, origin, Some(&mut tile_cache)), uncached);
+        assert_eq!(tile_cache.reused_path_count(), 2);
+
+        // Paints can change without retiling.
+        scene.set_path_paint(path_id, red);
+        let uncached = render_translated(&scene, origin, None);
+        assert_eq!(render_translated(&scene, origin, Some(&mut tile_cache)), uncached);
+        assert_eq!(tile_cache.reused_path_count(), 2);
+
+        // So can translations by whole tiles, but not by anything else.
+        let translation = Point2DF32::new(32.0, 16.0);
+        let uncached = render_translated(&scene, translation, None);
+        assert_eq!(render_translated(&scene, translation, Some(&mut tile_cache)), uncached);
+        assert_eq!(tile_cache.reused_path_count(), 2);
+        let translation = Point2DF32::new(33.0, 16.0);
+        let uncached = render_translated(&scene, translation, None);
+        assert_eq!(render_translated(&scene, translation, Some(&mut tile_cache)), uncached);
+        assert_eq!(tile_cache.reused_path_count(), 0);
+
+        // Paths that are cut off by the view box aren't moved.
+        let translation = Point2DF32::new(81.0, 16.0);
+        render_translated(&scene, translation, Some(&mut tile_cache));
+        let translation = Point2DF32::new(97.0, 16.0);
+        let uncached = render_translated(&scene, translation, None);
+        assert_eq!(render_translated(&scene, translation, Some(&mut tile_cache)), uncached);
+        assert_eq!(tile_cache.reused_path_count(), 0);
+    }
+}
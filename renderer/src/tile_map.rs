@@ -11,7 +11,7 @@
 use pathfinder_geometry::basic::point::Point2DI32;
 use pathfinder_geometry::basic::rect::RectI32;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct DenseTileMap<T> {
     pub data: Vec<T>,
     pub rect: RectI32,
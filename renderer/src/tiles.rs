@@ -15,24 +15,30 @@ use crate::sorted_vector::SortedVector;
 use pathfinder_geometry::basic::line_segment::LineSegmentF32;
 use pathfinder_geometry::basic::point::{Point2DF32, Point2DI32};
 use pathfinder_geometry::basic::rect::{RectF32, RectI32};
+use pathfinder_geometry::fill::FillRule;
 use pathfinder_geometry::outline::{Contour, Outline, PointIndex};
 use pathfinder_geometry::segment::Segment;
 use std::cmp::Ordering;
 use std::mem;
 
-// TODO(pcwalton): Make this configurable.
-const FLATTENING_TOLERANCE: f32 = 0.1;
+pub const DEFAULT_FLATTENING_TOLERANCE: f32 = 0.1;
 
-pub const TILE_WIDTH: u32 = 16;
-pub const TILE_HEIGHT: u32 = 16;
+pub const DEFAULT_TILE_WIDTH: u32 = 16;
+pub const DEFAULT_TILE_HEIGHT: u32 = 16;
+
+/// The largest tile width or height. The mask framebuffer holds up to 256×256 tiles, so at this
+/// size it's a 16384×16384 R16F texture, which takes 512 MB. Where the GPU can't make textures
+/// that large, the mask holds fewer tiles, and scenes that need more are rejected by the
+/// renderer.
+pub const MAX_TILE_SIZE: u32 = 64;
+
+// Alpha tiles store their coordinates in 12 bits each, so they can't reach tiles this far out.
+pub(crate) const MAX_ALPHA_TILE_COORD: i32 = 4096;
 
 pub(crate) struct Tiler<'a> {
-    builder: &'a SceneBuilder<'a>,
     outline: &'a Outline,
     pub built_object: BuiltObject,
-    paint_id: PaintId,
-    object_index: u16,
-    object_is_opaque: bool,
+    flattening_tolerance: f32,
 
     point_queue: SortedVector<QueuedEndpoint>,
     active_edges: SortedVector<ActiveEdge>,
@@ -41,27 +47,22 @@ pub(crate) struct Tiler<'a> {
 
 impl<'a> Tiler<'a> {
     #[allow(clippy::or_fun_call)]
-    pub(crate) fn new(
-        builder: &'a SceneBuilder<'a>,
-        outline: &'a Outline,
-        view_box: RectF32,
-        object_index: u16,
-        paint_id: PaintId,
-        object_is_opaque: bool,
-    ) -> Tiler<'a> {
+    pub(crate) fn new(outline: &'a Outline,
+                      view_box: RectF32,
+                      fill_rule: FillRule,
+                      tile_size: Point2DI32,
+                      flattening_tolerance: f32)
+                      -> Tiler<'a> {
         let bounds = outline
             .bounds()
             .intersection(view_box)
             .unwrap_or(RectF32::default());
-        let built_object = BuiltObject::new(bounds);
+        let built_object = BuiltObject::new(bounds, fill_rule, tile_size);
 
         Tiler {
-            builder,
             outline,
             built_object,
-            object_index,
-            paint_id,
-            object_is_opaque,
+            flattening_tolerance,
 
             point_queue: SortedVector::new(),
             active_edges: SortedVector::new(),
@@ -69,7 +70,9 @@ impl<'a> Tiler<'a> {
         }
     }
 
-    pub(crate) fn generate_tiles(&mut self) {
+    /// Computes the fills and backdrops of the tiles without packing them into alpha tiles. The
+    /// alpha tiles of the object are numbered from zero.
+    pub(crate) fn generate_fills(&mut self) {
         // Initialize the point queue.
         self.init_point_queue();
 
@@ -82,12 +85,6 @@ impl<'a> Tiler<'a> {
         for strip_origin_y in tile_rect.min_y()..tile_rect.max_y() {
             self.generate_strip(strip_origin_y);
         }
-
-        // Pack and cull.
-        self.pack_and_cull();
-
-        // Done!
-        debug!("{:#?}", self.built_object);
     }
 
     fn generate_strip(&mut self, strip_origin_y: i32) {
@@ -95,7 +92,8 @@ impl<'a> Tiler<'a> {
         self.process_old_active_edges(strip_origin_y);
 
         // Add new active edges.
-        let strip_max_y = ((i32::from(strip_origin_y) + 1) * TILE_HEIGHT as i32) as f32;
+        let tile_height = self.built_object.tile_size.y();
+        let strip_max_y = ((i32::from(strip_origin_y) + 1) * tile_height) as f32;
         while let Some(queued_endpoint) = self.point_queue.peek() {
             // We're done when we see an endpoint that belongs to the next tile strip.
             //
@@ -109,39 +107,6 @@ impl<'a> Tiler<'a> {
         }
     }
 
-    fn pack_and_cull(&mut self) {
-        for (tile_index, tile) in self.built_object.tiles.data.iter().enumerate() {
-            let tile_coords = self
-                .built_object
-                .local_tile_index_to_coords(tile_index as u32);
-
-            if tile.is_solid() {
-                // Blank tiles are always skipped.
-                if tile.backdrop == 0 {
-                    continue;
-                }
-
-                // If this is a solid tile, poke it into the Z-buffer and stop here.
-                if self.object_is_opaque {
-                    self.builder.z_buffer.update(tile_coords, self.object_index);
-                    continue;
-                }
-            }
-
-            let origin_uv = paint::paint_id_to_tex_coords(self.paint_id);
-
-            let alpha_tile = AlphaTileBatchPrimitive::new(
-                tile_coords,
-                tile.backdrop,
-                self.object_index,
-                tile.alpha_tile_index as u16,
-                origin_uv,
-            );
-
-            self.built_object.alpha_tiles.push(alpha_tile);
-        }
-    }
-
     fn process_old_active_edges(&mut self, tile_y: i32) {
         let mut current_tile_x = self.built_object.tile_rect().min_x();
         let mut current_subtile_x = 0.0;
@@ -153,7 +118,8 @@ impl<'a> Tiler<'a> {
         // FIXME(pcwalton): Yuck.
         let mut last_segment_x = -9999.0;
 
-        let tile_top = (i32::from(tile_y) * TILE_HEIGHT as i32) as f32;
+        let tile_size = self.built_object.tile_size;
+        let tile_top = (i32::from(tile_y) * tile_size.y()) as f32;
 
         debug!("---------- tile y {}({}) ----------", tile_y, tile_top);
         debug!("old active edges: {:#?}", self.old_active_edges);
@@ -189,14 +155,13 @@ impl<'a> Tiler<'a> {
             last_segment_x = segment_x;
 
             // Do initial subtile fill, if necessary.
-            let segment_tile_x = f32::floor(segment_x) as i32 / TILE_WIDTH as i32;
+            let segment_tile_x = f32::floor(segment_x) as i32 / tile_size.x();
             if current_tile_x < segment_tile_x && current_subtile_x > 0.0 {
                 let current_x =
-                    (i32::from(current_tile_x) * TILE_WIDTH as i32) as f32 + current_subtile_x;
-                let tile_right_x = ((i32::from(current_tile_x) + 1) * TILE_WIDTH as i32) as f32;
+                    (i32::from(current_tile_x) * tile_size.x()) as f32 + current_subtile_x;
+                let tile_right_x = ((i32::from(current_tile_x) + 1) * tile_size.x()) as f32;
                 let current_tile_coords = Point2DI32::new(current_tile_x, tile_y);
                 self.built_object.add_active_fill(
-                    self.builder,
                     current_x,
                     tile_right_x,
                     current_winding,
@@ -229,13 +194,12 @@ impl<'a> Tiler<'a> {
             // Do final subtile fill, if necessary.
             debug_assert_eq!(current_tile_x, segment_tile_x);
             let segment_subtile_x =
-                segment_x - (i32::from(current_tile_x) * TILE_WIDTH as i32) as f32;
+                segment_x - (i32::from(current_tile_x) * tile_size.x()) as f32;
             if segment_subtile_x > current_subtile_x {
                 let current_x =
-                    (i32::from(current_tile_x) * TILE_WIDTH as i32) as f32 + current_subtile_x;
+                    (i32::from(current_tile_x) * tile_size.x()) as f32 + current_subtile_x;
                 let current_tile_coords = Point2DI32::new(current_tile_x, tile_y);
                 self.built_object.add_active_fill(
-                    self.builder,
                     current_x,
                     segment_x,
                     current_winding,
@@ -250,7 +214,7 @@ impl<'a> Tiler<'a> {
             // Process the edge.
             debug!("about to process existing active edge {:#?}", active_edge);
             debug_assert!(f32::abs(active_edge.crossing.y() - tile_top) < 0.1);
-            active_edge.process(self.builder, &mut self.built_object, tile_y);
+            active_edge.process(&mut self.built_object, tile_y, self.flattening_tolerance);
             if !active_edge.segment.is_none() {
                 self.active_edges.push(active_edge);
             }
@@ -286,9 +250,9 @@ impl<'a> Tiler<'a> {
                 contour,
                 prev_endpoint_index,
                 &mut self.active_edges,
-                self.builder,
                 &mut self.built_object,
                 tile_y,
+                self.flattening_tolerance,
             );
 
             self.point_queue.push(QueuedEndpoint {
@@ -310,9 +274,9 @@ impl<'a> Tiler<'a> {
                 contour,
                 point_index.point(),
                 &mut self.active_edges,
-                self.builder,
                 &mut self.built_object,
                 tile_y,
+                self.flattening_tolerance,
             );
 
             self.point_queue.push(QueuedEndpoint {
@@ -354,10 +318,65 @@ impl<'a> Tiler<'a> {
     }
 }
 
-pub fn round_rect_out_to_tile_bounds(rect: RectF32) -> RectI32 {
+impl BuiltObject {
+    /// Packs the tiles of this object into alpha tiles, skipping those outside its clip path, and
+    /// marks the solid tiles of opaque objects in the Z-buffer.
+    pub(crate) fn pack_and_cull(&mut self,
+                                builder: &SceneBuilder,
+                                object_index: u32,
+                                paint_id: PaintId,
+                                object_is_opaque: bool,
+                                clip_path: Option<&BuiltObject>) {
+        for (tile_index, tile) in self.tiles.data.iter().enumerate() {
+            let tile_coords = self.local_tile_index_to_coords(tile_index as u32);
+
+            // Tiles outside the clip path are skipped, and tiles on its edge need a mask.
+            let clip_tile = match clip_path {
+                None => ClipTile::Inside,
+                Some(clip_path) => ClipTile::from_clip_path(clip_path, tile_coords),
+            };
+            let clip_is_inside = match clip_tile {
+                ClipTile::Inside => true,
+                ClipTile::Outside => continue,
+                ClipTile::Mask { .. } => false,
+            };
+
+            if tile.is_solid() {
+                // Blank tiles are always skipped.
+                if !self.fill_rule.winding_is_inside(tile.backdrop as i32) {
+                    continue;
+                }
+
+                // If this is a solid tile, poke it into the Z-buffer and stop here.
+                if object_is_opaque && clip_is_inside {
+                    builder.z_buffer_for_path(object_index).update(tile_coords, object_index);
+                    continue;
+                }
+            }
+
+            let origin_uv = paint::paint_id_to_tex_coords(paint_id);
+
+            let mut alpha_tile = AlphaTileBatchPrimitive::new(
+                tile_coords,
+                tile.backdrop,
+                object_index,
+                tile.alpha_tile_index as u16,
+                origin_uv,
+                self.fill_rule,
+            );
+            if let ClipTile::Mask { tile: clip_tile, fill_rule } = clip_tile {
+                alpha_tile.set_clip(clip_tile.alpha_tile_index, clip_tile.backdrop, fill_rule);
+            }
+
+            self.alpha_tiles.push(alpha_tile);
+        }
+    }
+}
+
+pub fn round_rect_out_to_tile_bounds(rect: RectF32, tile_size: Point2DI32) -> RectI32 {
     rect.scale_xy(Point2DF32::new(
-        1.0 / TILE_WIDTH as f32,
-        1.0 / TILE_HEIGHT as f32,
+        1.0 / tile_size.x() as f32,
+        1.0 / tile_size.y() as f32,
     ))
     .round_out()
     .to_i32()
@@ -367,13 +386,13 @@ fn process_active_segment(
     contour: &Contour,
     from_endpoint_index: u32,
     active_edges: &mut SortedVector<ActiveEdge>,
-    builder: &SceneBuilder,
     built_object: &mut BuiltObject,
     tile_y: i32,
+    flattening_tolerance: f32,
 ) {
     let mut active_edge = ActiveEdge::from_segment(&contour.segment_after(from_endpoint_index));
     debug!("... process_active_segment({:#?})", active_edge);
-    active_edge.process(builder, built_object, tile_y);
+    active_edge.process(built_object, tile_y, flattening_tolerance);
     if !active_edge.segment.is_none() {
         debug!("... ... pushing resulting active edge: {:#?}", active_edge);
         active_edges.push(active_edge);
@@ -423,8 +442,8 @@ impl ActiveEdge {
         }
     }
 
-    fn process(&mut self, builder: &SceneBuilder, built_object: &mut BuiltObject, tile_y: i32) {
-        let tile_bottom = ((i32::from(tile_y) + 1) * TILE_HEIGHT as i32) as f32;
+    fn process(&mut self, built_object: &mut BuiltObject, tile_y: i32, flattening_tolerance: f32) {
+        let tile_bottom = ((i32::from(tile_y) + 1) * built_object.tile_size.y()) as f32;
         debug!(
             "process_active_edge({:#?}, tile_y={}({}))",
             self, tile_y, tile_bottom
@@ -436,7 +455,7 @@ impl ActiveEdge {
         if segment.is_line() {
             let line_segment = segment.as_line_segment();
             self.segment =
-                match self.process_line_segment(&line_segment, builder, built_object, tile_y) {
+                match self.process_line_segment(&line_segment, built_object, tile_y) {
                     Some(lower_part) => Segment::line(&lower_part),
                     None => Segment::none(),
                 };
@@ -453,7 +472,7 @@ impl ActiveEdge {
             let first_line_segment =
                 LineSegmentF32::new(self.crossing, segment.baseline.upper_point()).orient(winding);
             if self
-                .process_line_segment(&first_line_segment, builder, built_object, tile_y)
+                .process_line_segment(&first_line_segment, built_object, tile_y)
                 .is_some()
             {
                 return;
@@ -468,7 +487,7 @@ impl ActiveEdge {
 
             while !before_segment
                 .as_cubic_segment()
-                .is_flat(FLATTENING_TOLERANCE)
+                .is_flat(flattening_tolerance)
             {
                 let next_t = 0.5 * split_t;
                 let (before, after) = oriented_segment.as_cubic_segment().split(next_t);
@@ -484,7 +503,7 @@ impl ActiveEdge {
             );
 
             let line = before_segment.baseline.orient(winding);
-            match self.process_line_segment(&line, builder, built_object, tile_y) {
+            match self.process_line_segment(&line, built_object, tile_y) {
                 Some(ref lower_part) if split_t == 1.0 => {
                     self.segment = Segment::line(&lower_part);
                     return;
@@ -505,23 +524,22 @@ impl ActiveEdge {
     fn process_line_segment(
         &mut self,
         line_segment: &LineSegmentF32,
-        builder: &SceneBuilder,
         built_object: &mut BuiltObject,
         tile_y: i32,
     ) -> Option<LineSegmentF32> {
-        let tile_bottom = ((i32::from(tile_y) + 1) * TILE_HEIGHT as i32) as f32;
+        let tile_bottom = ((i32::from(tile_y) + 1) * built_object.tile_size.y()) as f32;
         debug!(
             "process_line_segment({:?}, tile_y={}) tile_bottom={}",
             line_segment, tile_y, tile_bottom
         );
 
         if line_segment.max_y() <= tile_bottom {
-            built_object.generate_fill_primitives_for_line(builder, *line_segment, tile_y);
+            built_object.generate_fill_primitives_for_line(*line_segment, tile_y);
             return None;
         }
 
         let (upper_part, lower_part) = line_segment.split_at_y(tile_bottom);
-        built_object.generate_fill_primitives_for_line(builder, upper_part, tile_y);
+        built_object.generate_fill_primitives_for_line(upper_part, tile_y);
         self.crossing = lower_part.upper_point();
         Some(lower_part)
     }
@@ -533,14 +551,40 @@ impl PartialOrd<ActiveEdge> for ActiveEdge {
     }
 }
 
+// How a tile of a path is affected by its clip path.
+#[derive(Clone, Copy, Debug)]
+enum ClipTile {
+    Inside,
+    Outside,
+    Mask { tile: TileObjectPrimitive, fill_rule: FillRule },
+}
+
+impl ClipTile {
+    fn from_clip_path(clip_path: &BuiltObject, tile_coords: Point2DI32) -> ClipTile {
+        let tile = match clip_path.tile_coords_to_local_index(tile_coords) {
+            None => return ClipTile::Outside,
+            Some(tile_index) => clip_path.tiles.data[tile_index as usize],
+        };
+        if !tile.is_solid() {
+            ClipTile::Mask { tile, fill_rule: clip_path.fill_rule }
+        } else if clip_path.fill_rule.winding_is_inside(tile.backdrop as i32) {
+            ClipTile::Inside
+        } else {
+            ClipTile::Outside
+        }
+    }
+}
+
 impl AlphaTileBatchPrimitive {
     #[inline]
     fn new(tile_coords: Point2DI32,
            backdrop: i8,
-           object_index: u16,
+           object_index: u32,
            tile_index: u16,
-           origin_uv: Point2DI32)
+           origin_uv: Point2DI32,
+           fill_rule: FillRule)
            -> AlphaTileBatchPrimitive {
+        // `Scene::build()` rejects view boxes with tiles past `MAX_ALPHA_TILE_COORD`.
         AlphaTileBatchPrimitive {
             tile_x_lo: (tile_coords.x() & 0xff) as u8,
             tile_y_lo: (tile_coords.y() & 0xff) as u8,
@@ -550,9 +594,20 @@ impl AlphaTileBatchPrimitive {
             tile_index,
             origin_u: origin_uv.x() as u16,
             origin_v: origin_uv.y() as u16,
+            fill_rule: fill_rule_to_u8(fill_rule),
+            clip_fill_rule: 0,
+            clip_tile_index: !0,
+            clip_backdrop: 1,
         }
     }
 
+    #[inline]
+    fn set_clip(&mut self, clip_tile_index: u16, clip_backdrop: i8, clip_fill_rule: FillRule) {
+        self.clip_tile_index = clip_tile_index;
+        self.clip_backdrop = clip_backdrop;
+        self.clip_fill_rule = fill_rule_to_u8(clip_fill_rule);
+    }
+
     #[inline]
     pub fn tile_coords(&self) -> Point2DI32 {
         Point2DI32::new(
@@ -562,6 +617,13 @@ impl AlphaTileBatchPrimitive {
     }
 }
 
+fn fill_rule_to_u8(fill_rule: FillRule) -> u8 {
+    match fill_rule {
+        FillRule::Winding => 0,
+        FillRule::EvenOdd => 1,
+    }
+}
+
 impl Default for TileObjectPrimitive {
     #[inline]
     fn default() -> TileObjectPrimitive {
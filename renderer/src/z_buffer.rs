@@ -25,8 +25,8 @@ pub struct ZBuffer {
 }
 
 impl ZBuffer {
-    pub fn new(view_box: RectF32) -> ZBuffer {
-        let tile_rect = tiles::round_rect_out_to_tile_bounds(view_box);
+    pub fn new(view_box: RectF32, tile_size: Point2DI32) -> ZBuffer {
+        let tile_rect = tiles::round_rect_out_to_tile_bounds(view_box, tile_size);
         ZBuffer {
             buffer: DenseTileMap::from_builder(|_| AtomicUsize::new(0), tile_rect),
         }
@@ -38,7 +38,7 @@ impl ZBuffer {
         existing_depth < object_index as usize + 1
     }
 
-    pub fn update(&self, coords: Point2DI32, object_index: u16) {
+    pub fn update(&self, coords: Point2DI32, object_index: u32) {
         let tile_index = self.buffer.coords_to_index_unchecked(coords);
         let mut old_depth = self.buffer.data[tile_index].load(AtomicOrdering::SeqCst);
         let new_depth = (object_index + 1) as usize;
@@ -74,7 +74,7 @@ impl ZBuffer {
             let origin_uv = paint::paint_id_to_tex_coords(paths[object_index as usize].paint());
 
             solid_tiles.push(SolidTileBatchPrimitive::new(tile_coords + self.buffer.rect.origin(),
-                                                          object_index as u16,
+                                                          object_index,
                                                           origin_uv));
         }
 
@@ -83,7 +83,7 @@ impl ZBuffer {
 }
 
 impl SolidTileBatchPrimitive {
-    fn new(tile_coords: Point2DI32, object_index: u16, origin_uv: Point2DI32)
+    fn new(tile_coords: Point2DI32, object_index: u32, origin_uv: Point2DI32)
            -> SolidTileBatchPrimitive {
         SolidTileBatchPrimitive {
             tile_x: tile_coords.x() as i16,
@@ -0,0 +1,205 @@
+#version {{version}}
+
+// pathfinder/resources/shaders/composite.fs.glsl
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Composites a group onto the target beneath it. Groups hold premultiplied color. Source-over
+// is left to hardware blending; the other modes read the backdrop and replace it.
+//
+// This must match `blend.rs`.
+
+precision highp float;
+
+#define BLEND_MODE_SRC_OVER     0
+#define BLEND_MODE_CLEAR        1
+#define BLEND_MODE_COPY         2
+#define BLEND_MODE_SRC_IN       3
+#define BLEND_MODE_SRC_OUT      4
+#define BLEND_MODE_SRC_ATOP     5
+#define BLEND_MODE_DEST_OVER    6
+#define BLEND_MODE_DEST_IN      7
+#define BLEND_MODE_DEST_OUT     8
+#define BLEND_MODE_DEST_ATOP    9
+#define BLEND_MODE_XOR          10
+#define BLEND_MODE_LIGHTER      11
+#define BLEND_MODE_MULTIPLY     12
+#define BLEND_MODE_SCREEN       13
+#define BLEND_MODE_OVERLAY      14
+#define BLEND_MODE_DARKEN       15
+#define BLEND_MODE_LIGHTEN      16
+#define BLEND_MODE_COLOR_DODGE  17
+#define BLEND_MODE_COLOR_BURN   18
+#define BLEND_MODE_HARD_LIGHT   19
+#define BLEND_MODE_SOFT_LIGHT   20
+#define BLEND_MODE_DIFFERENCE   21
+#define BLEND_MODE_EXCLUSION    22
+#define BLEND_MODE_HUE          23
+#define BLEND_MODE_SATURATION   24
+#define BLEND_MODE_COLOR        25
+#define BLEND_MODE_LUMINOSITY   26
+
+uniform sampler2D uSource;
+uniform sampler2D uBackdrop;
+uniform float uOpacity;
+uniform int uBlendMode;
+
+in vec2 vTexCoord;
+
+out vec4 oFragColor;
+
+float screen(float src, float dest) {
+    return dest + src - dest * src;
+}
+
+float hardLight(float src, float dest) {
+    return src <= 0.5 ? dest * 2.0 * src : screen(2.0 * src - 1.0, dest);
+}
+
+float colorDodge(float src, float dest) {
+    if (dest == 0.0)
+        return 0.0;
+    if (src >= 1.0)
+        return 1.0;
+    return min(1.0, dest / (1.0 - src));
+}
+
+float colorBurn(float src, float dest) {
+    if (dest >= 1.0)
+        return 1.0;
+    if (src == 0.0)
+        return 0.0;
+    return 1.0 - min(1.0, (1.0 - dest) / src);
+}
+
+float softLight(float src, float dest) {
+    if (src <= 0.5)
+        return dest - (1.0 - 2.0 * src) * dest * (1.0 - dest);
+    float d = dest <= 0.25 ? ((16.0 * dest - 12.0) * dest + 4.0) * dest : sqrt(dest);
+    return dest + (2.0 * src - 1.0) * (d - dest);
+}
+
+float lum(vec3 color) {
+    return dot(color, vec3(0.3, 0.59, 0.11));
+}
+
+float sat(vec3 color) {
+    return max(color.r, max(color.g, color.b)) - min(color.r, min(color.g, color.b));
+}
+
+vec3 clipColor(vec3 color) {
+    float l = lum(color);
+    float n = min(color.r, min(color.g, color.b));
+    float x = max(color.r, max(color.g, color.b));
+    if (n < 0.0)
+        color = l + (color - l) * l / (l - n);
+    if (x > 1.0)
+        color = l + (color - l) * (1.0 - l) / (x - l);
+    return color;
+}
+
+vec3 setLum(vec3 color, float newLum) {
+    return clipColor(color + (newLum - lum(color)));
+}
+
+vec3 setSat(vec3 color, float newSat) {
+    float n = min(color.r, min(color.g, color.b));
+    float x = max(color.r, max(color.g, color.b));
+    return x > n ? (color - n) * newSat / (x - n) : vec3(0.0);
+}
+
+// Applies a blend mode to unpremultiplied colors.
+vec3 blend(vec3 src, vec3 dest, int blendMode) {
+    switch (blendMode) {
+    case BLEND_MODE_MULTIPLY:
+        return src * dest;
+    case BLEND_MODE_SCREEN:
+        return dest + src - dest * src;
+    case BLEND_MODE_OVERLAY:
+        return vec3(hardLight(dest.r, src.r), hardLight(dest.g, src.g), hardLight(dest.b, src.b));
+    case BLEND_MODE_DARKEN:
+        return min(src, dest);
+    case BLEND_MODE_LIGHTEN:
+        return max(src, dest);
+    case BLEND_MODE_COLOR_DODGE:
+        return vec3(colorDodge(src.r, dest.r),
+                    colorDodge(src.g, dest.g),
+                    colorDodge(src.b, dest.b));
+    case BLEND_MODE_COLOR_BURN:
+        return vec3(colorBurn(src.r, dest.r), colorBurn(src.g, dest.g), colorBurn(src.b, dest.b));
+    case BLEND_MODE_HARD_LIGHT:
+        return vec3(hardLight(src.r, dest.r), hardLight(src.g, dest.g), hardLight(src.b, dest.b));
+    case BLEND_MODE_SOFT_LIGHT:
+        return vec3(softLight(src.r, dest.r), softLight(src.g, dest.g), softLight(src.b, dest.b));
+    case BLEND_MODE_DIFFERENCE:
+        return abs(dest - src);
+    case BLEND_MODE_EXCLUSION:
+        return dest + src - 2.0 * dest * src;
+    case BLEND_MODE_HUE:
+        return setLum(setSat(src, sat(dest)), lum(dest));
+    case BLEND_MODE_SATURATION:
+        return setLum(setSat(dest, sat(src)), lum(dest));
+    case BLEND_MODE_COLOR:
+        return setLum(src, lum(dest));
+    case BLEND_MODE_LUMINOSITY:
+        return setLum(dest, lum(src));
+    }
+    return src;
+}
+
+// The Porter-Duff factors of the source and the backdrop.
+vec2 porterDuffFactors(float srcAlpha, float destAlpha, int blendMode) {
+    switch (blendMode) {
+    case BLEND_MODE_CLEAR:
+        return vec2(0.0, 0.0);
+    case BLEND_MODE_COPY:
+        return vec2(1.0, 0.0);
+    case BLEND_MODE_SRC_IN:
+        return vec2(destAlpha, 0.0);
+    case BLEND_MODE_SRC_OUT:
+        return vec2(1.0 - destAlpha, 0.0);
+    case BLEND_MODE_SRC_ATOP:
+        return vec2(destAlpha, 1.0 - srcAlpha);
+    case BLEND_MODE_DEST_OVER:
+        return vec2(1.0 - destAlpha, 1.0);
+    case BLEND_MODE_DEST_IN:
+        return vec2(0.0, srcAlpha);
+    case BLEND_MODE_DEST_OUT:
+        return vec2(0.0, 1.0 - srcAlpha);
+    case BLEND_MODE_DEST_ATOP:
+        return vec2(1.0 - destAlpha, srcAlpha);
+    case BLEND_MODE_XOR:
+        return vec2(1.0 - destAlpha, 1.0 - srcAlpha);
+    case BLEND_MODE_LIGHTER:
+        return vec2(1.0, 1.0);
+    }
+    return vec2(1.0, 1.0 - srcAlpha);
+}
+
+void main() {
+    vec4 src = texture(uSource, vTexCoord) * uOpacity;
+    if (uBlendMode == BLEND_MODE_SRC_OVER) {
+        oFragColor = src;
+        return;
+    }
+
+    vec4 dest = texture(uBackdrop, vTexCoord);
+
+    // Blend modes mix the source color with the backdrop where both are present.
+    vec3 srcColor = src.rgb;
+    if (uBlendMode >= BLEND_MODE_MULTIPLY && src.a > 0.0 && dest.a > 0.0) {
+        vec3 srcStraight = src.rgb / src.a, destStraight = dest.rgb / dest.a;
+        vec3 blended = blend(srcStraight, destStraight, uBlendMode);
+        srcColor = mix(srcStraight, blended, dest.a) * src.a;
+    }
+
+    vec2 factors = porterDuffFactors(src.a, dest.a, uBlendMode);
+    vec4 result = vec4(srcColor, src.a) * factors.x + dest * factors.y;
+    oFragColor = clamp(result, 0.0, 1.0);
+}
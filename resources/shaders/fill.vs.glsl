@@ -16,8 +16,8 @@ uniform vec2 uFramebufferSize;
 uniform vec2 uTileSize;
 
 in vec2 aTessCoord;
-in uint aFromPx;
-in uint aToPx;
+in uvec2 aFromPx;
+in uvec2 aToPx;
 in vec2 aFromSubpx;
 in vec2 aToSubpx;
 in uint aTileIndex;
@@ -34,8 +34,8 @@ vec2 computeTileOffset(uint tileIndex, float stencilTextureWidth) {
 void main() {
     vec2 tileOrigin = computeTileOffset(aTileIndex, uFramebufferSize.x);
 
-    vec2 from = vec2(aFromPx & 15u, aFromPx >> 4u) + aFromSubpx;
-    vec2 to = vec2(aToPx & 15u, aToPx >> 4u) + aToSubpx;
+    vec2 from = vec2(aFromPx) + aFromSubpx;
+    vec2 to = vec2(aToPx) + aToSubpx;
 
     vec2 position;
     if (aTessCoord.x < 0.5)
@@ -12,15 +12,36 @@
 
 precision highp float;
 
+{{{include_tile_paint}}}
+
 uniform sampler2D uStencilTexture;
 
 in vec2 vTexCoord;
 in float vBackdrop;
+in float vFillRule;
+in vec2 vClipTexCoord;
+in float vClipBackdrop;
+in float vClipFillRule;
 in vec4 vColor;
 
 out vec4 oFragColor;
 
+// Nonzero winding if 0, even-odd if 1.
+float computeCoverage(float winding, float fillRule) {
+    if (fillRule < 0.5)
+        return min(abs(winding), 1.0);
+    return 1.0 - abs(1.0 - mod(abs(winding), 2.0));
+}
+
 void main() {
-    float coverage = abs(texture(uStencilTexture, vTexCoord).r + vBackdrop);
-    oFragColor = vec4(vColor.rgb, vColor.a * coverage);
+    float winding = texture(uStencilTexture, vTexCoord).r + vBackdrop;
+    float coverage = computeCoverage(winding, vFillRule);
+
+    float clipWinding = vClipBackdrop;
+    if (vClipTexCoord.x >= 0.0)
+        clipWinding += texture(uStencilTexture, vClipTexCoord).r;
+    coverage *= computeCoverage(clipWinding, vClipFillRule);
+
+    vec4 color = getPaintColor(vColor);
+    oFragColor = vec4(color.rgb, color.a * coverage);
 }
@@ -17,12 +17,23 @@ in vec2 aTessCoord;
 in uvec3 aTileOrigin;
 in int aBackdrop;
 in uint aTileIndex;
+in uint aFillRule;
+in uint aClipFillRule;
+in uint aClipTileIndex;
+in int aClipBackdrop;
 
 out vec2 vTexCoord;
 out float vBackdrop;
+out float vFillRule;
+out vec2 vClipTexCoord;
+out float vClipBackdrop;
+out float vClipFillRule;
 out vec4 vColor;
+out vec2 vPaintPosition;
+flat out vec4 vPaintInfo;
+flat out vec4 vPaintRect;
 
-vec4 getColor();
+vec4 getColor(vec2 tilePosition);
 
 vec2 computeTileOffset(uint tileIndex, float stencilTextureWidth) {
     uint tilesPerRow = uint(stencilTextureWidth / uTileSize.x);
@@ -32,14 +43,27 @@ vec2 computeTileOffset(uint tileIndex, float stencilTextureWidth) {
 
 void computeVaryings() {
     vec2 origin = vec2(aTileOrigin.xy) + vec2(aTileOrigin.z & 15u, aTileOrigin.z >> 4u) * 256.0;
-    vec2 pixelPosition = (origin + aTessCoord) * uTileSize + uViewBoxOrigin;
+    vec2 tilePosition = (origin + aTessCoord) * uTileSize;
+    vec2 pixelPosition = tilePosition + uViewBoxOrigin;
     vec2 position = (pixelPosition / uFramebufferSize * 2.0 - 1.0) * vec2(1.0, -1.0);
     vec2 maskTexCoordOrigin = computeTileOffset(aTileIndex, uStencilTextureSize.x);
     vec2 maskTexCoord = maskTexCoordOrigin + aTessCoord * uTileSize;
 
     vTexCoord = maskTexCoord / uStencilTextureSize;
     vBackdrop = float(aBackdrop);
-    vColor = getColor();
+    vFillRule = float(aFillRule);
+
+    // A clip tile index of 0xffff means that the clip path has no mask in this tile, in which case
+    // its coverage comes from the backdrop alone.
+    if (aClipTileIndex == 0xffffu) {
+        vClipTexCoord = vec2(-1.0);
+    } else {
+        vec2 clipTexCoordOrigin = computeTileOffset(aClipTileIndex, uStencilTextureSize.x);
+        vClipTexCoord = (clipTexCoordOrigin + aTessCoord * uTileSize) / uStencilTextureSize;
+    }
+    vClipBackdrop = float(aClipBackdrop);
+    vClipFillRule = float(aClipFillRule);
+    vColor = getColor(tilePosition);
     gl_Position = vec4(position, 0.0, 1.0);
 }
 
@@ -10,6 +10,9 @@
 
 uniform vec4 uColor;
 
-vec4 getColor() {
+vec4 getColor(vec2 tilePosition) {
+    vPaintPosition = vec2(0.0);
+    vPaintInfo = vec4(0.0);
+    vPaintRect = vec4(0.0);
     return uColor;
 }
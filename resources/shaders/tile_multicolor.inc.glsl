@@ -11,8 +11,53 @@
 uniform sampler2D uPaintTexture;
 uniform vec2 uPaintTextureSize;
 
+// The texel coordinates of the paint record. See `Scene::build_paint_data()`.
 in vec2 aColorTexCoord;
 
-vec4 getColor() {
-    return texture(uPaintTexture, aColorTexCoord);
+uvec4 fetchPaintBytes(ivec2 texelCoord) {
+    return uvec4(texelFetch(uPaintTexture, texelCoord, 0) * 255.0 + 0.5);
+}
+
+float fetchPaintFloat(ivec2 texelCoord) {
+    uvec4 bytes = fetchPaintBytes(texelCoord);
+    return uintBitsToFloat(bytes.x | (bytes.y << 8u) | (bytes.z << 16u) | (bytes.w << 24u));
+}
+
+vec4 getColor(vec2 tilePosition) {
+    ivec2 recordCoord = ivec2(aColorTexCoord);
+    uvec4 header = fetchPaintBytes(recordCoord);
+
+    // Solid colors.
+    if (header.x == 0u) {
+        vPaintPosition = vec2(0.0);
+        vPaintInfo = vec4(0.0);
+        vPaintRect = vec4(0.0);
+        return texelFetch(uPaintTexture, recordCoord + ivec2(1, 0), 0);
+    }
+
+    // The first data row starts with the transform from tile space to paint space.
+    int row = int(header.z | (header.w << 8u));
+    mat2 paintMatrix = mat2(fetchPaintFloat(ivec2(0, row)),
+                            fetchPaintFloat(ivec2(1, row)),
+                            fetchPaintFloat(ivec2(2, row)),
+                            fetchPaintFloat(ivec2(3, row)));
+    vec2 paintVector = vec2(fetchPaintFloat(ivec2(4, row)), fetchPaintFloat(ivec2(5, row)));
+    vPaintPosition = paintMatrix * tilePosition + paintVector;
+
+    // Patterns. The rest of the row holds the rectangle of the image in the atlas and the
+    // opacity.
+    if (header.x == 3u) {
+        vPaintInfo = vec4(float(header.x), float(header.y), 0.0, fetchPaintFloat(ivec2(10, row)));
+        vPaintRect = vec4(fetchPaintFloat(ivec2(6, row)),
+                          fetchPaintFloat(ivec2(7, row)),
+                          fetchPaintFloat(ivec2(8, row)),
+                          fetchPaintFloat(ivec2(9, row)));
+        return vec4(0.0);
+    }
+
+    // Gradients. The rest of the row holds the focus, and the next row holds the color ramp.
+    float rampTexCoordY = (float(row) + 1.5) / uPaintTextureSize.y;
+    vPaintInfo = vec4(float(header.x), float(header.y), rampTexCoordY, 0.0);
+    vPaintRect = vec4(fetchPaintFloat(ivec2(6, row)), fetchPaintFloat(ivec2(7, row)), 0.0, 0.0);
+    return vec4(0.0);
 }
@@ -0,0 +1,86 @@
+// pathfinder/resources/shaders/tile_paint.inc.glsl
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+uniform sampler2D uPaintTexture;
+uniform sampler2D uPaintAtlas;
+
+in vec2 vPaintPosition;
+flat in vec4 vPaintInfo;
+flat in vec4 vPaintRect;
+
+// Fetches a texel of a pattern's image, premultiplied, wrapping on the axes along which the
+// pattern repeats. Outside the image on the other axes, the pattern is transparent.
+vec4 fetchPatternTexel(ivec2 texelCoord, ivec2 imageSize, bvec2 repeats) {
+    if (repeats.x)
+        texelCoord.x = int(mod(float(texelCoord.x), float(imageSize.x)));
+    if (repeats.y)
+        texelCoord.y = int(mod(float(texelCoord.y), float(imageSize.y)));
+    if (any(lessThan(texelCoord, ivec2(0))) || any(greaterThanEqual(texelCoord, imageSize)))
+        return vec4(0.0);
+    vec4 texel = texelFetch(uPaintAtlas, ivec2(vPaintRect.xy) + texelCoord, 0);
+    return vec4(texel.rgb * texel.a, texel.a);
+}
+
+// Samples a pattern with bilinear filtering. `vPaintRect` holds the origin and size of its image
+// in the atlas, and `vPaintInfo.w` holds its opacity.
+vec4 getPatternColor() {
+    ivec2 imageSize = ivec2(vPaintRect.zw);
+    int repeatMode = int(vPaintInfo.y + 0.5);
+    bvec2 repeats = bvec2((repeatMode & 1) != 0, (repeatMode & 2) != 0);
+
+    vec2 position = vPaintPosition - 0.5;
+    ivec2 texelCoord = ivec2(floor(position));
+    vec2 t = position - floor(position);
+    vec4 upper = mix(fetchPatternTexel(texelCoord, imageSize, repeats),
+                     fetchPatternTexel(texelCoord + ivec2(1, 0), imageSize, repeats),
+                     t.x);
+    vec4 lower = mix(fetchPatternTexel(texelCoord + ivec2(0, 1), imageSize, repeats),
+                     fetchPatternTexel(texelCoord + ivec2(1, 1), imageSize, repeats),
+                     t.x);
+    vec4 color = mix(upper, lower, t.y);
+
+    // The texels were premultiplied so that transparent ones don't darken their neighbors, but
+    // paints are blended with straight alpha, so convert back.
+    if (color.a > 0.0)
+        color.rgb /= color.a;
+    return vec4(color.rgb, color.a * vPaintInfo.w);
+}
+
+// `vPaintInfo` holds the paint kind, the spread method or repeat mode, and the vertical texture
+// coordinate of the color ramp. The kind, spread method, and repeat mode must match the values
+// in `paint.rs`.
+vec4 getPaintColor(vec4 color) {
+    if (vPaintInfo.x < 0.5)
+        return color;
+    if (vPaintInfo.x > 2.5)
+        return getPatternColor();
+
+    float t;
+    if (vPaintInfo.x < 1.5) {
+        // Linear gradients run from (0, 0) to (1, 0).
+        t = vPaintPosition.x;
+    } else {
+        // Radial gradients are unit circles around the origin. Find the circle, interpolated
+        // between the focus and the outer circle, that passes through this point.
+        vec2 focus = vPaintRect.xy, delta = vPaintPosition - focus;
+        float a = dot(focus, focus) - 1.0, b = dot(delta, focus), c = dot(delta, delta);
+        t = (-b - sqrt(b * b - a * c)) / a;
+    }
+
+    if (vPaintInfo.y < 0.5)
+        t = clamp(t, 0.0, 1.0);
+    else if (vPaintInfo.y < 1.5)
+        t = 1.0 - abs(mod(t, 2.0) - 1.0);
+    else
+        t = fract(t);
+
+    float rampWidth = float(textureSize(uPaintTexture, 0).x);
+    return texture(uPaintTexture, vec2((t * (rampWidth - 1.0) + 0.5) / rampWidth, vPaintInfo.z));
+}
@@ -12,10 +12,12 @@
 
 precision highp float;
 
+{{{include_tile_paint}}}
+
 in vec4 vColor;
 
 out vec4 oFragColor;
 
 void main() {
-    oFragColor = vColor;
+    oFragColor = getPaintColor(vColor);
 }
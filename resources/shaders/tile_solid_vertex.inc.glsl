@@ -16,14 +16,18 @@ in vec2 aTessCoord;
 in vec2 aTileOrigin;
 
 out vec4 vColor;
+out vec2 vPaintPosition;
+flat out vec4 vPaintInfo;
+flat out vec4 vPaintRect;
 
-vec4 getColor();
+vec4 getColor(vec2 tilePosition);
 
 void computeVaryings() {
-    vec2 pixelPosition = (aTileOrigin + aTessCoord) * uTileSize + uViewBoxOrigin;
+    vec2 tilePosition = (aTileOrigin + aTessCoord) * uTileSize;
+    vec2 pixelPosition = tilePosition + uViewBoxOrigin;
     vec2 position = (pixelPosition / uFramebufferSize * 2.0 - 1.0) * vec2(1.0, -1.0);
 
-    vColor = getColor();
+    vColor = getColor(tilePosition);
     //vColor = vec4(1.0, 0.0, 0.0, 1.0);
     gl_Position = vec4(position, 0.0, 1.0);
 }
@@ -14,23 +14,34 @@
 extern crate bitflags;
 
 use pathfinder_geometry::basic::line_segment::LineSegmentF32;
-use pathfinder_geometry::basic::point::Point2DF32;
+use pathfinder_geometry::basic::point::{Point2DF32, Point2DI32};
 use pathfinder_geometry::basic::rect::RectF32;
-use pathfinder_geometry::basic::transform2d::{Transform2DF32, Transform2DF32PathIter};
+use pathfinder_geometry::basic::transform2d::Transform2DF32;
 use pathfinder_geometry::color::ColorU;
+use pathfinder_geometry::fill::FillRule;
 use pathfinder_geometry::outline::Outline;
 use pathfinder_geometry::segment::{Segment, SegmentFlags};
 use pathfinder_geometry::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
-use pathfinder_renderer::paint::Paint;
-use pathfinder_renderer::scene::{PathObject, Scene};
+use pathfinder_renderer::concurrent::executor::SequentialExecutor;
+use pathfinder_renderer::options::RenderOptions;
+use pathfinder_renderer::paint::{Gradient, GradientGeometry, Image, Paint, Pattern, RepeatMode};
+use pathfinder_renderer::paint::SpreadMethod;
+use pathfinder_renderer::scene::{ClipPath, ClipPathId, PathObject, Scene};
 use std::fmt::{Display, Formatter, Result as FormatResult};
 use std::mem;
-use usvg::{Color as SvgColor, LineCap as UsvgLineCap, LineJoin as UsvgLineJoin, Node, NodeExt};
+use std::sync::Arc;
+use usvg::{Align, AspectRatio, Color as SvgColor, FillRule as UsvgFillRule, Image as UsvgImage};
+use usvg::ImageData;
+use usvg::{ImageFormat, LineCap as UsvgLineCap, LineJoin as UsvgLineJoin, Node, NodeExt};
 use usvg::{NodeKind, Opacity, Paint as UsvgPaint, PathSegment as UsvgPathSegment};
-use usvg::{Rect as UsvgRect, Transform as UsvgTransform, Tree, Visibility};
+use usvg::{Rect as UsvgRect, SpreadMethod as UsvgSpreadMethod, Transform as UsvgTransform};
+use usvg::{Pattern as UsvgPattern, Tree, Units, Visibility};
 
 const HAIRLINE_STROKE_WIDTH: f32 = 0.0333;
 
+// Pattern tiles are rasterized at no more than this many pixels on a side.
+const MAX_PATTERN_TILE_SIZE: f32 = 2048.0;
+
 pub struct BuiltSVG {
     pub scene: Scene,
     pub result_flags: BuildResultFlags,
@@ -73,7 +84,7 @@ impl BuiltSVG {
             NodeKind::Svg(ref svg) => {
                 built_svg.scene.set_view_box(usvg_rect_to_euclid_rect(&svg.view_box.rect));
                 for kid in root.children() {
-                    built_svg.process_node(&kid, &global_transform);
+                    built_svg.process_node(&tree, &kid, &global_transform, None);
                 }
             }
             _ => unreachable!(),
@@ -86,15 +97,27 @@ impl BuiltSVG {
         built_svg
     }
 
-    fn process_node(&mut self, node: &Node, transform: &Transform2DF32) {
+    fn process_node(&mut self,
+                    tree: &Tree,
+                    node: &Node,
+                    transform: &Transform2DF32,
+                    clip_path: Option<ClipPathId>) {
         let node_transform = usvg_transform_to_transform_2d(&node.transform());
         let transform = transform.pre_mul(&node_transform);
 
         match *node.borrow() {
             NodeKind::Group(ref group) => {
-                if group.clip_path.is_some() {
-                    self.result_flags
-                        .insert(BuildResultFlags::UNSUPPORTED_CLIP_PATH_ATTR);
+                let mut clip_path = clip_path;
+                if let Some(ref clip_path_id) = group.clip_path {
+                    if let Some(mut new_clip_path) =
+                            self.svg_clip_path(tree, clip_path_id, &transform) {
+                        if let Some(outer_clip_path) = clip_path {
+                            let outer_clip_path =
+                                &self.scene.clip_paths()[outer_clip_path.0 as usize];
+                            new_clip_path = outer_clip_path.intersection(&new_clip_path);
+                        }
+                        clip_path = Some(self.scene.push_clip_path(new_clip_path));
+                    }
                 }
                 if group.filter.is_some() {
                     self.result_flags
@@ -104,46 +127,71 @@ impl BuiltSVG {
                     self.result_flags
                         .insert(BuildResultFlags::UNSUPPORTED_MASK_ATTR);
                 }
-                if group.opacity.is_some() {
-                    self.result_flags
-                        .insert(BuildResultFlags::UNSUPPORTED_OPACITY_ATTR);
+                // usvg doesn't pass `mix-blend-mode` through, so groups always blend normally.
+                if let Some(opacity) = group.opacity {
+                    self.scene.push_group(opacity.value() as f32);
                 }
 
                 for kid in node.children() {
-                    self.process_node(&kid, &transform)
+                    self.process_node(tree, &kid, &transform, clip_path)
+                }
+
+                if group.opacity.is_some() {
+                    self.scene.pop_group();
                 }
             }
             NodeKind::Path(ref path) if path.visibility == Visibility::Visible => {
                 if let Some(ref fill) = path.fill {
+                    let path = UsvgPathToSegments::new(path.segments.iter().cloned());
+                    let mut outline = Outline::from_segments(path);
+                    let bounding_box = outline.bounds();
+                    outline.transform(&transform);
+
                     let style = self.scene.push_paint(&Paint::from_svg_paint(
+                        tree,
                         &fill.paint,
                         fill.opacity,
+                        bounding_box,
+                        &transform,
                         &mut self.result_flags,
                     ));
 
-                    let path = UsvgPathToSegments::new(path.segments.iter().cloned());
-                    let path = Transform2DF32PathIter::new(path, &transform);
-                    let outline = Outline::from_segments(path);
-
                     let name = format!("Fill({})", node.id());
-                    self.scene.push_path(PathObject::new(outline, style, name));
+                    let mut path_object = PathObject::new(outline, style, name);
+                    path_object.set_fill_rule(FillRule::from_usvg_fill_rule(fill.rule));
+                    path_object.set_clip_path(clip_path);
+                    self.scene.push_path(path_object);
                 }
 
                 if let Some(ref stroke) = path.stroke {
-                    let style = self.scene.push_paint(&Paint::from_svg_paint(
-                        &stroke.paint,
-                        stroke.opacity,
-                        &mut self.result_flags,
-                    ));
-
                     let stroke_style = StrokeStyle {
                         line_width: f32::max(stroke.width.value() as f32, HAIRLINE_STROKE_WIDTH),
                         line_cap: LineCap::from_usvg_line_cap(stroke.linecap),
                         line_join: LineJoin::from_usvg_line_join(stroke.linejoin),
+                        miter_limit: stroke.miterlimit.value() as f32,
+                        dash_array: match stroke.dasharray {
+                            Some(ref dash_array) => {
+                                let dash_array: Vec<_> =
+                                    dash_array.iter().map(|&length| length as f32).collect();
+                                dash_array.into()
+                            }
+                            None => Arc::new([]),
+                        },
+                        dash_offset: stroke.dashoffset as f32,
                     };
 
                     let path = UsvgPathToSegments::new(path.segments.iter().cloned());
                     let outline = Outline::from_segments(path);
+                    let bounding_box = outline.bounds();
+
+                    let style = self.scene.push_paint(&Paint::from_svg_paint(
+                        tree,
+                        &stroke.paint,
+                        stroke.opacity,
+                        bounding_box,
+                        &transform,
+                        &mut self.result_flags,
+                    ));
 
                     let mut stroke_to_fill = OutlineStrokeToFill::new(outline, stroke_style);
                     stroke_to_fill.offset();
@@ -151,44 +199,58 @@ impl BuiltSVG {
                     outline.transform(&transform);
 
                     let name = format!("Stroke({})", node.id());
-                    self.scene.push_path(PathObject::new(outline, style, name));
+                    let mut path_object = PathObject::new(outline, style, name);
+                    path_object.set_clip_path(clip_path);
+                    self.scene.push_path(path_object);
                 }
             }
             NodeKind::Path(..) => {}
             NodeKind::ClipPath(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_CLIP_PATH_NODE);
+                // Clip paths are looked up when groups refer to them.
             }
             NodeKind::Defs { .. } => {
-                if node.has_children() {
-                    self.result_flags
-                        .insert(BuildResultFlags::UNSUPPORTED_DEFS_NODE);
+                for kid in node.children() {
+                    self.process_node(tree, &kid, &transform, None)
                 }
             }
             NodeKind::Filter(..) => {
                 self.result_flags
                     .insert(BuildResultFlags::UNSUPPORTED_FILTER_NODE);
             }
-            NodeKind::Image(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_IMAGE_NODE);
+            NodeKind::Image(ref image) if image.visibility == Visibility::Visible => {
+                let pixels = match decode_usvg_image(image) {
+                    Some(pixels) => pixels,
+                    None => {
+                        self.result_flags.insert(BuildResultFlags::UNSUPPORTED_IMAGE_NODE);
+                        return;
+                    }
+                };
+
+                let dest_rect = usvg_rect_to_euclid_rect(&image.view_box.rect);
+                let image_rect = RectF32::new(Point2DF32::default(), pixels.size().to_f32());
+                let image_transform =
+                    view_box_to_transform(image_rect, &image.view_box.aspect, dest_rect);
+                let mut pattern = Pattern::new(pixels, RepeatMode::NoRepeat);
+                pattern.set_transform(image_transform.post_mul(&transform));
+                let style = self.scene.push_paint(&Paint::Pattern(pattern));
+
+                let mut outline = Outline::from_segments(rect_to_segments(dest_rect));
+                outline.transform(&transform);
+                let name = format!("Image({})", node.id());
+                let mut path_object = PathObject::new(outline, style, name);
+                path_object.set_clip_path(clip_path);
+                self.scene.push_path(path_object);
             }
-            NodeKind::LinearGradient(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_LINEAR_GRADIENT_NODE);
+            NodeKind::Image(..) => {}
+            NodeKind::LinearGradient(..) |
+            NodeKind::RadialGradient(..) |
+            NodeKind::Pattern(..) => {
+                // Paint servers are looked up when paths refer to them.
             }
             NodeKind::Mask(..) => {
                 self.result_flags
                     .insert(BuildResultFlags::UNSUPPORTED_MASK_NODE);
             }
-            NodeKind::Pattern(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_PATTERN_NODE);
-            }
-            NodeKind::RadialGradient(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_RADIAL_GRADIENT_NODE);
-            }
             NodeKind::Svg(..) => {
                 self.result_flags
                     .insert(BuildResultFlags::UNSUPPORTED_NESTED_SVG_NODE);
@@ -201,6 +263,74 @@ impl BuiltSVG {
     }
 }
 
+impl BuiltSVG {
+    // Converts the `<clipPath>` element with the given ID to a clip path. Its children are
+    // united, each according to its own clip rule, and the result is intersected with the clip
+    // path of the `<clipPath>` itself, if any.
+    fn svg_clip_path(&mut self, tree: &Tree, id: &str, transform: &Transform2DF32)
+                     -> Option<ClipPath> {
+        let node = match tree.defs_by_id(id) {
+            Some(node) => node,
+            None => {
+                self.result_flags.insert(BuildResultFlags::UNSUPPORTED_CLIP_PATH_ATTR);
+                return None;
+            }
+        };
+
+        let kind = node.borrow();
+        let usvg_clip_path = match *kind {
+            NodeKind::ClipPath(ref usvg_clip_path) => usvg_clip_path,
+            _ => {
+                self.result_flags.insert(BuildResultFlags::UNSUPPORTED_CLIP_PATH_ATTR);
+                return None;
+            }
+        };
+
+        // Clip paths in object bounding box units would need the bounds of each path they clip.
+        if let Units::ObjectBoundingBox = usvg_clip_path.units {
+            self.result_flags.insert(BuildResultFlags::UNSUPPORTED_CLIP_PATH_ATTR);
+            return None;
+        }
+
+        let clip_transform =
+            transform.pre_mul(&usvg_transform_to_transform_2d(&usvg_clip_path.transform));
+        let mut clip_path: Option<ClipPath> = None;
+        for kid in node.children() {
+            let kid_transform =
+                clip_transform.pre_mul(&usvg_transform_to_transform_2d(&kid.transform()));
+            match *kid.borrow() {
+                NodeKind::Path(ref path) if path.visibility == Visibility::Visible => {
+                    let segments = UsvgPathToSegments::new(path.segments.iter().cloned());
+                    let mut kid_outline = Outline::from_segments(segments);
+                    kid_outline.transform(&kid_transform);
+
+                    let kid_fill_rule = path.fill
+                                            .as_ref()
+                                            .map_or(UsvgFillRule::NonZero, |fill| fill.rule);
+                    let kid_clip_path =
+                        ClipPath::new(kid_outline, FillRule::from_usvg_fill_rule(kid_fill_rule));
+                    clip_path = Some(match clip_path {
+                        None => kid_clip_path,
+                        Some(clip_path) => clip_path.union(&kid_clip_path),
+                    });
+                }
+                NodeKind::Path(..) => {}
+                _ => self.result_flags.insert(BuildResultFlags::UNSUPPORTED_CLIP_PATH_NODE),
+            }
+        }
+
+        // An empty `<clipPath>` clips everything away.
+        let mut clip_path =
+            clip_path.unwrap_or_else(|| ClipPath::new(Outline::new(), FillRule::Winding));
+        if let Some(ref outer_id) = usvg_clip_path.clip_path {
+            if let Some(outer_clip_path) = self.svg_clip_path(tree, outer_id, transform) {
+                clip_path = clip_path.intersection(&outer_clip_path);
+            }
+        }
+        Some(clip_path)
+    }
+}
+
 impl Display for BuildResultFlags {
     fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
         if self.is_empty() {
@@ -244,27 +374,195 @@ impl Display for BuildResultFlags {
 }
 
 trait PaintExt {
-    fn from_svg_paint(svg_paint: &UsvgPaint, opacity: Opacity, result_flags: &mut BuildResultFlags)
+    fn from_svg_paint(tree: &Tree,
+                      svg_paint: &UsvgPaint,
+                      opacity: Opacity,
+                      bounding_box: RectF32,
+                      transform: &Transform2DF32,
+                      result_flags: &mut BuildResultFlags)
                       -> Self;
+    fn from_svg_pattern(tree: &Tree,
+                        node: &Node,
+                        pattern: &UsvgPattern,
+                        bounding_box: RectF32,
+                        transform: &Transform2DF32,
+                        result_flags: &mut BuildResultFlags)
+                        -> Self;
 }
 
 impl PaintExt for Paint {
-    #[inline]
-    fn from_svg_paint(svg_paint: &UsvgPaint, opacity: Opacity, result_flags: &mut BuildResultFlags)
+    fn from_svg_paint(tree: &Tree,
+                      svg_paint: &UsvgPaint,
+                      opacity: Opacity,
+                      bounding_box: RectF32,
+                      transform: &Transform2DF32,
+                      result_flags: &mut BuildResultFlags)
                       -> Paint {
-        Paint {
-            color: match *svg_paint {
-                UsvgPaint::Color(color) => ColorU::from_svg_color(color, opacity),
-                UsvgPaint::Link(_) => {
-                    // TODO(pcwalton)
-                    result_flags.insert(BuildResultFlags::UNSUPPORTED_LINK_PAINT);
-                    ColorU::black()
-                }
-            },
+        let id = match *svg_paint {
+            UsvgPaint::Color(color) => return Paint::Color(ColorU::from_svg_color(color, opacity)),
+            UsvgPaint::Link(ref id) => id,
+        };
+
+        let node = match tree.defs_by_id(id) {
+            Some(node) => node,
+            None => {
+                result_flags.insert(BuildResultFlags::UNSUPPORTED_LINK_PAINT);
+                return Paint::Color(ColorU::black());
+            }
+        };
+
+        let kind = node.borrow();
+        let (mut gradient, base) = match *kind {
+            NodeKind::LinearGradient(ref linear) => {
+                let line = LineSegmentF32::new(
+                    Point2DF32::new(linear.x1 as f32, linear.y1 as f32),
+                    Point2DF32::new(linear.x2 as f32, linear.y2 as f32),
+                );
+                (Gradient::linear(line), &linear.base)
+            }
+            NodeKind::RadialGradient(ref radial) => {
+                let geometry = GradientGeometry::Radial {
+                    center: Point2DF32::new(radial.cx as f32, radial.cy as f32),
+                    radius: radial.r.value() as f32,
+                    focus: Point2DF32::new(radial.fx as f32, radial.fy as f32),
+                };
+                (Gradient::new(geometry), &radial.base)
+            }
+            NodeKind::Pattern(ref pattern) => {
+                let mut paint = Paint::from_svg_pattern(tree,
+                                                        &node,
+                                                        pattern,
+                                                        bounding_box,
+                                                        transform,
+                                                        result_flags);
+                paint.apply_opacity(opacity.value() as f32);
+                return paint;
+            }
+            _ => {
+                result_flags.insert(BuildResultFlags::UNSUPPORTED_LINK_PAINT);
+                return Paint::Color(ColorU::black());
+            }
+        };
+
+        for stop in &base.stops {
+            gradient.add_color_stop(stop.offset.value() as f32,
+                                    ColorU::from_svg_color(stop.color, stop.opacity));
+        }
+        gradient.set_spread(match base.spread_method {
+            UsvgSpreadMethod::Pad => SpreadMethod::Pad,
+            UsvgSpreadMethod::Reflect => SpreadMethod::Reflect,
+            UsvgSpreadMethod::Repeat => SpreadMethod::Repeat,
+        });
+
+        let mut gradient_transform = usvg_transform_to_transform_2d(&base.transform);
+        if let Units::ObjectBoundingBox = base.units {
+            let bounding_box_transform = Transform2DF32::from_scale(bounding_box.size())
+                .post_translate(bounding_box.origin());
+            gradient_transform = gradient_transform.post_mul(&bounding_box_transform);
+        }
+        gradient.set_transform(gradient_transform.post_mul(transform));
+
+        let mut paint = Paint::Gradient(gradient);
+        paint.apply_opacity(opacity.value() as f32);
+        paint
+    }
+
+    // Renders one tile of the pattern's contents into an image, at the resolution that the tile
+    // will be drawn at, and repeats it.
+    fn from_svg_pattern(tree: &Tree,
+                        node: &Node,
+                        pattern: &UsvgPattern,
+                        bounding_box: RectF32,
+                        transform: &Transform2DF32,
+                        result_flags: &mut BuildResultFlags)
+                        -> Paint {
+        let mut tile_rect = usvg_rect_to_euclid_rect(&pattern.rect);
+        if let Units::ObjectBoundingBox = pattern.units {
+            tile_rect = RectF32::new(
+                bounding_box.origin() + tile_rect.origin().scale_xy(bounding_box.size()),
+                tile_rect.size().scale_xy(bounding_box.size()),
+            );
+        }
+
+        let pattern_transform = usvg_transform_to_transform_2d(&pattern.transform)
+            .post_mul(transform);
+        let max_side = f32::max(tile_rect.size().x(), tile_rect.size().y());
+        let scale = f32::min(pattern_transform.scale_factor(), MAX_PATTERN_TILE_SIZE / max_side);
+        let tile_size = tile_rect.size().scale(scale);
+        let image_size = Point2DI32::new(f32::ceil(tile_size.x()) as i32,
+                                         f32::ceil(tile_size.y()) as i32);
+        if image_size.x() <= 0 || image_size.y() <= 0 {
+            return Paint::Color(ColorU::transparent_black());
         }
+
+        // Pattern contents are positioned relative to the origin of the tile. A view box
+        // overrides the content units.
+        let mut content_transform = Transform2DF32::from_scale(Point2DF32::splat(scale));
+        if let Some(ref view_box) = pattern.view_box {
+            let tile_bounds = RectF32::new(Point2DF32::default(), tile_rect.size());
+            content_transform = view_box_to_transform(usvg_rect_to_euclid_rect(&view_box.rect),
+                                                      &view_box.aspect,
+                                                      tile_bounds).post_mul(&content_transform);
+        } else if let Units::ObjectBoundingBox = pattern.content_units {
+            content_transform = Transform2DF32::from_scale(bounding_box.size())
+                .post_mul(&content_transform);
+        }
+
+        let mut tile = BuiltSVG { scene: Scene::new(), result_flags: BuildResultFlags::empty() };
+        tile.scene.set_view_box(RectF32::new(Point2DF32::default(), image_size.to_f32()));
+        for kid in node.children() {
+            tile.process_node(tree, &kid, &content_transform, None);
+        }
+        result_flags.insert(tile.result_flags);
+
+        let pixels = match tile.scene.render_to_pixels(RenderOptions::default(),
+                                                       image_size,
+                                                       &SequentialExecutor) {
+            Ok(pixels) => pixels,
+            Err(_) => {
+                result_flags.insert(BuildResultFlags::UNSUPPORTED_PATTERN_NODE);
+                return Paint::Color(ColorU::transparent_black());
+            }
+        };
+        let mut paint_pattern = Pattern::new(Image::new(image_size, pixels), RepeatMode::Repeat);
+        paint_pattern.set_transform(Transform2DF32::from_scale(Point2DF32::splat(1.0 / scale))
+            .post_translate(tile_rect.origin())
+            .post_mul(&pattern_transform));
+        Paint::Pattern(paint_pattern)
     }
 }
 
+// Decodes the pixels of an `<image>` element. Returns `None` for nested SVG images and for images
+// that fail to load.
+fn decode_usvg_image(usvg_image: &UsvgImage) -> Option<Image> {
+    let format = match usvg_image.format {
+        ImageFormat::PNG => image::ImageFormat::PNG,
+        ImageFormat::JPEG => image::ImageFormat::JPEG,
+        ImageFormat::SVG => return None,
+    };
+    let decoded = match usvg_image.data {
+        ImageData::Raw(ref data) => image::load_from_memory_with_format(data, format).ok()?,
+        ImageData::Path(ref path) => image::open(path).ok()?,
+    };
+    let decoded = decoded.to_rgba();
+    let size = Point2DI32::new(decoded.width() as i32, decoded.height() as i32);
+    Some(Image::new(size, decoded.into_raw()))
+}
+
+fn rect_to_segments(rect: RectF32) -> impl Iterator<Item = Segment> {
+    let points = [rect.origin(), rect.upper_right(), rect.lower_right(), rect.lower_left()];
+    (0..4).map(move |index| {
+        let mut segment = Segment::line(&LineSegmentF32::new(points[index],
+                                                             points[(index + 1) % 4]));
+        if index == 0 {
+            segment.flags.insert(SegmentFlags::FIRST_IN_SUBPATH);
+        } else if index == 3 {
+            segment.flags.insert(SegmentFlags::CLOSES_SUBPATH);
+        }
+        segment
+    })
+}
+
 fn usvg_rect_to_euclid_rect(rect: &UsvgRect) -> RectF32 {
     RectF32::new(
         Point2DF32::new(rect.x as f32, rect.y as f32),
@@ -283,6 +581,42 @@ fn usvg_transform_to_transform_2d(transform: &UsvgTransform) -> Transform2DF32 {
     )
 }
 
+// Maps a view box into a viewport the way `preserveAspectRatio` says to: stretched to fill it, or
+// scaled uniformly to fit inside it (or to cover it, when slicing) and then aligned.
+fn view_box_to_transform(view_box: RectF32, aspect: &AspectRatio, viewport: RectF32)
+                         -> Transform2DF32 {
+    let mut scale = Point2DF32::new(viewport.size().x() / view_box.size().x(),
+                                    viewport.size().y() / view_box.size().y());
+    let alignment = match aspect.align {
+        Align::None => None,
+        Align::XMinYMin => Some(Point2DF32::new(0.0, 0.0)),
+        Align::XMidYMin => Some(Point2DF32::new(0.5, 0.0)),
+        Align::XMaxYMin => Some(Point2DF32::new(1.0, 0.0)),
+        Align::XMinYMid => Some(Point2DF32::new(0.0, 0.5)),
+        Align::XMidYMid => Some(Point2DF32::new(0.5, 0.5)),
+        Align::XMaxYMid => Some(Point2DF32::new(1.0, 0.5)),
+        Align::XMinYMax => Some(Point2DF32::new(0.0, 1.0)),
+        Align::XMidYMax => Some(Point2DF32::new(0.5, 1.0)),
+        Align::XMaxYMax => Some(Point2DF32::new(1.0, 1.0)),
+    };
+
+    let mut origin = viewport.origin();
+    if let Some(alignment) = alignment {
+        let uniform_scale = if aspect.slice {
+            f32::max(scale.x(), scale.y())
+        } else {
+            f32::min(scale.x(), scale.y())
+        };
+        scale = Point2DF32::splat(uniform_scale);
+        let slack = viewport.size() - view_box.size().scale(uniform_scale);
+        origin = origin + slack.scale_xy(alignment);
+    }
+
+    Transform2DF32::from_translation(-view_box.origin())
+        .post_mul(&Transform2DF32::from_scale(scale))
+        .post_translate(origin)
+}
+
 struct UsvgPathToSegments<I>
 where
     I: Iterator<Item = UsvgPathSegment>,
@@ -393,10 +727,7 @@ impl LineCapExt for LineCap {
     fn from_usvg_line_cap(usvg_line_cap: UsvgLineCap) -> LineCap {
         match usvg_line_cap {
             UsvgLineCap::Butt => LineCap::Butt,
-            UsvgLineCap::Round => {
-                // TODO(pcwalton)
-                LineCap::Square
-            }
+            UsvgLineCap::Round => LineCap::Round,
             UsvgLineCap::Square => LineCap::Square,
         }
     }
@@ -411,11 +742,22 @@ impl LineJoinExt for LineJoin {
     fn from_usvg_line_join(usvg_line_join: UsvgLineJoin) -> LineJoin {
         match usvg_line_join {
             UsvgLineJoin::Miter => LineJoin::Miter,
-            UsvgLineJoin::Round => {
-                // TODO(pcwalton)
-                LineJoin::Miter
-            }
+            UsvgLineJoin::Round => LineJoin::Round,
             UsvgLineJoin::Bevel => LineJoin::Bevel,
         }
     }
 }
+
+trait FillRuleExt {
+    fn from_usvg_fill_rule(usvg_fill_rule: UsvgFillRule) -> Self;
+}
+
+impl FillRuleExt for FillRule {
+    #[inline]
+    fn from_usvg_fill_rule(usvg_fill_rule: UsvgFillRule) -> FillRule {
+        match usvg_fill_rule {
+            UsvgFillRule::NonZero => FillRule::Winding,
+            UsvgFillRule::EvenOdd => FillRule::EvenOdd,
+        }
+    }
+}
@@ -18,9 +18,11 @@ use pathfinder_geometry::basic::transform2d::Transform2DF32;
 use pathfinder_geometry::outline::{Contour, Outline};
 use pathfinder_geometry::stroke::{OutlineStrokeToFill, StrokeStyle};
 use pathfinder_renderer::paint::PaintId;
-use pathfinder_renderer::scene::{PathObject, Scene};
+use pathfinder_renderer::scene::{ClipPathId, PathObject, Scene};
 use skribo::{FontCollection, Layout, TextStyle};
+use std::collections::hash_map::{Entry, HashMap};
 use std::mem;
+use std::sync::Arc;
 
 pub trait SceneExt {
     // TODO(pcwalton): Support stroked glyphs.
@@ -30,7 +32,8 @@ pub trait SceneExt {
                      transform: &Transform2DF32,
                      render_mode: TextRenderMode,
                      hinting_options: HintingOptions,
-                     paint_id: PaintId)
+                     paint_id: PaintId,
+                     clip_path: Option<ClipPathId>)
                      -> Result<(), GlyphLoadingError>
                      where F: Loader;
 
@@ -40,7 +43,8 @@ pub trait SceneExt {
                    transform: &Transform2DF32,
                    render_mode: TextRenderMode,
                    hinting_options: HintingOptions,
-                   paint_id: PaintId)
+                   paint_id: PaintId,
+                   clip_path: Option<ClipPathId>)
                    -> Result<(), GlyphLoadingError>;
 
     fn push_text(&mut self,
@@ -50,7 +54,8 @@ pub trait SceneExt {
                  transform: &Transform2DF32,
                  render_mode: TextRenderMode,
                  hinting_options: HintingOptions,
-                 paint_id: PaintId)
+                 paint_id: PaintId,
+                 clip_path: Option<ClipPathId>)
                  -> Result<(), GlyphLoadingError>;
 }
 
@@ -62,20 +67,12 @@ impl SceneExt for Scene {
                      transform: &Transform2DF32,
                      render_mode: TextRenderMode,
                      hinting_options: HintingOptions,
-                     paint_id: PaintId)
+                     paint_id: PaintId,
+                     clip_path: Option<ClipPathId>)
                      -> Result<(), GlyphLoadingError>
                      where F: Loader {
-        let mut outline_builder = OutlinePathBuilder::new(transform);
-        font.outline(glyph_id, hinting_options, &mut outline_builder)?;
-        let mut outline = outline_builder.build();
-
-        if let TextRenderMode::Stroke(stroke_style) = render_mode {
-            let mut stroke_to_fill = OutlineStrokeToFill::new(outline, stroke_style);
-            stroke_to_fill.offset();
-            outline = stroke_to_fill.outline;
-        }
-
-        self.push_path(PathObject::new(outline, paint_id, String::new()));
+        let outline = load_glyph_outline(font, glyph_id, hinting_options)?;
+        push_glyph_outline(self, Arc::new(outline), transform, render_mode, paint_id, clip_path);
         Ok(())
     }
 
@@ -85,8 +82,12 @@ impl SceneExt for Scene {
                    transform: &Transform2DF32,
                    render_mode: TextRenderMode,
                    hinting_options: HintingOptions,
-                   paint_id: PaintId)
+                   paint_id: PaintId,
+                   clip_path: Option<ClipPathId>)
                    -> Result<(), GlyphLoadingError> {
+        // Glyphs that appear more than once in the layout share one outline, keyed by the
+        // address of their font, which the layout keeps alive.
+        let mut outline_cache: HashMap<(usize, u32), Arc<Outline>> = HashMap::new();
         for glyph in &layout.glyphs {
             let offset = Point2DF32::new(glyph.offset.x, glyph.offset.y);
             let font = &*glyph.font.font;
@@ -95,12 +96,21 @@ impl SceneExt for Scene {
             let scale = Point2DF32::new(scale, -scale);
             let transform =
                 Transform2DF32::from_scale(scale).post_mul(transform).post_translate(offset);
-            self.push_glyph(font,
-                            glyph.glyph_id,
-                            &transform,
-                            render_mode,
-                            hinting_options,
-                            paint_id)?;
+
+            let cache_key = (font as *const _ as usize, glyph.glyph_id);
+            let outline = match outline_cache.entry(cache_key) {
+                Entry::Occupied(entry) => entry.get().clone(),
+                Entry::Vacant(entry) => {
+                    let outline = load_glyph_outline(font, glyph.glyph_id, hinting_options)?;
+                    entry.insert(Arc::new(outline)).clone()
+                }
+            };
+            push_glyph_outline(self,
+                               outline,
+                               &transform,
+                               render_mode.clone(),
+                               paint_id,
+                               clip_path);
         }
         Ok(())
     }
@@ -113,14 +123,57 @@ impl SceneExt for Scene {
                  transform: &Transform2DF32,
                  render_mode: TextRenderMode,
                  hinting_options: HintingOptions,
-                 paint_id: PaintId)
+                 paint_id: PaintId,
+                 clip_path: Option<ClipPathId>)
                  -> Result<(), GlyphLoadingError> {
         let layout = skribo::layout(style, collection, text);
-        self.push_layout(&layout, style, &transform, render_mode, hinting_options, paint_id)
+        self.push_layout(&layout,
+                         style,
+                         &transform,
+                         render_mode,
+                         hinting_options,
+                         paint_id,
+                         clip_path)
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+fn load_glyph_outline<F>(font: &F, glyph_id: u32, hinting_options: HintingOptions)
+                         -> Result<Outline, GlyphLoadingError>
+                         where F: Loader {
+    let mut outline_builder = OutlinePathBuilder::new();
+    font.outline(glyph_id, hinting_options, &mut outline_builder)?;
+    Ok(outline_builder.build())
+}
+
+// Pushes a glyph whose outline is in font units. A filled glyph keeps its outline, which may be
+// shared with other glyphs, and is placed by the renderer. A stroked one can't: the stroke width
+// is in the units the glyph is transformed to, so the transform has to be baked in first.
+fn push_glyph_outline(scene: &mut Scene,
+                      outline: Arc<Outline>,
+                      transform: &Transform2DF32,
+                      render_mode: TextRenderMode,
+                      paint_id: PaintId,
+                      clip_path: Option<ClipPathId>) {
+    let mut path = match render_mode {
+        TextRenderMode::Fill => {
+            let mut path = PathObject::from_shared_outline(outline, paint_id, String::new());
+            path.set_transform(*transform);
+            path
+        }
+        TextRenderMode::Stroke(stroke_style) => {
+            let mut outline = (*outline).clone();
+            outline.transform(transform);
+            let mut stroke_to_fill = OutlineStrokeToFill::new(outline, stroke_style);
+            stroke_to_fill.offset();
+            PathObject::new(stroke_to_fill.outline, paint_id, String::new())
+        }
+    };
+    path.set_clip_path(clip_path);
+    scene.push_path(path);
+}
+
+/// How glyphs are drawn. Like `StrokeStyle`, this isn't `Copy`, but it's cheap to clone.
+#[derive(Clone, PartialEq, Debug)]
 pub enum TextRenderMode {
     Fill,
     Stroke(StrokeStyle),
@@ -129,16 +182,11 @@ pub enum TextRenderMode {
 struct OutlinePathBuilder {
     outline: Outline,
     current_contour: Contour,
-    transform: Transform2DF32,
 }
 
 impl OutlinePathBuilder {
-    fn new(transform: &Transform2DF32) -> OutlinePathBuilder {
-        OutlinePathBuilder {
-            outline: Outline::new(),
-            current_contour: Contour::new(),
-            transform: *transform,
-        }
+    fn new() -> OutlinePathBuilder {
+        OutlinePathBuilder { outline: Outline::new(), current_contour: Contour::new() }
     }
 
     fn flush_current_contour(&mut self) {
@@ -148,7 +196,7 @@ impl OutlinePathBuilder {
     }
 
     fn convert_point(&self, point: Point2D<f32>) -> Point2DF32 {
-        self.transform.transform_point(Point2DF32::new(point.x, point.y))
+        Point2DF32::new(point.x, point.y)
     }
 }
 